@@ -0,0 +1,657 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::bytes::{be_u16, be_u32};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::registry::{ChunkData, DecodeError};
+
+/// Why an animated PNG is not valid, or a frame could not be found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ApngError {
+    /// There is no `acTL` chunk.
+    NotAnimated,
+    /// There is no `IHDR` chunk to check frame bounds against.
+    MissingHeader,
+    /// An `acTL`, `fcTL` or `fdAT` chunk has the wrong size.
+    InvalidLength { chunk_type: String, length: usize },
+    /// `fcTL` and `fdAT` sequence numbers must count up from 0 without gaps.
+    BadSequence { expected: u32, found: u32 },
+    /// The frame count in `acTL` does not match the `fcTL` chunks.
+    FrameCountMismatch { declared: u32, found: usize },
+    /// The frame region does not fit the canvas, or is empty.
+    FrameOutOfBounds { sequence_number: u32 },
+    /// The dispose or blend op is not one the spec defines.
+    InvalidOp { sequence_number: u32 },
+    /// An `fdAT` chunk comes before any `fcTL`.
+    OrphanFrameData { sequence_number: u32 },
+    /// A frame has no image data.
+    EmptyFrame { sequence_number: u32 },
+    /// There is no frame with this index.
+    FrameNotFound(usize),
+    /// An animation needs at least one frame.
+    NoFrames,
+    /// Frame `index` has a different `IHDR` or `PLTE` than the first frame.
+    HeaderMismatch { index: usize },
+}
+
+impl Display for ApngError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApngError::NotAnimated => write!(f, "not an animated png: no acTL chunk"),
+            ApngError::MissingHeader => write!(f, "no IHDR chunk found"),
+            ApngError::InvalidLength { chunk_type, length } => {
+                write!(f, "{} chunk has invalid length {}", chunk_type, length)
+            },
+            ApngError::BadSequence { expected, found } => {
+                write!(f, "expected sequence number {}, found {}", expected, found)
+            },
+            ApngError::FrameCountMismatch { declared, found } => {
+                write!(f, "acTL declares {} frames but {} were found", declared, found)
+            },
+            ApngError::FrameOutOfBounds { sequence_number } => {
+                write!(f, "frame at sequence number {} does not fit the canvas", sequence_number)
+            },
+            ApngError::InvalidOp { sequence_number } => {
+                write!(f, "frame at sequence number {} has an unknown dispose or blend op", sequence_number)
+            },
+            ApngError::OrphanFrameData { sequence_number } => {
+                write!(f, "fdAT at sequence number {} does not follow an fcTL", sequence_number)
+            },
+            ApngError::EmptyFrame { sequence_number } => {
+                write!(f, "frame at sequence number {} has no image data", sequence_number)
+            },
+            ApngError::FrameNotFound(index) => write!(f, "no frame {}", index),
+            ApngError::NoFrames => write!(f, "an animation needs at least one frame"),
+            ApngError::HeaderMismatch { index } => {
+                write!(f, "frame {} does not match the size, color type or palette of frame 0", index)
+            },
+        }
+    }
+}
+
+impl std::error::Error for ApngError {}
+
+/// The `acTL` chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    /// 0 means loop forever.
+    pub num_plays: u32,
+}
+
+impl TryFrom<&[u8]> for AnimationControl {
+    type Error = ApngError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != 8 {
+            return Err(ApngError::InvalidLength { chunk_type: "acTL".to_string(), length: data.len() });
+        }
+        Ok(AnimationControl { num_frames: be_u32(data, 0), num_plays: be_u32(data, 4) })
+    }
+}
+
+impl Display for AnimationControl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.num_plays == 0 {
+            write!(f, "{} frames, loops forever", self.num_frames)
+        } else {
+            write!(f, "{} frames, {} plays", self.num_frames, self.num_plays)
+        }
+    }
+}
+
+impl AnimationControl {
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = self.num_frames.to_be_bytes().to_vec();
+        data.extend_from_slice(&self.num_plays.to_be_bytes());
+        Chunk::new(ChunkType::from_str("acTL").unwrap(), data)
+    }
+}
+
+impl ChunkData for AnimationControl {
+    const CHUNK_TYPES: &'static [&'static str] = &["acTL"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        AnimationControl::try_from(chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        self.to_chunk()
+    }
+
+    fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// How the frame region is treated before the next frame is rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisposeOp {
+    None,
+    Background,
+    Previous,
+}
+
+/// Whether the frame replaces or is composited over the output buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendOp {
+    Source,
+    Over,
+}
+
+impl Display for DisposeOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DisposeOp::None => "none",
+            DisposeOp::Background => "background",
+            DisposeOp::Previous => "previous",
+        };
+        f.pad(name)
+    }
+}
+
+impl Display for BlendOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BlendOp::Source => "source",
+            BlendOp::Over => "over",
+        };
+        f.pad(name)
+    }
+}
+
+/// The `fcTL` chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl TryFrom<&[u8]> for FrameControl {
+    type Error = ApngError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != 26 {
+            return Err(ApngError::InvalidLength { chunk_type: "fcTL".to_string(), length: data.len() });
+        }
+        let sequence_number = be_u32(data, 0);
+        let dispose_op = match data[24] {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            _ => return Err(ApngError::InvalidOp { sequence_number }),
+        };
+        let blend_op = match data[25] {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            _ => return Err(ApngError::InvalidOp { sequence_number }),
+        };
+        Ok(FrameControl {
+            sequence_number,
+            width: be_u32(data, 4),
+            height: be_u32(data, 8),
+            x_offset: be_u32(data, 12),
+            y_offset: be_u32(data, 16),
+            delay_num: be_u16(data, 20),
+            delay_den: be_u16(data, 22),
+            dispose_op,
+            blend_op,
+        })
+    }
+}
+
+impl FrameControl {
+    /// The frame delay in seconds. A denominator of 0 means 1/100s units.
+    pub fn delay(&self) -> f64 {
+        let den = if self.delay_den == 0 { 100 } else { self.delay_den };
+        self.delay_num as f64 / den as f64
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = vec![];
+        for value in [self.sequence_number, self.width, self.height, self.x_offset, self.y_offset] {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        data.extend_from_slice(&self.delay_num.to_be_bytes());
+        data.extend_from_slice(&self.delay_den.to_be_bytes());
+        data.push(self.dispose_op as u8);
+        data.push(self.blend_op as u8);
+        Chunk::new(ChunkType::from_str("fcTL").unwrap(), data)
+    }
+}
+
+impl Display for FrameControl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{} {}x{} at {},{}, delay {}/{}s, dispose {}, blend {}",
+               self.sequence_number, self.width, self.height, self.x_offset, self.y_offset,
+               self.delay_num, if self.delay_den == 0 { 100 } else { self.delay_den },
+               self.dispose_op, self.blend_op)
+    }
+}
+
+impl ChunkData for FrameControl {
+    const CHUNK_TYPES: &'static [&'static str] = &["fcTL"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        FrameControl::try_from(chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        self.to_chunk()
+    }
+
+    fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// The `fdAT` chunk: a sequence number followed by compressed image data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameData {
+    pub sequence_number: u32,
+    pub data: Vec<u8>,
+}
+
+impl TryFrom<&[u8]> for FrameData {
+    type Error = ApngError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() < 4 {
+            return Err(ApngError::InvalidLength { chunk_type: "fdAT".to_string(), length: data.len() });
+        }
+        Ok(FrameData { sequence_number: be_u32(data, 0), data: data[4..].to_vec() })
+    }
+}
+
+impl FrameData {
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = self.sequence_number.to_be_bytes().to_vec();
+        data.extend_from_slice(&self.data);
+        Chunk::new(ChunkType::from_str("fdAT").unwrap(), data)
+    }
+}
+
+impl ChunkData for FrameData {
+    const CHUNK_TYPES: &'static [&'static str] = &["fdAT"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        FrameData::try_from(chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        self.to_chunk()
+    }
+
+    fn summary(&self) -> String {
+        format!("#{}", self.sequence_number)
+    }
+}
+
+/// One frame of an animation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub control: FrameControl,
+    /// Index of the frame's `fcTL` in `Png::chunks`.
+    pub chunk_index: usize,
+    /// The compressed image data: `IDAT` payloads for the default image,
+    /// `fdAT` payloads without their sequence numbers otherwise.
+    pub data: Vec<Vec<u8>>,
+    /// The frame is the `IDAT` image shown by decoders without APNG support.
+    pub is_default_image: bool,
+}
+
+/// The typed view of an animated PNG's `acTL`, `fcTL` and `fdAT` chunks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Animation {
+    pub control: AnimationControl,
+    pub frames: Vec<Frame>,
+}
+
+impl Animation {
+    pub fn is_animated(png: &Png) -> bool {
+        png.chunk_by_type("acTL").is_some()
+    }
+
+    /// Reads and validates the animation: sequence numbers, the declared
+    /// frame count, frame bounds and that every frame has data.
+    pub fn from_png(png: &Png) -> Result<Animation, ApngError> {
+        let control = AnimationControl::try_from(png.chunk_by_type("acTL").ok_or(ApngError::NotAnimated)?.data())?;
+        let ihdr = png.chunk_by_type("IHDR").filter(|chunk| chunk.length() == 13).ok_or(ApngError::MissingHeader)?;
+        let (canvas_width, canvas_height) = (be_u32(ihdr.data(), 0), be_u32(ihdr.data(), 4));
+
+        let mut frames: Vec<Frame> = vec![];
+        let mut next_sequence = 0;
+        let mut seen_idat = false;
+        let mut check_sequence = |found: u32| {
+            if found != next_sequence {
+                return Err(ApngError::BadSequence { expected: next_sequence, found });
+            }
+            next_sequence += 1;
+            Ok(())
+        };
+        for (idx, chunk) in png.chunks().iter().enumerate() {
+            match &chunk.chunk_type().bytes() {
+                b"fcTL" => {
+                    let frame_control = FrameControl::try_from(chunk.data())?;
+                    check_sequence(frame_control.sequence_number)?;
+                    frames.push(Frame {
+                        control: frame_control,
+                        chunk_index: idx,
+                        data: vec![],
+                        is_default_image: !seen_idat,
+                    });
+                },
+                b"fdAT" => {
+                    let frame_data = FrameData::try_from(chunk.data())?;
+                    check_sequence(frame_data.sequence_number)?;
+                    match frames.last_mut() {
+                        Some(frame) if !frame.is_default_image => frame.data.push(frame_data.data),
+                        _ => return Err(ApngError::OrphanFrameData { sequence_number: frame_data.sequence_number }),
+                    }
+                },
+                b"IDAT" => {
+                    seen_idat = true;
+                    if let Some(frame) = frames.last_mut().filter(|frame| frame.is_default_image) {
+                        frame.data.push(chunk.data().to_vec());
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        if control.num_frames as usize != frames.len() {
+            return Err(ApngError::FrameCountMismatch { declared: control.num_frames, found: frames.len() });
+        }
+        for frame in &frames {
+            let fc = &frame.control;
+            let fits = fc.width > 0
+                && fc.height > 0
+                && fc.x_offset.checked_add(fc.width).is_some_and(|right| right <= canvas_width)
+                && fc.y_offset.checked_add(fc.height).is_some_and(|bottom| bottom <= canvas_height);
+            let covers_canvas = fc.x_offset == 0 && fc.y_offset == 0
+                && fc.width == canvas_width && fc.height == canvas_height;
+            if !fits || (frame.is_default_image && !covers_canvas) {
+                return Err(ApngError::FrameOutOfBounds { sequence_number: fc.sequence_number });
+            }
+            if frame.data.is_empty() {
+                return Err(ApngError::EmptyFrame { sequence_number: fc.sequence_number });
+            }
+        }
+        Ok(Animation { control, frames })
+    }
+
+    /// Assembles an animation from full-size PNG frames, each shown for its
+    /// delay in milliseconds. The first frame becomes the default image and
+    /// keeps its `IDAT`; the others are converted to `fdAT`. Every frame must
+    /// share the first frame's `IHDR` and `PLTE`.
+    pub fn build(frames: &[(&Png, u16)], num_plays: u32) -> Result<Png, ApngError> {
+        let (first, _) = frames.first().ok_or(ApngError::NoFrames)?;
+        let ihdr = first.chunk_by_type("IHDR").filter(|chunk| chunk.length() == 13).ok_or(ApngError::MissingHeader)?;
+        let palette = |png: &Png| png.chunk_by_type("PLTE").map(|chunk| chunk.data().to_vec());
+        let (width, height) = (be_u32(ihdr.data(), 0), be_u32(ihdr.data(), 4));
+
+        let mut chunks = vec![ihdr.clone(), AnimationControl { num_frames: frames.len() as u32, num_plays }.to_chunk()];
+        chunks.extend(
+            first
+                .chunks()
+                .iter()
+                .skip_while(|chunk| &chunk.chunk_type().bytes() == b"IHDR")
+                .take_while(|chunk| !matches!(&chunk.chunk_type().bytes(), b"IDAT" | b"IEND"))
+                .filter(|chunk| !matches!(&chunk.chunk_type().bytes(), b"acTL" | b"fcTL" | b"fdAT"))
+                .cloned(),
+        );
+        let mut sequence_number = 0;
+        for (index, (png, delay)) in frames.iter().enumerate() {
+            if png.chunk_by_type("IHDR") != Some(ihdr) || palette(png) != palette(first) {
+                return Err(ApngError::HeaderMismatch { index });
+            }
+            let control = FrameControl {
+                sequence_number,
+                width,
+                height,
+                x_offset: 0,
+                y_offset: 0,
+                delay_num: *delay,
+                delay_den: 1000,
+                dispose_op: DisposeOp::None,
+                blend_op: BlendOp::Source,
+            };
+            chunks.push(control.to_chunk());
+            sequence_number += 1;
+            for idat in png.chunks().iter().filter(|chunk| &chunk.chunk_type().bytes() == b"IDAT") {
+                if index == 0 {
+                    chunks.push(idat.clone());
+                } else {
+                    chunks.push(FrameData { sequence_number, data: idat.data().to_vec() }.to_chunk());
+                    sequence_number += 1;
+                }
+            }
+        }
+        chunks.push(Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]));
+
+        let png = Png::from_chunks(chunks);
+        Animation::from_png(&png)?;
+        Ok(png)
+    }
+
+    pub fn frame(&self, index: usize) -> Result<&Frame, ApngError> {
+        self.frames.get(index).ok_or(ApngError::FrameNotFound(index))
+    }
+
+    /// The range of chunk indices that belong to frame `index`: from its
+    /// `fcTL` up to the next `fcTL` or `IEND`.
+    fn frame_chunks(&self, png: &Png, index: usize) -> Result<std::ops::Range<usize>, ApngError> {
+        let start = self.frame(index)?.chunk_index;
+        let end = match self.frames.get(index + 1) {
+            Some(next) => next.chunk_index,
+            None => png
+                .chunks()
+                .iter()
+                .position(|chunk| &chunk.chunk_type().bytes() == b"IEND")
+                .unwrap_or(png.chunks().len()),
+        };
+        Ok(start..end)
+    }
+
+    /// Builds a standalone PNG from frame `index`: the frame's own size and
+    /// image data, with the palette and other chunks that come before the
+    /// image data. The frame is not composited onto earlier frames.
+    pub fn extract_frame(&self, png: &Png, index: usize) -> Result<Png, ApngError> {
+        let frame = self.frame(index)?;
+        let mut chunks = vec![];
+        for chunk in png.chunks() {
+            match &chunk.chunk_type().bytes() {
+                b"IHDR" => {
+                    let mut data = chunk.data().to_vec();
+                    data[0..4].copy_from_slice(&frame.control.width.to_be_bytes());
+                    data[4..8].copy_from_slice(&frame.control.height.to_be_bytes());
+                    chunks.push(Chunk::new(*chunk.chunk_type(), data));
+                },
+                b"IDAT" | b"fdAT" | b"IEND" => break,
+                b"acTL" | b"fcTL" => {},
+                _ => chunks.push(chunk.clone()),
+            }
+        }
+        let idat = ChunkType::from_str("IDAT").unwrap();
+        chunks.extend(frame.data.iter().map(|data| Chunk::new(idat, data.clone())));
+        chunks.push(Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]));
+        Ok(Png::from_chunks(chunks))
+    }
+
+    /// Stores `chunk` right after the `fcTL` of frame `index`. Only `fcTL`
+    /// and `fdAT` carry sequence numbers, so the animation stays valid.
+    pub fn insert_frame_chunk(&self, png: &mut Png, index: usize, chunk: Chunk) -> Result<(), ApngError> {
+        let position = self.frame(index)?.chunk_index + 1;
+        png.insert_chunk(position, chunk);
+        Ok(())
+    }
+
+    /// Finds the first chunk of `chunk_type` that belongs to frame `index`.
+    pub fn frame_chunk<'a>(&self, png: &'a Png, index: usize, chunk_type: &ChunkType) -> Result<Option<&'a Chunk>, ApngError> {
+        let range = self.frame_chunks(png, index)?;
+        Ok(png.chunks()[range].iter().find(|chunk| chunk.chunk_type() == chunk_type))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    fn frame_control(sequence_number: u32, width: u32, x_offset: u32) -> FrameControl {
+        FrameControl {
+            sequence_number,
+            width,
+            height: 2,
+            x_offset,
+            y_offset: 0,
+            delay_num: 1,
+            delay_den: 10,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Over,
+        }
+    }
+
+    fn fd(sequence_number: u32, data: &[u8]) -> Chunk {
+        FrameData { sequence_number, data: data.to_vec() }.to_chunk()
+    }
+
+    fn testing_apng() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", vec![0, 0, 0, 4, 0, 0, 0, 2, 8, 6, 0, 0, 0]),
+            AnimationControl { num_frames: 2, num_plays: 0 }.to_chunk(),
+            chunk("gAMA", vec![0, 0, 177, 143]),
+            frame_control(0, 4, 0).to_chunk(),
+            chunk("IDAT", vec![1, 2, 3]),
+            chunk("IDAT", vec![4]),
+            frame_control(1, 2, 1).to_chunk(),
+            fd(2, &[5, 6]),
+            chunk("IEND", vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_typed_chunks_round_trip() {
+        let fc = frame_control(3, 4, 0);
+        assert_eq!(FrameControl::try_from(fc.to_chunk().data()).unwrap(), fc);
+        let ac = AnimationControl { num_frames: 2, num_plays: 3 };
+        assert_eq!(AnimationControl::try_from(ac.to_chunk().data()).unwrap(), ac);
+        assert_eq!(ac.to_string(), "2 frames, 3 plays");
+        assert_eq!(FrameData::try_from(&[0, 0, 0, 7, 9][..]).unwrap(), FrameData { sequence_number: 7, data: vec![9] });
+        assert!(matches!(FrameData::try_from(&[0, 0, 7][..]), Err(ApngError::InvalidLength { .. })));
+        assert_eq!(fc.delay(), 0.1);
+        assert_eq!(fc.to_string(), "#3 4x2 at 0,0, delay 1/10s, dispose none, blend over");
+    }
+
+    #[test]
+    fn test_from_png() {
+        let animation = Animation::from_png(&testing_apng()).unwrap();
+        assert_eq!(animation.frames.len(), 2);
+        assert!(animation.frames[0].is_default_image);
+        assert_eq!(animation.frames[0].data, vec![vec![1, 2, 3], vec![4]]);
+        assert_eq!(animation.frames[1].data, vec![vec![5, 6]]);
+        assert_eq!(animation.frames[1].chunk_index, 6);
+    }
+
+    #[test]
+    fn test_validation() {
+        let mut png = testing_apng();
+        png.strip_chunks(|chunk| &chunk.chunk_type().bytes() == b"fdAT");
+        assert_eq!(Animation::from_png(&png), Err(ApngError::EmptyFrame { sequence_number: 1 }));
+
+        let mut png = testing_apng();
+        png.strip_chunks(|chunk| &chunk.chunk_type().bytes() == b"fdAT");
+        png.append_chunk(fd(3, &[5]));
+        assert_eq!(Animation::from_png(&png), Err(ApngError::BadSequence { expected: 2, found: 3 }));
+
+        let mut png = testing_apng();
+        png.insert_chunk(2, fd(0, &[1]));
+        assert_eq!(Animation::from_png(&png), Err(ApngError::OrphanFrameData { sequence_number: 0 }));
+
+        let png = Png::from_chunks(vec![chunk("IHDR", vec![0; 13])]);
+        assert_eq!(Animation::from_png(&png), Err(ApngError::NotAnimated));
+
+        let mut png = testing_apng();
+        png.remove_chunk_at(6);
+        png.insert_chunk(6, frame_control(1, 2, 3).to_chunk());
+        assert_eq!(Animation::from_png(&png), Err(ApngError::FrameOutOfBounds { sequence_number: 1 }));
+    }
+
+    #[test]
+    fn test_frame_count_mismatch() {
+        let mut png = testing_apng();
+        png.strip_chunks(|chunk| &chunk.chunk_type().bytes() == b"acTL");
+        png.insert_chunk(1, AnimationControl { num_frames: 3, num_plays: 0 }.to_chunk());
+        assert_eq!(Animation::from_png(&png), Err(ApngError::FrameCountMismatch { declared: 3, found: 2 }));
+    }
+
+    #[test]
+    fn test_extract_frame() {
+        let png = testing_apng();
+        let animation = Animation::from_png(&png).unwrap();
+        let frame = animation.extract_frame(&png, 1).unwrap();
+        let types: Vec<String> = frame.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, vec!["IHDR", "gAMA", "IDAT", "IEND"]);
+        assert_eq!(&frame.chunks()[0].data()[0..8], &[0, 0, 0, 2, 0, 0, 0, 2]);
+        assert_eq!(frame.chunks()[2].data(), &[5, 6]);
+        assert_eq!(animation.extract_frame(&png, 2), Err(ApngError::FrameNotFound(2)));
+    }
+
+    #[test]
+    fn test_frame_messages() {
+        let mut png = testing_apng();
+        let animation = Animation::from_png(&png).unwrap();
+        animation.insert_frame_chunk(&mut png, 1, chunk("ruSt", b"second".to_vec())).unwrap();
+        animation.insert_frame_chunk(&mut png, 0, chunk("ruSt", b"first".to_vec())).unwrap();
+
+        let animation = Animation::from_png(&png).unwrap();
+        let rust = ChunkType::from_str("ruSt").unwrap();
+        assert_eq!(animation.frame_chunk(&png, 0, &rust).unwrap().unwrap().data(), b"first");
+        assert_eq!(animation.frame_chunk(&png, 1, &rust).unwrap().unwrap().data(), b"second");
+    }
+
+    fn still(data: &[u8]) -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", vec![0, 0, 0, 4, 0, 0, 0, 2, 8, 6, 0, 0, 0]),
+            chunk("sRGB", vec![0]),
+            chunk("IDAT", data.to_vec()),
+            chunk("IDAT", vec![9]),
+            chunk("IEND", vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_build() {
+        let (first, second) = (still(&[1]), still(&[2]));
+        let png = Animation::build(&[(&first, 100), (&second, 250)], 3).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, vec!["IHDR", "acTL", "sRGB", "fcTL", "IDAT", "IDAT", "fcTL", "fdAT", "fdAT", "IEND"]);
+
+        let animation = Animation::from_png(&png).unwrap();
+        assert_eq!(animation.control, AnimationControl { num_frames: 2, num_plays: 3 });
+        assert_eq!(animation.frames[1].control.delay(), 0.25);
+        assert_eq!(animation.frames[1].data, vec![vec![2], vec![9]]);
+        assert_eq!(animation.extract_frame(&png, 1).unwrap().chunks()[2..4], second.chunks()[2..4]);
+    }
+
+    #[test]
+    fn test_build_rejects_mismatched_frames() {
+        assert_eq!(Animation::build(&[], 0), Err(ApngError::NoFrames));
+        let mut other = still(&[2]);
+        other.remove_chunk_at(0);
+        other.insert_chunk(0, chunk("IHDR", vec![0, 0, 0, 4, 0, 0, 0, 4, 8, 6, 0, 0, 0]));
+        let first = still(&[1]);
+        assert_eq!(Animation::build(&[(&first, 100), (&other, 100)], 0), Err(ApngError::HeaderMismatch { index: 1 }));
+    }
+}
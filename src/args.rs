@@ -1,8 +1,6 @@
 use clap::Args;
 use std::path::PathBuf;
-use structopt;
 use structopt::StructOpt;
-use crate::chunk_type::ChunkType;
 
 // TODO: remove the 'pub's
 #[derive(StructOpt, Args, Debug)]
@@ -12,37 +10,382 @@ pub struct Encode {
     #[structopt(short, long, default_value = "ruSt")]
     pub chunk_type: String,
     pub message: String,
+    /// More files, directories or glob patterns to encode the message into
+    pub more_paths: Vec<PathBuf>,
+    /// Also set tIME to now (png only)
+    #[structopt(short, long)]
+    pub touch: bool,
+    /// Where to store the message: app or comment (jpeg, gif), gap or lsb (bmp)
+    #[structopt(long)]
+    pub carrier: Option<String>,
+    #[structopt(flatten)]
+    pub batch: Batch,
+    #[structopt(flatten)]
+    pub output: Output,
 }
 
 #[derive(StructOpt, Args, Debug)]
 #[structopt(name = "basic")]
 pub struct Decode {
-    pub file_path: PathBuf,
+    /// Files, directories or glob patterns
+    #[arg(required = true)]
+    pub file_paths: Vec<PathBuf>,
     #[structopt(short, long, default_value = "ruSt")]
     pub chunk_type: String,
+    #[structopt(flatten)]
+    pub batch: Batch,
 }
 
 #[derive(StructOpt, Args, Debug)]
 #[structopt(name = "basic")]
 pub struct Remove {
-    pub file_path: PathBuf,
+    /// Files, directories or glob patterns
+    #[arg(required = true)]
+    pub file_paths: Vec<PathBuf>,
     #[structopt(short, long, default_value = "ruSt")]
     pub chunk_type: String,
+    #[structopt(flatten)]
+    pub batch: Batch,
+    #[structopt(flatten)]
+    pub output: Output,
+}
+
+/// Options for commands that take several files.
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct Batch {
+    /// Also process images in subdirectories of directory arguments; hidden
+    /// files and directories are skipped, as by globs
+    #[structopt(short, long)]
+    pub recursive: bool,
+    /// Number of files to process at once; defaults to the number of cpus
+    #[structopt(short, long)]
+    pub jobs: Option<usize>,
+    /// Keep going after a file fails, and report every failure at the end
+    #[structopt(long)]
+    pub continue_on_error: bool,
+}
+
+/// Where commands that change a file write the result.
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct Output {
+    /// Write the result here and leave the input untouched (single file only)
+    #[structopt(short, long)]
+    pub output: Option<PathBuf>,
+    /// Keep the replaced file as its name plus this suffix, e.g. .bak
+    #[structopt(long)]
+    pub backup: Option<String>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct Dump {
+    pub file_path: PathBuf,
+    /// json or yaml
+    #[structopt(short, long, default_value = "json")]
+    pub format: String,
+}
+
+#[cfg(feature = "serde")]
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct Build {
+    /// A description written by `dump`
+    pub description: PathBuf,
+    pub output: PathBuf,
+    /// json or yaml
+    #[structopt(short, long, default_value = "json")]
+    pub format: String,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct SetDpi {
+    pub file_path: PathBuf,
+    /// Horizontal resolution in dots per inch
+    pub dpi: f64,
+    /// Vertical resolution, if different
+    #[structopt(short, long)]
+    pub vertical: Option<f64>,
 }
 
-#[derive(Args, Debug)]
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct Touch {
+    pub file_path: PathBuf,
+    /// UTC time as 2024-02-29T13:45:00 instead of now
+    #[structopt(short, long)]
+    pub time: Option<String>,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct Normalize {
+    pub file_path: PathBuf,
+    /// Convert a standard png to apple's CgBI variant instead
+    #[structopt(long)]
+    pub to_cgbi: bool,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct Diff {
+    pub before: PathBuf,
+    pub after: PathBuf,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct ExtractTrailer {
+    pub file_path: PathBuf,
+    pub output: PathBuf,
+    /// Report the format of the trailer (zip, rar, 7z, pdf) if recognised
+    #[structopt(long)]
+    pub detect: bool,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct Strip {
+    pub file_path: PathBuf,
+    /// Chunk name (`?` wildcards allowed) or property to always keep:
+    /// critical, ancillary, public, private, safe-to-copy, unsafe-to-copy
+    #[structopt(short, long)]
+    pub keep: Vec<String>,
+    /// Chunk name or property to remove. If omitted, every ancillary chunk
+    /// except those affecting rendering or animation (tRNS, gAMA, cHRM, sRGB,
+    /// iCCP, sBIT, cICP, mDCV, cLLI, pHYs, acTL, fcTL, fdAT) and the default
+    /// message chunks (ruSt, arCh); use `--drop ancillary` to remove those too
+    #[structopt(short, long)]
+    pub drop: Vec<String>,
+    /// List the chunks that would be removed without writing the file
+    #[structopt(long)]
+    pub dry_run: bool,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
 pub struct Print {
     pub file_path: PathBuf,
+    /// Dump the data of chunks matching this name or property
+    #[structopt(long)]
+    pub hex: Vec<String>,
+    /// Print machine-readable JSON instead of a table; data after IEND
+    /// becomes a last entry of type "trailer"
+    #[structopt(long)]
+    pub json: bool,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct ApngList {
+    pub file_path: PathBuf,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct ApngExtract {
+    pub file_path: PathBuf,
+    /// Frame index, starting at 0
+    pub frame: usize,
+    pub output: PathBuf,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct ApngEncode {
+    pub file_path: PathBuf,
+    /// Frame index, starting at 0
+    pub frame: usize,
+    #[structopt(short, long, default_value = "ruSt")]
+    pub chunk_type: String,
+    pub message: String,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct ApngDecode {
+    pub file_path: PathBuf,
+    /// Frame index, starting at 0
+    pub frame: usize,
+    #[structopt(short, long, default_value = "ruSt")]
+    pub chunk_type: String,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct ApngBuild {
+    pub output: PathBuf,
+    /// Frame files in order, each optionally followed by `:delay` in milliseconds
+    #[arg(required = true)]
+    pub frames: Vec<String>,
+    /// Delay in milliseconds for frames without their own
+    #[structopt(short, long, default_value = "100")]
+    pub delay: u16,
+    /// Number of times to play the animation; 0 loops forever
+    #[structopt(short, long, default_value = "0")]
+    pub plays: u32,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct ExifShow {
+    pub file_path: PathBuf,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct ExifSet {
+    pub file_path: PathBuf,
+    /// Tag name (e.g. Artist, GPSLatitude) or number
+    pub tag: String,
+    pub value: String,
+    /// Directory for numeric tags: ifd0, exif, gps, interop or ifd1
+    #[structopt(short, long)]
+    pub ifd: Option<String>,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct ExifRemove {
+    pub file_path: PathBuf,
+    /// Tag name (e.g. Artist, GPSLatitude) or number
+    pub tag: String,
+    /// Directory for numeric tags: ifd0, exif, gps, interop or ifd1
+    #[structopt(short, long)]
+    pub ifd: Option<String>,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct ExifStripGps {
+    pub file_path: PathBuf,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct XmpShow {
+    pub file_path: PathBuf,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct XmpSet {
+    pub file_path: PathBuf,
+    /// Property as prefix:name (e.g. dc:title, dc:creator)
+    pub property: String,
+    /// One value, or several for array properties like dc:creator
+    #[arg(required = true)]
+    pub values: Vec<String>,
+    /// Declare a custom namespace as prefix=uri
+    #[structopt(short, long)]
+    pub namespace: Vec<String>,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct XmpRemove {
+    pub file_path: PathBuf,
+    /// Property as prefix:name
+    pub property: String,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct IccExtract {
+    pub file_path: PathBuf,
+    /// Where to write the .icc profile
+    pub output: PathBuf,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct IccEmbed {
+    pub file_path: PathBuf,
+    /// The .icc profile to embed
+    pub profile: PathBuf,
+    /// Profile name stored in iCCP; defaults to the profile file name
+    #[structopt(short, long)]
+    pub name: Option<String>,
+    /// Remove sRGB instead of refusing, since the two may not appear together
+    #[structopt(short, long)]
+    pub replace_srgb: bool,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct ColorShow {
+    pub file_path: PathBuf,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct ColorSet {
+    pub file_path: PathBuf,
+    /// gAMA, cHRM, sRGB, cICP, mDCV or cLLI
+    pub chunk_type: String,
+    /// e.g. 0.45455 for gAMA, perceptual for sRGB, 9,16,0,1 for cICP
+    pub value: String,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct ColorClear {
+    pub file_path: PathBuf,
+    /// Chunk types to remove; all color chunks if none are given
+    pub chunk_types: Vec<String>,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct PaletteShow {
+    pub file_path: PathBuf,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct PaletteSwatch {
+    pub file_path: PathBuf,
+    /// Where to write the swatch png
+    pub output: PathBuf,
+    /// Side of each color square in pixels
+    #[structopt(short, long, default_value = "16")]
+    pub size: u32,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct PaletteSet {
+    pub file_path: PathBuf,
+    /// Entry to replace; the palette size appends a new entry
+    pub index: usize,
+    /// New color as #rrggbb, or #rrggbbaa to also set its alpha
+    pub color: String,
 }
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-    use std::convert::TryFrom;
-    use std::str::FromStr;
-
     #[test]
     pub fn test_sub_command() {
         // assert_eq!( ,  );
     }
-}
\ No newline at end of file
+}
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct PolyglotBuild {
+    pub file_path: PathBuf,
+    /// The zip archive to add
+    pub zip: PathBuf,
+    pub output: PathBuf,
+    /// trailer (after IEND) or chunk (in a private arCh chunk)
+    #[structopt(short, long, default_value = "trailer")]
+    pub placement: String,
+}
+
+#[derive(StructOpt, Args, Debug)]
+#[structopt(name = "basic")]
+pub struct PolyglotVerify {
+    pub file_path: PathBuf,
+}
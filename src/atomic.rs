@@ -0,0 +1,188 @@
+use std::fs::{self, File, FileTimes};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Where [`write`] puts the new contents of a file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Write here instead of over the source, which is left untouched.
+    pub output: Option<PathBuf>,
+    /// Keep the file being replaced as its name plus this suffix.
+    pub backup: Option<String>,
+}
+
+fn temp_path(destination: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let name = destination.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let unique = format!(".{}.{}-{}.tmp", name, std::process::id(), COUNTER.fetch_add(1, Ordering::SeqCst));
+    destination.with_file_name(unique)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Follows `path` to the file it links to, so that renaming over it updates
+/// the target instead of replacing the link with a regular file.
+fn resolve_symlink(path: &Path) -> io::Result<PathBuf> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::canonicalize(path),
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+fn backup(destination: &Path, suffix: &str) -> io::Result<PathBuf> {
+    let backup = with_suffix(destination, suffix);
+    match fs::remove_file(&backup) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {},
+    }
+    // A hard link keeps the old contents once the rename replaces the
+    // destination, without a moment where neither exists.
+    if fs::hard_link(destination, &backup).is_err() {
+        fs::copy(destination, &backup)?;
+    }
+    Ok(backup)
+}
+
+fn write_temp(temp: &Path, bytes: &[u8], source: &fs::Metadata) -> io::Result<()> {
+    let mut file = File::options().write(true).create_new(true).open(temp)?;
+    file.write_all(bytes)?;
+    file.set_permissions(source.permissions())?;
+    file.set_times(FileTimes::new().set_modified(source.modified()?))?;
+    file.sync_all()
+}
+
+/// Replaces the contents of `source`, or writes them to `options.output`,
+/// so that a crash or full disk leaves either the old file or the new one:
+/// the bytes go to a temporary file in the same directory, are synced to
+/// disk and then renamed over the destination. The new file gets the
+/// permissions and modification time of `source`. A symlinked destination
+/// stays a symlink and its target is written. Returns the path written.
+pub fn write(source: &Path, bytes: &[u8], options: &WriteOptions) -> io::Result<PathBuf> {
+    let destination = &resolve_symlink(options.output.as_deref().unwrap_or(source))?;
+    let metadata = fs::metadata(source)?;
+    let temp = temp_path(destination);
+    if let Err(e) = write_temp(&temp, bytes, &metadata) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    if let Some(suffix) = &options.backup {
+        if destination.exists() {
+            if let Err(e) = backup(destination, suffix) {
+                let _ = fs::remove_file(&temp);
+                return Err(e);
+            }
+        }
+    }
+    if let Err(e) = fs::rename(&temp, destination) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    // Make the rename itself durable; directories cannot be opened for
+    // syncing everywhere, so this is best effort.
+    if let Some(dir) = destination.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(destination.to_path_buf())
+}
+
+/// Replaces the contents of `path` in place with [`write`].
+pub fn replace(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    write(path, bytes, &WriteOptions::default()).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    fn testing_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rush-png-atomic-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn leftovers(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().to_string()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_replace_keeps_mtime() {
+        let dir = testing_dir("replace");
+        let path = dir.join("a.png");
+        fs::write(&path, b"old").unwrap();
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        File::options().write(true).open(&path).unwrap().set_modified(mtime).unwrap();
+        replace(&path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), mtime);
+        assert_eq!(leftovers(&dir), vec!["a.png"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_output_and_backup() {
+        let dir = testing_dir("backup");
+        let path = dir.join("a.png");
+        fs::write(&path, b"old").unwrap();
+        let output = dir.join("b.png");
+        let options = WriteOptions { output: Some(output.clone()), backup: None };
+        assert_eq!(write(&path, b"new", &options).unwrap(), output);
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+        assert_eq!(fs::read(&output).unwrap(), b"new");
+
+        let options = WriteOptions { output: None, backup: Some(".bak".to_string()) };
+        write(&path, b"newer", &options).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"newer");
+        assert_eq!(fs::read(dir.join("a.png.bak")).unwrap(), b"old");
+        assert_eq!(leftovers(&dir), vec!["a.png", "a.png.bak", "b.png"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = testing_dir("permissions");
+        let path = dir.join("a.png");
+        fs::write(&path, b"old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        replace(&path, b"new").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_keeps_symlinks() {
+        let dir = testing_dir("symlink");
+        fs::create_dir(dir.join("real")).unwrap();
+        let target = dir.join("real/a.png");
+        fs::write(&target, b"old").unwrap();
+        let link = dir.join("a.png");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        replace(&link, b"new").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+        assert_eq!(leftovers(&dir), vec!["a.png", "real"]);
+        assert_eq!(leftovers(&dir.join("real")), vec!["a.png"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_source() {
+        let dir = testing_dir("missing");
+        assert!(replace(&dir.join("a.png"), b"new").is_err());
+        assert!(leftovers(&dir).is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
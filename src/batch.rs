@@ -0,0 +1,319 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Extensions of the files a directory expands to.
+pub const IMAGE_EXTENSIONS: &[&str] = &["png", "apng", "jpg", "jpeg", "gif", "webp", "bmp", "tif", "tiff"];
+
+/// Why the inputs of a batch could not be expanded to files.
+#[derive(Debug)]
+pub enum BatchError {
+    /// A path does not exist and is not a glob pattern.
+    NotFound(PathBuf),
+    /// A glob pattern or directory matched no files.
+    NoMatches(String),
+    Io { path: PathBuf, source: std::io::Error },
+}
+
+impl Display for BatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatchError::NotFound(path) => write!(f, "{} not found", path.display()),
+            BatchError::NoMatches(pattern) => write!(f, "no files match {}", pattern),
+            BatchError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BatchError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+fn is_pattern(s: &str) -> bool {
+    s.contains(['*', '?'])
+}
+
+/// Matches one path component against a pattern where `*` is any run of
+/// characters and `?` is any one character.
+fn matches_component(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => matches_component(&pattern[1..], name) || (!name.is_empty() && matches_component(pattern, &name[1..])),
+        (Some('?'), Some(_)) => matches_component(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) => p == n && matches_component(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+fn read_dir_sorted(dir: &Path) -> Result<Vec<PathBuf>, BatchError> {
+    let io = |source| BatchError::Io { path: dir.to_path_buf(), source };
+    let mut entries = fs::read_dir(dir)
+        .map_err(io)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io)?;
+    entries.sort();
+    Ok(entries)
+}
+
+fn has_image_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| IMAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+}
+
+/// A directory that is not reached through a symlink. Recursion skips
+/// symlinked directories, which could otherwise loop forever.
+fn is_real_dir(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir())
+}
+
+/// Like shells, directory walks and wildcards leave out hidden entries.
+fn is_hidden(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+fn walk(dir: &Path, recursive: bool, found: &mut Vec<PathBuf>) -> Result<(), BatchError> {
+    for path in read_dir_sorted(dir)? {
+        if is_hidden(&path) {
+            continue;
+        }
+        if path.is_dir() {
+            if recursive && is_real_dir(&path) {
+                walk(&path, recursive, found)?;
+            }
+        } else if has_image_extension(&path) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Follows the glob components from `dir`; `**` matches any number of
+/// directories, including none.
+fn glob(dir: &Path, components: &[String], found: &mut Vec<PathBuf>) -> Result<(), BatchError> {
+    let Some((component, rest)) = components.split_first() else {
+        if dir.is_file() {
+            found.push(dir.to_path_buf());
+        }
+        return Ok(());
+    };
+    if component == "**" {
+        glob(dir, rest, found)?;
+        for path in read_dir_sorted(dir)? {
+            if is_real_dir(&path) && !is_hidden(&path) {
+                glob(&path, components, found)?;
+            }
+        }
+    } else if is_pattern(component) {
+        let pattern: Vec<char> = component.chars().collect();
+        for path in read_dir_sorted(dir)? {
+            let name: Vec<char> = path.file_name().map(|name| name.to_string_lossy().chars().collect()).unwrap_or_default();
+            if !is_hidden(&path) && matches_component(&pattern, &name) {
+                glob(&path, rest, found)?;
+            }
+        }
+    } else {
+        let path = dir.join(component);
+        if path.exists() {
+            glob(&path, rest, found)?;
+        }
+    }
+    Ok(())
+}
+
+/// Expands files, directories and glob patterns (`*`, `?`, `**`) to the
+/// files they name, in order and without duplicates. Directories expand to
+/// the images in them, and in their subdirectories when `recursive`. Paths
+/// that resolve to the same file, e.g. through a symlink or `..`, count as
+/// duplicates so no file is edited twice at once.
+pub fn expand(inputs: &[PathBuf], recursive: bool) -> Result<Vec<PathBuf>, BatchError> {
+    let mut files = vec![];
+    let mut seen = HashSet::new();
+    for input in inputs {
+        let mut found = vec![];
+        let text = input.to_string_lossy();
+        if input.is_file() {
+            found.push(input.clone());
+        } else if input.is_dir() {
+            walk(input, recursive, &mut found)?;
+        } else if is_pattern(&text) {
+            let mut base = PathBuf::new();
+            let mut components = vec![];
+            for component in input.components() {
+                match component {
+                    Component::Normal(name) if components.is_empty() && !is_pattern(&name.to_string_lossy()) => base.push(name),
+                    Component::Normal(name) => components.push(name.to_string_lossy().to_string()),
+                    other if components.is_empty() => base.push(other),
+                    other => components.push(other.as_os_str().to_string_lossy().to_string()),
+                }
+            }
+            let base = if base.as_os_str().is_empty() { PathBuf::from(".") } else { base };
+            glob(&base, &components, &mut found)?;
+            if input.is_relative() {
+                for path in found.iter_mut() {
+                    if let Ok(stripped) = path.strip_prefix(".") {
+                        *path = stripped.to_path_buf();
+                    }
+                }
+            }
+        } else {
+            return Err(BatchError::NotFound(input.clone()));
+        }
+        if found.is_empty() {
+            return Err(BatchError::NoMatches(text.to_string()));
+        }
+        for path in found {
+            let canonical = fs::canonicalize(&path).map_err(|source| BatchError::Io { path: path.clone(), source })?;
+            if seen.insert(canonical) {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// What happened to one file of a batch; `result` is `None` when the file
+/// was skipped because an earlier one failed.
+#[derive(Debug)]
+pub struct Outcome<T, E> {
+    pub path: PathBuf,
+    pub result: Option<Result<T, E>>,
+}
+
+/// Runs `task` on every path with `jobs` worker threads. `report` is called
+/// as each file finishes, from whichever thread ran it. Unless
+/// `continue_on_error`, files not yet started are skipped after the first
+/// failure. The outcomes come back in the order of `paths`.
+pub fn run<T, E, F, R>(paths: &[PathBuf], jobs: usize, continue_on_error: bool, task: F, report: R) -> Vec<Outcome<T, E>>
+where
+    T: Send,
+    E: Send,
+    F: Fn(&Path) -> Result<T, E> + Sync,
+    R: Fn(&Path, &Result<T, E>) + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results: Vec<Mutex<Option<Result<T, E>>>> = paths.iter().map(|_| Mutex::new(None)).collect();
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, paths.len().max(1)) {
+            scope.spawn(|| loop {
+                let idx = next.fetch_add(1, Ordering::SeqCst);
+                if idx >= paths.len() || (!continue_on_error && failed.load(Ordering::SeqCst)) {
+                    break;
+                }
+                let result = task(&paths[idx]);
+                if result.is_err() {
+                    failed.store(true, Ordering::SeqCst);
+                }
+                report(&paths[idx], &result);
+                *results[idx].lock().unwrap() = Some(result);
+            });
+        }
+    });
+    paths
+        .iter()
+        .zip(results)
+        .map(|(path, result)| Outcome { path: path.clone(), result: result.into_inner().unwrap() })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        matches_component(&pattern.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>())
+    }
+
+    fn testing_tree(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("rush-png-batch-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for file in ["a.png", "b.PNG", "notes.txt", "icons/c.png", "icons/deep/d.gif", "icons/.e.png"] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_matches_component() {
+        assert!(matches("*.png", "a.png"));
+        assert!(matches("?.png", "a.png"));
+        assert!(matches("a*b*c", "aXbYbZc"));
+        assert!(!matches("*.png", "a.gif"));
+        assert!(!matches("?.png", "ab.png"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let root = testing_tree("expand");
+        let names = |paths: Vec<PathBuf>| -> Vec<String> {
+            paths.iter().map(|path| path.strip_prefix(&root).unwrap().to_string_lossy().replace('\\', "/")).collect()
+        };
+        assert_eq!(names(expand(std::slice::from_ref(&root), false).unwrap()), vec!["a.png", "b.PNG"]);
+        assert_eq!(
+            names(expand(std::slice::from_ref(&root), true).unwrap()),
+            vec!["a.png", "b.PNG", "icons/c.png", "icons/deep/d.gif"],
+        );
+        assert_eq!(names(expand(&[root.join("**/*.png")], false).unwrap()), vec!["a.png", "icons/c.png"]);
+        assert_eq!(names(expand(&[root.join("a.png"), root.join("*.png")], false).unwrap()), vec!["a.png"]);
+        let dotted = [root.join("a.png"), root.join("icons/../a.png")];
+        assert_eq!(names(expand(&dotted, false).unwrap()), vec!["a.png"]);
+        // Hidden files are left out of walks and wildcards, but can be named.
+        assert_eq!(names(expand(&[root.join("icons/.e.png")], false).unwrap()), vec!["icons/.e.png"]);
+        assert!(matches!(expand(&[root.join("*.jpg")], false), Err(BatchError::NoMatches(_))));
+        assert!(matches!(expand(&[root.join("missing.png")], false), Err(BatchError::NotFound(_))));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_expand_skips_symlinked_directories() {
+        let root = testing_tree("symlinks");
+        std::os::unix::fs::symlink(&root, root.join("icons/loop")).unwrap();
+        std::os::unix::fs::symlink(root.join("a.png"), root.join("link.png")).unwrap();
+        let names = |paths: Vec<PathBuf>| -> Vec<String> {
+            paths.iter().map(|path| path.strip_prefix(&root).unwrap().to_string_lossy().to_string()).collect()
+        };
+        // link.png resolves to a.png, so it is a duplicate.
+        assert_eq!(
+            names(expand(std::slice::from_ref(&root), true).unwrap()),
+            vec!["a.png", "b.PNG", "icons/c.png", "icons/deep/d.gif"],
+        );
+        assert_eq!(names(expand(&[root.join("**/*.png")], false).unwrap()), vec!["a.png", "icons/c.png"]);
+        assert_eq!(names(expand(&[root.join("link.png")], false).unwrap()), vec!["link.png"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_run() {
+        let paths: Vec<PathBuf> = (0..20).map(|i| PathBuf::from(format!("{}.png", i))).collect();
+        let task = |path: &Path| {
+            let n: usize = path.file_stem().unwrap().to_str().unwrap().parse().unwrap();
+            if n % 7 == 3 { Err(n) } else { Ok(n * 2) }
+        };
+        let reported = AtomicUsize::new(0);
+        let outcomes = run(&paths, 4, true, task, |_, _| {
+            reported.fetch_add(1, Ordering::SeqCst);
+        });
+        assert_eq!(reported.load(Ordering::SeqCst), 20);
+        assert_eq!(outcomes[2].result.as_ref().unwrap(), &Ok(4));
+        assert_eq!(outcomes[10].result.as_ref().unwrap(), &Err(10));
+        assert_eq!(outcomes.iter().filter(|outcome| matches!(outcome.result, Some(Err(_)))).count(), 3);
+
+        let outcomes = run(&paths, 1, false, task, |_, _| {});
+        assert_eq!(outcomes[3].result.as_ref().unwrap(), &Err(3));
+        assert!(outcomes[4..].iter().all(|outcome| outcome.result.is_none()));
+    }
+}
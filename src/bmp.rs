@@ -0,0 +1,493 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::bytes::{le_u16, le_u32};
+use crate::container::{self, Container, Format, Segment};
+use crate::Error;
+
+/// Why a byte slice could not be read as a `Bmp`, or a message could not be
+/// stored in or found in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BmpError {
+    /// The file does not start with `BM`.
+    NotBmp,
+    /// The headers or pixel array run past the end of the file.
+    Truncated,
+    /// Only `BITMAPINFOHEADER` and its later versions are supported.
+    UnsupportedHeader(u32),
+    /// The pixel array is compressed (RLE, JPEG or PNG).
+    Compressed(u32),
+    /// The bits per pixel are not 1, 4, 8, 16, 24 or 32.
+    InvalidDepth(u16),
+    /// Pixel LSBs are only used for 16, 24 and 32-bit images.
+    UnsupportedDepth(u16),
+    /// The message does not fit in the pixel LSBs.
+    MessageTooLarge,
+    /// No message is stored under this key.
+    MessageNotFound(String),
+    /// The pixel LSBs already carry the message stored under this key.
+    LsbInUse(String),
+}
+
+impl Display for BmpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BmpError::NotBmp => write!(f, "not a bmp: bad signature"),
+            BmpError::Truncated => write!(f, "bmp is truncated"),
+            BmpError::UnsupportedHeader(size) => write!(f, "unsupported bmp header of {} bytes", size),
+            BmpError::Compressed(compression) => write!(f, "compressed bmp (method {}) is not supported", compression),
+            BmpError::InvalidDepth(bpp) => write!(f, "invalid bmp bit depth {}", bpp),
+            BmpError::UnsupportedDepth(bpp) => write!(f, "cannot hide bits in {}-bit pixels", bpp),
+            BmpError::MessageTooLarge => write!(f, "message is too large for the pixel data"),
+            BmpError::MessageNotFound(key) => write!(f, "no {} message found", key),
+            BmpError::LsbInUse(key) => write!(f, "the pixel bits already hold the {} message", key),
+        }
+    }
+}
+
+impl std::error::Error for BmpError {}
+
+/// Where a message is stored in a `Bmp`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BmpCarrier {
+    /// Between the headers and the pixel array, which moves to make room.
+    /// The image is unchanged; this is the default.
+    Gap,
+    /// In the least significant bit of each pixel byte. The file size stays
+    /// the same but the image changes slightly.
+    Lsb,
+}
+
+impl FromStr for BmpCarrier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gap" => Ok(BmpCarrier::Gap),
+            "lsb" => Ok(BmpCarrier::Lsb),
+            _ => Err(format!("unknown carrier {:?}, expected gap or lsb", s)),
+        }
+    }
+}
+
+/// Every message starts with this, the key and a little-endian u32 length.
+const MESSAGE_MAGIC: &[u8; 4] = b"RUSH";
+const RECORD_HEADER_LEN: usize = 12;
+
+fn record(key: &[u8; 4], message: &[u8]) -> Vec<u8> {
+    let mut ret = MESSAGE_MAGIC.to_vec();
+    ret.extend_from_slice(key);
+    ret.extend_from_slice(&(message.len() as u32).to_le_bytes());
+    ret.extend_from_slice(message);
+    ret
+}
+
+/// An uncompressed BMP, kept as raw bytes with its header fields parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bmp {
+    bytes: Vec<u8>,
+    dib_size: u32,
+    width: i32,
+    height: i32,
+    bpp: u16,
+    compression: u32,
+}
+
+impl TryFrom<&[u8]> for Bmp {
+    type Error = BmpError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if !value.starts_with(b"BM") {
+            return Err(BmpError::NotBmp);
+        }
+        if value.len() < 18 {
+            return Err(BmpError::Truncated);
+        }
+        let dib_size = le_u32(value, 14);
+        if dib_size < 40 {
+            return Err(BmpError::UnsupportedHeader(dib_size));
+        }
+        if value.len() < 14 + dib_size as usize {
+            return Err(BmpError::Truncated);
+        }
+        let compression = le_u32(value, 30);
+        // BI_RGB, or BI_BITFIELDS/BI_ALPHABITFIELDS which only add masks
+        if !matches!(compression, 0 | 3 | 6) {
+            return Err(BmpError::Compressed(compression));
+        }
+        let bmp = Bmp {
+            bytes: value.to_vec(),
+            dib_size,
+            width: le_u32(value, 18) as i32,
+            height: le_u32(value, 22) as i32,
+            bpp: le_u16(value, 28),
+            compression,
+        };
+        if !matches!(bmp.bpp, 1 | 4 | 8 | 16 | 24 | 32) {
+            return Err(BmpError::InvalidDepth(bmp.bpp));
+        }
+        let pixel_end = bmp.checked_pixel_array_len().and_then(|len| len.checked_add(bmp.pixel_offset()));
+        if bmp.pixel_offset() < bmp.header_end() || !matches!(pixel_end, Some(end) if end <= value.len()) {
+            return Err(BmpError::Truncated);
+        }
+        Ok(bmp)
+    }
+}
+
+impl Bmp {
+    pub fn width(&self) -> u32 {
+        self.width.unsigned_abs()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height.unsigned_abs()
+    }
+
+    pub fn bits_per_pixel(&self) -> u16 {
+        self.bpp
+    }
+
+    fn pixel_offset(&self) -> usize {
+        le_u32(&self.bytes, 10) as usize
+    }
+
+    /// The end of the file header, DIB header, bit masks and color table.
+    fn header_end(&self) -> usize {
+        let masks = if self.dib_size == 40 && self.compression == 3 {
+            12
+        } else if self.dib_size == 40 && self.compression == 6 {
+            16
+        } else {
+            0
+        };
+        let colors = match le_u32(&self.bytes, 46) {
+            0 if self.bpp <= 8 => 1 << self.bpp,
+            colors => colors as usize,
+        };
+        14 + self.dib_size as usize + masks + colors * 4
+    }
+
+    fn row_size(&self) -> usize {
+        (self.bpp as usize * self.width() as usize).div_ceil(32) * 4
+    }
+
+    fn pixel_array_len(&self) -> usize {
+        self.row_size() * self.height() as usize
+    }
+
+    /// `pixel_array_len`, or `None` when the header describes more pixels
+    /// than fit in memory. Checked once on parse so the above cannot
+    /// overflow afterwards.
+    fn checked_pixel_array_len(&self) -> Option<usize> {
+        let row_bits = (self.bpp as usize).checked_mul(self.width() as usize)?;
+        (row_bits.div_ceil(32) * 4).checked_mul(self.height() as usize)
+    }
+
+    /// Offsets of the pixel bytes, skipping row padding.
+    fn pixel_bytes(&self) -> impl Iterator<Item = usize> + '_ {
+        let row_bytes = self.bpp as usize / 8 * self.width() as usize;
+        (0..self.height() as usize)
+            .flat_map(move |row| {
+                let start = self.pixel_offset() + row * self.row_size();
+                start..start + row_bytes
+            })
+    }
+
+    /// Inserts `data` at `at`, before the pixel array, and fixes up the file
+    /// size, pixel offset and, for V5 headers, the embedded profile offset.
+    fn splice_gap(&mut self, at: usize, remove: usize, data: &[u8]) {
+        let delta = data.len() as i64 - remove as i64;
+        self.bytes.splice(at..at + remove, data.iter().cloned());
+        let shift = |value: u32| (value as i64 + delta) as u32;
+        let file_size = self.bytes.len() as u32;
+        self.bytes[2..6].copy_from_slice(&file_size.to_le_bytes());
+        let pixel_offset = shift(le_u32(&self.bytes, 10));
+        self.bytes[10..14].copy_from_slice(&pixel_offset.to_le_bytes());
+        if self.dib_size >= 124 {
+            // the profile offset is relative to the DIB header
+            let profile_offset = le_u32(&self.bytes, 14 + 112);
+            if profile_offset != 0 && 14 + profile_offset as usize >= at {
+                self.bytes[126..130].copy_from_slice(&shift(profile_offset).to_le_bytes());
+            }
+        }
+    }
+
+    /// Finds the gap record for `key`: its offset and total length.
+    fn gap_record(&self, key: &[u8; 4]) -> Option<(usize, usize)> {
+        let gap = &self.bytes[self.header_end()..self.pixel_offset()];
+        let mut idx = 0;
+        while idx + RECORD_HEADER_LEN <= gap.len() {
+            if &gap[idx..idx + 4] == MESSAGE_MAGIC {
+                let len = RECORD_HEADER_LEN + le_u32(gap, idx + 8) as usize;
+                if idx + len <= gap.len() {
+                    if &gap[idx + 4..idx + 8] == key {
+                        return Some((self.header_end() + idx, len));
+                    }
+                    idx += len;
+                    continue;
+                }
+            }
+            idx += 1;
+        }
+        None
+    }
+
+    fn lsb_capacity(&self) -> usize {
+        self.pixel_bytes().count() / 8
+    }
+
+    fn read_lsb(&self, len: usize) -> Vec<u8> {
+        let bits: Vec<u8> = self.pixel_bytes().take(len * 8).map(|idx| self.bytes[idx] & 1).collect();
+        bits.chunks(8).map(|bits| bits.iter().fold(0, |byte, bit| byte << 1 | bit)).collect()
+    }
+
+    fn write_lsb(&mut self, data: &[u8]) {
+        let positions: Vec<usize> = self.pixel_bytes().take(data.len() * 8).collect();
+        for (i, idx) in positions.into_iter().enumerate() {
+            let bit = data[i / 8] >> (7 - i % 8) & 1;
+            self.bytes[idx] = self.bytes[idx] & !1 | bit;
+        }
+    }
+
+    /// The key and total length of the record in the pixel LSBs, if any.
+    fn lsb_record(&self) -> Option<([u8; 4], usize)> {
+        if !matches!(self.bpp, 16 | 24 | 32) || self.lsb_capacity() < RECORD_HEADER_LEN {
+            return None;
+        }
+        let header = self.read_lsb(RECORD_HEADER_LEN);
+        let len = RECORD_HEADER_LEN + le_u32(&header, 8) as usize;
+        let key = <[u8; 4]>::try_from(&header[4..8]).unwrap();
+        (&header[0..4] == MESSAGE_MAGIC && len <= self.lsb_capacity()).then_some((key, len))
+    }
+
+    fn lsb_record_len(&self, key: &[u8; 4]) -> Option<usize> {
+        self.lsb_record().filter(|(k, _)| k == key).map(|(_, len)| len)
+    }
+
+    pub fn insert_message_with(&mut self, key: &[u8; 4], message: &[u8], carrier: BmpCarrier) -> Result<(), BmpError> {
+        let record = record(key, message);
+        match carrier {
+            BmpCarrier::Gap => {
+                // a record already stored under `key` is replaced where it is
+                let (at, remove) = self.gap_record(key).unwrap_or((self.pixel_offset(), 0));
+                self.splice_gap(at, remove, &record);
+            },
+            BmpCarrier::Lsb => {
+                if !matches!(self.bpp, 16 | 24 | 32) {
+                    return Err(BmpError::UnsupportedDepth(self.bpp));
+                }
+                if record.len() > self.lsb_capacity() {
+                    return Err(BmpError::MessageTooLarge);
+                }
+                // there is room for one record only; replacing another key's
+                // message would lose it without a trace
+                if let Some((other, _)) = self.lsb_record().filter(|(other, _)| other != key) {
+                    return Err(BmpError::LsbInUse(String::from_utf8_lossy(&other).to_string()));
+                }
+                self.write_lsb(&record);
+            },
+        }
+        Ok(())
+    }
+
+    /// Reads the message stored under `key`, from the gap first and then
+    /// from the pixel LSBs.
+    pub fn message(&self, key: &[u8; 4]) -> Option<Vec<u8>> {
+        if let Some((at, len)) = self.gap_record(key) {
+            return Some(self.bytes[at + RECORD_HEADER_LEN..at + len].to_vec());
+        }
+        let len = self.lsb_record_len(key)?;
+        Some(self.read_lsb(len)[RECORD_HEADER_LEN..].to_vec())
+    }
+
+    /// Removes the message stored under `key`. A gap record is cut out; an
+    /// LSB record has its bits cleared, as the original bits are lost.
+    pub fn remove_message(&mut self, key: &[u8; 4]) -> Result<(), BmpError> {
+        if let Some((at, len)) = self.gap_record(key) {
+            self.splice_gap(at, len, &[]);
+        } else if let Some(len) = self.lsb_record_len(key) {
+            self.write_lsb(&vec![0; len]);
+        } else {
+            return Err(BmpError::MessageNotFound(String::from_utf8_lossy(key).to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+}
+
+impl Container for Bmp {
+    fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Bmp::try_from(bytes)?)
+    }
+
+    fn format(&self) -> Format {
+        Format::Bmp
+    }
+
+    fn segments(&self) -> Vec<Segment> {
+        let header_name = match self.dib_size {
+            40 => "Info header",
+            108 => "V4 header",
+            124 => "V5 header",
+            _ => "DIB header",
+        };
+        let mut ret = vec![
+            Segment { offset: 0, name: "File header".to_string(), length: 14, summary: None },
+            Segment {
+                offset: 14,
+                name: header_name.to_string(),
+                length: self.dib_size as usize,
+                summary: Some(format!("{}x{} {}-bit", self.width(), self.height(), self.bpp)),
+            },
+        ];
+        let mut offset = 14 + self.dib_size as usize;
+        if self.header_end() > offset {
+            ret.push(Segment { offset, name: "Masks/colors".to_string(), length: self.header_end() - offset, summary: None });
+            offset = self.header_end();
+        }
+        if self.pixel_offset() > offset {
+            let gap = &self.bytes[offset..self.pixel_offset()];
+            let keys: Vec<String> = gap
+                .windows(8)
+                .filter(|window| &window[0..4] == MESSAGE_MAGIC)
+                .map(|window| String::from_utf8_lossy(&window[4..8]).to_string())
+                .collect();
+            let summary = (!keys.is_empty()).then(|| format!("messages: {}", keys.join(", ")));
+            ret.push(Segment { offset, name: "Gap".to_string(), length: gap.len(), summary });
+        }
+        ret.push(Segment {
+            offset: self.pixel_offset(),
+            name: "Pixels".to_string(),
+            length: self.pixel_array_len(),
+            summary: None,
+        });
+        let end = self.pixel_offset() + self.pixel_array_len();
+        if self.bytes.len() > end {
+            ret.push(Segment { offset: end, name: "Trailing".to_string(), length: self.bytes.len() - end, summary: None });
+        }
+        ret
+    }
+
+    fn insert_message(&mut self, key: &str, message: &[u8]) -> Result<Vec<String>, Error> {
+        self.insert_message_with(&container::parse_key(key)?, message, BmpCarrier::Gap)?;
+        Ok(vec![])
+    }
+
+    fn insert_message_in(&mut self, key: &str, message: &[u8], carrier: &str) -> Result<Vec<String>, Error> {
+        let carrier = carrier
+            .parse()
+            .map_err(|_| Error::UnsupportedCarrier { format: Format::Bmp, carrier: carrier.to_string() })?;
+        self.insert_message_with(&container::parse_key(key)?, message, carrier)?;
+        Ok(vec![])
+    }
+
+    fn extract_message(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.message(&container::parse_key(key)?))
+    }
+
+    fn remove_message(&mut self, key: &str) -> Result<Vec<String>, Error> {
+        Bmp::remove_message(self, &container::parse_key(key)?)?;
+        Ok(vec![])
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3x2 24-bit image: each row is 9 bytes plus 3 of padding.
+    fn testing_bmp() -> Vec<u8> {
+        let mut bytes = b"BM".to_vec();
+        bytes.extend_from_slice(&(54u32 + 24).to_le_bytes());
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        bytes.extend_from_slice(&54u32.to_le_bytes());
+        bytes.extend_from_slice(&40u32.to_le_bytes());
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&24u16.to_le_bytes());
+        bytes.extend_from_slice(&[0; 24]);
+        bytes.extend((0..24).map(|i| i as u8 * 10));
+        bytes
+    }
+
+    #[test]
+    fn test_parse() {
+        let bmp = Bmp::try_from(testing_bmp().as_slice()).unwrap();
+        assert_eq!((bmp.width(), bmp.height(), bmp.bits_per_pixel()), (3, 2, 24));
+        assert_eq!(bmp.pixel_bytes().count(), 18);
+        assert_eq!(bmp.as_bytes(), testing_bmp());
+        assert_eq!(Bmp::try_from(&b"BN"[..]), Err(BmpError::NotBmp));
+        assert_eq!(Bmp::try_from(&testing_bmp()[..70]), Err(BmpError::Truncated));
+        let mut bytes = testing_bmp();
+        bytes[30] = 1;
+        assert_eq!(Bmp::try_from(bytes.as_slice()), Err(BmpError::Compressed(1)));
+    }
+
+    #[test]
+    fn test_invalid_dimensions() {
+        let mut bytes = testing_bmp();
+        bytes[28] = 7;
+        assert_eq!(Bmp::try_from(bytes.as_slice()), Err(BmpError::InvalidDepth(7)));
+
+        let mut bytes = testing_bmp();
+        bytes[18..22].copy_from_slice(&i32::MAX.to_le_bytes());
+        bytes[22..26].copy_from_slice(&i32::MAX.to_le_bytes());
+        bytes[28] = 32;
+        assert_eq!(Bmp::try_from(bytes.as_slice()), Err(BmpError::Truncated));
+    }
+
+    #[test]
+    fn test_gap_message() {
+        let mut bmp = Bmp::try_from(testing_bmp().as_slice()).unwrap();
+        bmp.insert_message_with(b"ruSt", b"hello", BmpCarrier::Gap).unwrap();
+        bmp.insert_message_with(b"abCd", b"x", BmpCarrier::Gap).unwrap();
+
+        let mut bmp = Bmp::try_from(bmp.as_bytes().as_slice()).unwrap();
+        assert_eq!(bmp.pixel_offset(), 54 + 17 + 13);
+        assert_eq!(le_u32(&bmp.bytes, 2) as usize, bmp.bytes.len());
+        assert_eq!(&bmp.bytes[bmp.pixel_offset()..], &testing_bmp()[54..]);
+        assert_eq!(bmp.message(b"ruSt"), Some(b"hello".to_vec()));
+        assert_eq!(bmp.segments()[2].summary.as_deref(), Some("messages: ruSt, abCd"));
+
+        bmp.insert_message_with(b"ruSt", b"hi", BmpCarrier::Gap).unwrap();
+        let mut bmp = Bmp::try_from(bmp.as_bytes().as_slice()).unwrap();
+        assert_eq!(bmp.pixel_offset(), 54 + 14 + 13);
+        assert_eq!(le_u32(&bmp.bytes, 2) as usize, bmp.bytes.len());
+        assert_eq!(bmp.message(b"ruSt"), Some(b"hi".to_vec()));
+        assert_eq!(bmp.segments()[2].summary.as_deref(), Some("messages: ruSt, abCd"));
+
+        bmp.remove_message(b"ruSt").unwrap();
+        bmp.remove_message(b"abCd").unwrap();
+        assert_eq!(bmp.as_bytes(), testing_bmp());
+        assert_eq!(bmp.remove_message(b"ruSt"), Err(BmpError::MessageNotFound("ruSt".to_string())));
+    }
+
+    #[test]
+    fn test_lsb_message() {
+        let mut bmp = Bmp::try_from(testing_bmp().as_slice()).unwrap();
+        assert_eq!(bmp.insert_message_with(b"ruSt", b"hello", BmpCarrier::Lsb), Err(BmpError::MessageTooLarge));
+
+        let mut bytes = testing_bmp();
+        bytes[18] = 40;
+        bytes.truncate(54);
+        bytes.extend(vec![0xAA; 120 * 2]);
+        let mut bmp = Bmp::try_from(bytes.as_slice()).unwrap();
+        bmp.insert_message_with(b"ruSt", b"hello", BmpCarrier::Lsb).unwrap();
+        assert_eq!(bmp.as_bytes().len(), bytes.len());
+        assert!(bmp.bytes[54..].iter().all(|&b| b & !1 == 0xAA));
+        assert_eq!(bmp.message(b"ruSt"), Some(b"hello".to_vec()));
+        assert_eq!(bmp.message(b"abCd"), None);
+        assert_eq!(bmp.insert_message_in("abCd", b"x", "lsb"), Err(Error::Bmp(BmpError::LsbInUse("ruSt".to_string()))));
+        bmp.insert_message_with(b"ruSt", b"hi", BmpCarrier::Lsb).unwrap();
+        assert_eq!(bmp.message(b"ruSt"), Some(b"hi".to_vec()));
+        bmp.remove_message(b"ruSt").unwrap();
+        assert_eq!(bmp.message(b"ruSt"), None);
+    }
+}
@@ -0,0 +1,42 @@
+//! Small readers shared by the chunk and container parsers. Callers check
+//! lengths first; these panic when `at` is out of bounds.
+
+pub(crate) fn be_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([data[at], data[at + 1]])
+}
+
+pub(crate) fn be_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+pub(crate) fn be_i32(data: &[u8], at: usize) -> i32 {
+    i32::from_be_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+/// `be_u32` for data that has not been checked, such as ICC tag tables.
+pub(crate) fn get_be_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at.checked_add(4)?)?.try_into().ok()?))
+}
+
+pub(crate) fn le_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+pub(crate) fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+/// Decodes Latin-1, which maps every byte to the code point of the same
+/// value.
+pub(crate) fn latin1(data: &[u8]) -> String {
+    data.iter().map(|&b| b as char).collect()
+}
+
+/// Chunk errors with an `InvalidLength { chunk_type, length }` variant.
+pub(crate) trait InvalidLength {
+    fn invalid_length(chunk_type: &str, length: usize) -> Self;
+}
+
+pub(crate) fn invalid_length<E: InvalidLength>(chunk_type: &str, length: usize) -> E {
+    E::invalid_length(chunk_type, length)
+}
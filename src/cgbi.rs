@@ -0,0 +1,250 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use std::str::FromStr;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::pixels::{self, image_data};
+use crate::png::{ImageHeader, Png, PngError};
+use crate::registry::{ChunkData, DecodeError};
+use crate::text;
+
+/// The flags Xcode writes in `CgBI`.
+const DEFAULT_FLAGS: u32 = 0x5000_2006;
+
+/// Why a PNG could not be converted to or from Apple's CgBI variant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CgbiError {
+    /// The file has no `CgBI` chunk, so it is a standard PNG already.
+    NotCgbi,
+    /// The file has a `CgBI` chunk already.
+    AlreadyCgbi,
+    /// Only 8-bit RGB and RGBA images are converted.
+    Unsupported { bit_depth: u8, color_type: u8 },
+    /// Interlaced images are not converted.
+    Interlaced,
+    /// The image data does not inflate or is the wrong size.
+    InvalidData(String),
+    Png(PngError),
+}
+
+impl Display for CgbiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CgbiError::NotCgbi => write!(f, "not an apple cgbi png"),
+            CgbiError::AlreadyCgbi => write!(f, "already an apple cgbi png"),
+            CgbiError::Unsupported { bit_depth, color_type } => {
+                write!(f, "only 8-bit rgb and rgba are supported, not {}-bit color type {}", bit_depth, color_type)
+            },
+            CgbiError::Interlaced => write!(f, "interlaced images are not supported"),
+            CgbiError::InvalidData(e) => write!(f, "invalid image data: {}", e),
+            CgbiError::Png(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CgbiError {}
+
+impl From<PngError> for CgbiError {
+    fn from(e: PngError) -> Self {
+        CgbiError::Png(e)
+    }
+}
+
+/// `CgBI`: marks an iOS-optimized PNG whose `IDAT` is raw deflate without
+/// the zlib wrapper, holding premultiplied BGRA pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CgbiHeader {
+    pub flags: u32,
+}
+
+impl ChunkData for CgbiHeader {
+    const CHUNK_TYPES: &'static [&'static str] = &["CgBI"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        let bytes = chunk.data().try_into().map_err(|_| DecodeError::invalid(format!("CgBI chunk has invalid length {}", chunk.length())))?;
+        Ok(CgbiHeader { flags: u32::from_be_bytes(bytes) })
+    }
+
+    fn encode(&self) -> Chunk {
+        Chunk::new(ChunkType::from_str("CgBI").unwrap(), self.flags.to_be_bytes().to_vec())
+    }
+
+    fn summary(&self) -> String {
+        format!("apple cgbi {:#010x}: raw deflate, premultiplied bgra", self.flags)
+    }
+}
+
+/// Whether `png` is Apple's CgBI variant, which starts with a `CgBI` chunk.
+pub fn is_cgbi(png: &Png) -> bool {
+    png.chunks().first().is_some_and(|chunk| &chunk.chunk_type().bytes() == b"CgBI")
+}
+
+fn check_header(png: &Png) -> Result<ImageHeader, CgbiError> {
+    let header = png.image_header()?;
+    if header.bit_depth != 8 || !(header.color_type == 2 || header.color_type == 6) {
+        return Err(CgbiError::Unsupported { bit_depth: header.bit_depth, color_type: header.color_type });
+    }
+    if header.interlaced {
+        return Err(CgbiError::Interlaced);
+    }
+    Ok(header)
+}
+
+/// Prefixes every row with filter type 0.
+fn filter_none(pixels: &[u8], header: &ImageHeader) -> Vec<u8> {
+    let stride = header.width as usize * header.channels() as usize;
+    let mut data = Vec::with_capacity(pixels.len() + header.height as usize);
+    for row in pixels.chunks(stride.max(1)) {
+        data.push(0);
+        data.extend_from_slice(row);
+    }
+    data
+}
+
+/// Swaps in new image data and the `CgBI` chunk. As critical chunks change,
+/// this also drops the unsafe-to-copy chunks and returns them.
+fn with_image_data(png: &Png, data: Vec<u8>, cgbi: Option<CgbiHeader>) -> (Png, Vec<Chunk>) {
+    let mut converted = png.clone();
+    let (_, dropped) = converted.edit_critical(|converted| {
+        let mut chunks = vec![];
+        let mut idat = Some(Chunk::new(ChunkType::from_str("IDAT").unwrap(), data));
+        if let Some(cgbi) = cgbi {
+            chunks.push(cgbi.encode());
+        }
+        for chunk in png.chunks() {
+            match &chunk.chunk_type().bytes() {
+                b"CgBI" => {},
+                b"IDAT" => chunks.extend(idat.take()),
+                _ => chunks.push(chunk.clone()),
+            }
+        }
+        let trailer = converted.take_trailer();
+        *converted = Png::from_chunks(chunks);
+        converted.set_trailer(trailer);
+    });
+    (converted, dropped)
+}
+
+/// Converts a CgBI PNG to a standard one: zlib-wrapped image data, RGBA
+/// channel order and straight alpha. Also returns the unsafe-to-copy chunks
+/// that were dropped.
+pub fn normalize(png: &Png) -> Result<(Png, Vec<Chunk>), CgbiError> {
+    if !is_cgbi(png) {
+        return Err(CgbiError::NotCgbi);
+    }
+    let header = check_header(png)?;
+    let mut data = vec![];
+    DeflateDecoder::new(image_data(png).as_slice())
+        .read_to_end(&mut data)
+        .map_err(|e| CgbiError::InvalidData(e.to_string()))?;
+    let mut pixels = pixels::unfilter(&data, &header).map_err(|e| CgbiError::InvalidData(e.to_string()))?;
+    for pixel in pixels.chunks_mut(header.channels() as usize) {
+        pixel.swap(0, 2);
+        if let [r, g, b, a] = pixel {
+            if *a > 0 {
+                for c in [r, g, b] {
+                    *c = ((*c as u16 * 255 + *a as u16 / 2) / *a as u16).min(255) as u8;
+                }
+            }
+        }
+    }
+    Ok(with_image_data(png, text::deflate(&filter_none(&pixels, &header)), None))
+}
+
+/// Converts a standard PNG to CgBI, the reverse of [`normalize`]. Alpha is
+/// premultiplied, so colors of nearly transparent pixels lose precision.
+pub fn to_cgbi(png: &Png) -> Result<(Png, Vec<Chunk>), CgbiError> {
+    if is_cgbi(png) {
+        return Err(CgbiError::AlreadyCgbi);
+    }
+    let header = check_header(png)?;
+    let data = text::inflate(&image_data(png)).map_err(|e| CgbiError::InvalidData(e.to_string()))?;
+    let mut pixels = pixels::unfilter(&data, &header).map_err(|e| CgbiError::InvalidData(e.to_string()))?;
+    for pixel in pixels.chunks_mut(header.channels() as usize) {
+        pixel.swap(0, 2);
+        if let [b, g, r, a] = pixel {
+            for c in [b, g, r] {
+                *c = ((*c as u16 * *a as u16 + 127) / 255) as u8;
+            }
+        }
+    }
+    let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+    encoder.write_all(&filter_none(&pixels, &header)).unwrap();
+    Ok(with_image_data(png, encoder.finish().unwrap(), Some(CgbiHeader { flags: DEFAULT_FLAGS })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png(color_type: u8, rows: &[&[u8]]) -> Png {
+        let header = ImageHeader { width: 2, height: rows.len() as u32, bit_depth: 8, color_type, interlaced: false };
+        let mut data = vec![];
+        for row in rows {
+            data.extend_from_slice(row);
+        }
+        Png::from_chunks(vec![
+            header.encode(),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), text::deflate(&data)),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ])
+    }
+
+    fn pixels(png: &Png) -> Vec<u8> {
+        let header = png.image_header().unwrap();
+        pixels::decode(png, &header).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let png = testing_png(6, &[&[0, 255, 128, 0, 255, 10, 20, 30, 0], &[0, 200, 100, 50, 128, 1, 2, 3, 4]]);
+        let (cgbi, dropped) = to_cgbi(&png).unwrap();
+        assert!(dropped.is_empty());
+        assert!(is_cgbi(&cgbi));
+        assert_eq!(cgbi.chunks().len(), 4);
+        assert_eq!(to_cgbi(&cgbi), Err(CgbiError::AlreadyCgbi));
+
+        let (normal, _) = normalize(&cgbi).unwrap();
+        assert!(!is_cgbi(&normal));
+        assert_eq!(normalize(&normal), Err(CgbiError::NotCgbi));
+        let restored = pixels(&normal);
+        // Opaque pixels survive exactly; transparent ones lose their color.
+        assert_eq!(&restored[..4], &[255, 128, 0, 255]);
+        assert_eq!(&restored[4..8], &[0, 0, 0, 0]);
+        for (restored, original) in restored[8..].iter().zip([200, 100, 50, 128, 1, 2, 3, 4]) {
+            assert!((*restored as i16 - original as i16).abs() <= 1 || original < 8);
+        }
+    }
+
+    #[test]
+    fn test_conversion_drops_unsafe_to_copy() {
+        let mut png = testing_png(6, &[&[0, 1, 2, 3, 4, 5, 6, 7, 8]]);
+        png.insert_chunk(1, Chunk::new(ChunkType::from_str("prVT").unwrap(), vec![1]));
+        png.insert_chunk(1, Chunk::new(ChunkType::from_str("prVt").unwrap(), vec![2]));
+        png.set_trailer(b"tail".to_vec());
+
+        let (cgbi, dropped) = to_cgbi(&png).unwrap();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].chunk_type().to_string(), "prVT");
+        assert!(cgbi.chunk_by_type("prVt").is_some());
+        assert_eq!(cgbi.trailer(), b"tail");
+
+        let mut cgbi = cgbi;
+        cgbi.insert_chunk(2, Chunk::new(ChunkType::from_str("prVT").unwrap(), vec![1]));
+        let (normal, dropped) = normalize(&cgbi).unwrap();
+        assert_eq!(dropped.len(), 1);
+        assert!(normal.chunk_by_type("prVT").is_none());
+        assert!(normal.chunk_by_type("prVt").is_some());
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let mut png = testing_png(2, &[&[0, 1, 2, 3, 4, 5, 6]]);
+        let header = ImageHeader { width: 2, height: 1, bit_depth: 8, color_type: 0, interlaced: false };
+        png.set_chunk(header.encode());
+        assert_eq!(to_cgbi(&png), Err(CgbiError::Unsupported { bit_depth: 8, color_type: 0 }));
+    }
+}
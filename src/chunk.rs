@@ -1,13 +1,12 @@
-use std::fmt::{Display, Formatter, write};
-use std::io::Read;
+use std::fmt::{Display, Formatter};
 use crate::chunk_type::ChunkType;
-use anyhow::Result;
-use crc::{Crc, Algorithm, CRC_32_ISO_HDLC};
-use crate::png::PngError;
+use crate::registry::{ChunkData, DecodeError};
+use crc::{Crc, CRC_32_ISO_HDLC};
 
 pub const CASTAGNOLI: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
 
-#[derive(Eq, PartialEq, Debug)]
+/// A single PNG chunk: length, type, data and CRC as laid out on disk.
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub struct Chunk{
     length: u32,
     chunk_type: ChunkType,
@@ -15,12 +14,26 @@ pub struct Chunk{
     crc: u32,
 }
 
-#[derive(Debug)]
-pub struct ChunkError;
+/// Why a byte slice could not be read as a `Chunk`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChunkError {
+    /// The slice ends before the length, type, data or CRC field does.
+    Truncated,
+    /// The type field is not four ASCII letters.
+    InvalidType([u8; 4]),
+    /// The stored CRC does not match the type and data.
+    CrcMismatch { stored: u32, computed: u32 },
+}
 
 impl std::fmt::Display for ChunkError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Chunk Error")
+        match self {
+            ChunkError::Truncated => write!(f, "chunk is truncated"),
+            ChunkError::InvalidType(bytes) => write!(f, "invalid chunk type {:?}", bytes),
+            ChunkError::CrcMismatch { stored, computed } => {
+                write!(f, "chunk crc mismatch: stored {:08x}, computed {:08x}", stored, computed)
+            },
+        }
     }
 }
 
@@ -34,27 +47,13 @@ impl TryFrom<&[u8]> for Chunk {
     type Error = ChunkError;
     // TODO: Optimisation code here
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let length = u32::from_be_bytes(value[0..4].try_into().unwrap());
-        let chunk_type: &[u8; 4] = <&[u8; 4]>::try_from(&value[4..8]).unwrap();
-        println!("chunk_type: {:?}", String::from_utf8(chunk_type.to_vec()));
-        let data = &value[8..(8 + length as usize)];
-        let crc = u32::from_be_bytes(value[(8 + length as usize)..(12 + length as usize)].try_into().unwrap());
-        let mix = chunk_type
-            .iter()
-            .chain(data.iter())
-            .copied()
-            .collect::<Vec<_>>();
-        let real_crc = CASTAGNOLI.checksum(mix.as_slice());
-        if crc == real_crc {
-            Ok(Chunk { length,
-                chunk_type: {ChunkType::try_from(*chunk_type).unwrap()},
-                data: data.to_vec(),
-                crc
-            })
+        let chunk = Chunk::from_bytes_unchecked(value)?;
+        let computed = chunk.computed_crc();
+        if chunk.crc == computed {
+            Ok(chunk)
         } else {
-            Err(ChunkError)
+            Err(ChunkError::CrcMismatch { stored: chunk.crc, computed })
         }
-
     }
 }
 
@@ -62,23 +61,83 @@ impl Display for Chunk {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "chunk_type: {}\ndata: {}\nlength:{}\ncrc:{}",
                self.chunk_type,
-               String::from_utf8(self.data.clone()).unwrap(),
+               self.data_as_string().unwrap_or_else(|_| format!("{:?}", self.data)),
                self.length,
                self.crc
         )
     }
 }
 
+/// Chunks serialize as their type, length, CRC and base64 `data`. When
+/// deserializing, `data_hex` is accepted in place of `data` and the length
+/// and CRC are recomputed, so hand-edited descriptions stay valid.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Chunk {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use base64::Engine;
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Chunk", 4)?;
+        state.serialize_field("type", &self.chunk_type)?;
+        state.serialize_field("length", &self.length)?;
+        state.serialize_field("crc", &self.crc)?;
+        state.serialize_field("data", &base64::engine::general_purpose::STANDARD.encode(&self.data))?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Chunk {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use base64::Engine;
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        struct Repr {
+            #[serde(rename = "type")]
+            chunk_type: ChunkType,
+            data: Option<String>,
+            data_hex: Option<String>,
+        }
+
+        let repr = Repr::deserialize(deserializer)?;
+        let data = match (repr.data, repr.data_hex) {
+            (Some(data), None) => base64::engine::general_purpose::STANDARD
+                .decode(data)
+                .map_err(D::Error::custom)?,
+            (None, Some(hex)) => decode_hex(&hex).ok_or_else(|| D::Error::custom("invalid data_hex"))?,
+            (None, None) => vec![],
+            (Some(_), Some(_)) => return Err(D::Error::custom("only one of data and data_hex may be set")),
+        };
+        Ok(Chunk::new(repr.chunk_type, data))
+    }
+}
+
+#[cfg(feature = "serde")]
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = hex.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+fn checksum(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+    let mix = chunk_type
+        .bytes()
+        .iter()
+        .chain(data.iter())
+        .copied()
+        .collect::<Vec<_>>();
+    CASTAGNOLI.checksum(mix.as_slice())
+}
+
 impl Chunk {
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
-        let mix = chunk_type
-            .bytes()
-            .iter()
-            .chain(data.iter())
-            .copied()
-            .collect::<Vec<_>>();
         let length = data.len() as u32;
-        let crc = CASTAGNOLI.checksum(mix.as_slice());
+        let crc = checksum(&chunk_type, &data);
         Chunk {
             chunk_type,
             data,
@@ -87,6 +146,29 @@ impl Chunk {
         }
     }
 
+    /// Parses a chunk without verifying its CRC, so damaged files can still
+    /// be inspected. The stored CRC is kept as-is.
+    pub fn from_bytes_unchecked(value: &[u8]) -> Result<Chunk, ChunkError> {
+        if value.len() < 12 {
+            return Err(ChunkError::Truncated);
+        }
+        let length = u32::from_be_bytes(value[0..4].try_into().unwrap());
+        let type_bytes = <[u8; 4]>::try_from(&value[4..8]).unwrap();
+        let chunk_type = ChunkType::try_from(type_bytes).map_err(|_| ChunkError::InvalidType(type_bytes))?;
+        let end = 8usize
+            .checked_add(length as usize)
+            .filter(|end| end + 4 <= value.len())
+            .ok_or(ChunkError::Truncated)?;
+        let data = &value[8..end];
+        let crc = u32::from_be_bytes(value[end..end + 4].try_into().unwrap());
+        Ok(Chunk {
+            length,
+            chunk_type,
+            data: data.to_vec(),
+            crc,
+        })
+    }
+
     pub fn length(&self) -> u32 {
         self.length
     }
@@ -103,9 +185,24 @@ impl Chunk {
         self.crc
     }
 
-    pub fn data_as_string(&self) -> Result<String> {
-        let s = String::from_utf8(self.data.clone())?;
-        Ok(s)
+    /// The CRC the chunk should carry, which differs from `crc()` only for
+    /// chunks read with `from_bytes_unchecked`.
+    pub fn computed_crc(&self) -> u32 {
+        checksum(&self.chunk_type, &self.data)
+    }
+
+    /// Reads the chunk as `T`, checking first that it is one of the chunk
+    /// types `T` handles.
+    pub fn decode<T: ChunkData>(&self) -> Result<T, DecodeError> {
+        let chunk_type = self.chunk_type.to_string();
+        if !T::CHUNK_TYPES.contains(&chunk_type.as_str()) {
+            return Err(DecodeError::WrongType { expected: T::CHUNK_TYPES.join(" or "), found: chunk_type });
+        }
+        T::parse(self)
+    }
+
+    pub fn data_as_string(&self) -> Result<String, std::string::FromUtf8Error> {
+        String::from_utf8(self.data.clone())
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
@@ -142,12 +239,12 @@ mod tests {
             .copied()
             .collect();
 
-        Chunk::try_from(chunk_data.as_ref())?
+        Chunk::try_from(chunk_data.as_ref()).unwrap()
     }
 
     #[test]
     fn test_new_chunk() {
-        let chunk_type = ChunkType::from_str("RuSt")?;
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
         let data = "This is where your secret message will be!".as_bytes().to_vec();
         let chunk = Chunk::new(chunk_type, data);
         assert_eq!(chunk.length(), 42);
@@ -169,7 +266,7 @@ mod tests {
     #[test]
     fn test_chunk_string() {
         let chunk = testing_chunk();
-        let chunk_string = chunk.data_as_string()?;
+        let chunk_string = chunk.data_as_string().unwrap();
         let expected_chunk_string = String::from("This is where your secret message will be!");
         assert_eq!(chunk_string, expected_chunk_string);
     }
@@ -196,9 +293,9 @@ mod tests {
             .copied()
             .collect();
 
-        let chunk = Chunk::try_from(chunk_data.as_ref())?;
+        let chunk = Chunk::try_from(chunk_data.as_ref()).unwrap();
 
-        let chunk_string = chunk.data_as_string()?;
+        let chunk_string = chunk.data_as_string().unwrap();
         let expected_chunk_string = String::from("This is where your secret message will be!");
 
         assert_eq!(chunk.length(), 42);
@@ -228,6 +325,44 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_unchecked_chunk_keeps_bad_crc() {
+        let mut chunk_data = testing_chunk().as_bytes();
+        let last = chunk_data.len() - 1;
+        chunk_data[last] ^= 1;
+
+        let chunk = Chunk::from_bytes_unchecked(chunk_data.as_ref()).unwrap();
+        assert_eq!(chunk.crc(), 2882656335);
+        assert_eq!(chunk.computed_crc(), 2882656334);
+    }
+
+    #[test]
+    fn test_truncated_chunk_from_bytes() {
+        let chunk_data = testing_chunk().as_bytes();
+        assert_eq!(Chunk::try_from(&chunk_data[..20]), Err(ChunkError::Truncated));
+        assert!(Chunk::from_bytes_unchecked(&chunk_data[..8]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_chunk_serde() {
+        let chunk = testing_chunk();
+        let json = serde_json::to_string(&chunk).unwrap();
+        assert!(json.starts_with("{\"type\":\"RuSt\",\"length\":42,\"crc\":2882656334,\"data\":\"VGhpcyBp"));
+        assert_eq!(serde_json::from_str::<Chunk>(&json).unwrap(), chunk);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_chunk_deserialize_recomputes_crc() {
+        let chunk: Chunk = serde_json::from_str(r#"{"type": "RuSt", "crc": 1, "data_hex": "68 65 79"}"#).unwrap();
+        assert_eq!(chunk.data(), b"hey");
+        assert_eq!(chunk.length(), 3);
+        assert_eq!(chunk.crc(), chunk.computed_crc());
+
+        assert!(serde_json::from_str::<Chunk>(r#"{"type": "RuSt", "data_hex": "6"}"#).is_err());
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -244,7 +379,7 @@ mod tests {
             .copied()
             .collect();
 
-        let chunk: Chunk = TryFrom::try_from(chunk_data.as_ref())?;
+        let chunk: Chunk = TryFrom::try_from(chunk_data.as_ref()).unwrap();
 
         let _chunk_string = format!("{}", chunk);
     }
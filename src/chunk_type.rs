@@ -1,7 +1,9 @@
-use std::fmt::{Display, Formatter, write};
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
-use anyhow::{Result, Error};
-#[derive(Eq, PartialEq, Debug)]
+
+/// The four-letter type code of a chunk, e.g. `IHDR` or `ruSt`. The case of
+/// each letter carries a property bit, exposed by the `is_*` methods.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 pub struct ChunkType {
     chunk_type: [u8; 4],
 }
@@ -11,6 +13,7 @@ enum ChunkTypeErrorKind {
     TooManyChars,
 }
 
+/// Returned when a chunk type is not exactly four ASCII letters.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ParseChunkTypeError {
     kind: ChunkTypeErrorKind,
@@ -33,7 +36,7 @@ impl TryFrom<[u8; 4]> for ChunkType {
 
 impl Display for ParseChunkTypeError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "ParseChunkTypeError!")
+        write!(f, "chunk type must be four ASCII letters")
     }
 }
 
@@ -59,13 +62,39 @@ impl Display for ChunkType {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for ChunkType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ChunkType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        ChunkType::from_str(&s).map_err(|_| serde::de::Error::custom(format!("invalid chunk type {:?}", s)))
+    }
+}
+
 impl ChunkType {
+    /// Chunk types registered by the PNG specification, its APNG additions
+    /// and the public extensions. Their meaning is understood, so image
+    /// edits may carry them over even when they are unsafe-to-copy.
+    pub const KNOWN_CHUNK_TYPES: [[u8; 4]; 29] = [
+        *b"IHDR", *b"PLTE", *b"IDAT", *b"IEND",
+        *b"tRNS", *b"cHRM", *b"gAMA", *b"iCCP", *b"sBIT", *b"sRGB", *b"cICP", *b"mDCV", *b"cLLI",
+        *b"tEXt", *b"zTXt", *b"iTXt", *b"bKGD", *b"hIST", *b"pHYs", *b"sPLT", *b"eXIf", *b"tIME",
+        *b"acTL", *b"fcTL", *b"fdAT",
+        *b"oFFs", *b"pCAL", *b"sCAL", *b"sTER",
+    ];
+
     pub fn bytes(&self) -> [u8; 4] {
         self.chunk_type
     }
 
     pub fn is_valid(&self) -> bool {
-        self.chunk_type.iter().all(|c| c.is_ascii_alphabetic()) && self.chunk_type[2].is_ascii_uppercase()
+        self.chunk_type.iter().all(|c| c.is_ascii_alphabetic()) && self.is_reserved_bit_valid()
     }
 
     pub fn is_critical(&self) -> bool {
@@ -83,6 +112,10 @@ impl ChunkType {
     pub fn is_safe_to_copy(&self) -> bool {
         self.chunk_type[3].is_ascii_lowercase()
     }
+
+    pub fn is_known(&self) -> bool {
+        ChunkType::KNOWN_CHUNK_TYPES.contains(&self.chunk_type)
+    }
 }
 
 
@@ -155,6 +188,12 @@ mod tests {
         assert!(!chunk.is_safe_to_copy());
     }
 
+    #[test]
+    pub fn test_chunk_type_is_known() {
+        assert!(ChunkType::from_str("tRNS").unwrap().is_known());
+        assert!(!ChunkType::from_str("RuSt").unwrap().is_known());
+    }
+
     #[test]
     pub fn test_valid_chunk_is_valid() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -176,6 +215,15 @@ mod tests {
         assert_eq!(&chunk.to_string(), "RuSt");
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    pub fn test_chunk_type_serde() {
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        assert_eq!(serde_json::to_string(&chunk).unwrap(), "\"RuSt\"");
+        assert_eq!(serde_json::from_str::<ChunkType>("\"RuSt\"").unwrap(), chunk);
+        assert!(serde_json::from_str::<ChunkType>("\"Ru5t\"").is_err());
+    }
+
     #[test]
     pub fn test_chunk_type_trait_impls() {
         let chunk_type_1: ChunkType = TryFrom::try_from([82, 117, 83, 116]).unwrap();
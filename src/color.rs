@@ -0,0 +1,528 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::bytes::{be_u16, be_u32};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::{ImageHeader, Png};
+use crate::registry::{ChunkData, DecodeError};
+
+/// The chunk types this module knows, in the order they are shown.
+pub const COLOR_CHUNKS: [&str; 6] = ["gAMA", "cHRM", "sRGB", "cICP", "mDCV", "cLLI"];
+
+/// Why a color chunk could not be read or built.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ColorError {
+    /// The chunk is not one of [`COLOR_CHUNKS`].
+    UnknownChunk(String),
+    /// The chunk data has the wrong size for its type.
+    InvalidLength { chunk_type: String, length: usize },
+    /// A field is out of range, or a value given on the command line does
+    /// not parse.
+    InvalidValue(String),
+    /// `sRGB` may not appear together with `iCCP`.
+    ConflictsWithIcc,
+}
+
+impl Display for ColorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorError::UnknownChunk(chunk_type) => {
+                write!(f, "{} is not a color chunk, expected one of {}", chunk_type, COLOR_CHUNKS.join(", "))
+            },
+            ColorError::InvalidLength { chunk_type, length } => write!(f, "{} chunk has invalid length {}", chunk_type, length),
+            ColorError::InvalidValue(e) => write!(f, "{}", e),
+            ColorError::ConflictsWithIcc => write!(f, "png has an iCCP chunk, which may not appear with sRGB"),
+        }
+    }
+}
+
+impl std::error::Error for ColorError {}
+
+fn invalid(message: impl Into<String>) -> ColorError {
+    ColorError::InvalidValue(message.into())
+}
+
+/// Parses `count` comma-separated numbers.
+fn numbers(s: &str, count: usize, what: &str) -> Result<Vec<f64>, ColorError> {
+    let values = s.split(',').map(|v| v.trim().parse::<f64>()).collect::<Result<Vec<_>, _>>();
+    match values {
+        Ok(values) if values.len() == count && values.iter().all(|v| v.is_finite() && *v >= 0.0) => Ok(values),
+        _ => Err(invalid(format!("{} takes {} comma-separated non-negative numbers", what, count))),
+    }
+}
+
+/// Scales `value` to a fixed-point field, rejecting what does not fit.
+fn fixed<T: TryFrom<u64>>(value: f64, scale: f64, what: &str) -> Result<T, ColorError> {
+    let scaled = (value * scale).round();
+    if scaled > u64::MAX as f64 {
+        return Err(invalid(format!("{} {} is out of range", what, value)));
+    }
+    T::try_from(scaled as u64).map_err(|_| invalid(format!("{} {} is out of range", what, value)))
+}
+
+/// `sRGB` rendering intents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual = 0,
+    RelativeColorimetric = 1,
+    Saturation = 2,
+    AbsoluteColorimetric = 3,
+}
+
+impl TryFrom<u8> for RenderingIntent {
+    type Error = ColorError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(RenderingIntent::Perceptual),
+            1 => Ok(RenderingIntent::RelativeColorimetric),
+            2 => Ok(RenderingIntent::Saturation),
+            3 => Ok(RenderingIntent::AbsoluteColorimetric),
+            _ => Err(invalid(format!("unknown rendering intent {}", value))),
+        }
+    }
+}
+
+impl FromStr for RenderingIntent {
+    type Err = ColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "perceptual" => Ok(RenderingIntent::Perceptual),
+            "relative" | "relative-colorimetric" => Ok(RenderingIntent::RelativeColorimetric),
+            "saturation" => Ok(RenderingIntent::Saturation),
+            "absolute" | "absolute-colorimetric" => Ok(RenderingIntent::AbsoluteColorimetric),
+            _ => s.parse::<u8>().map_err(|_| invalid(format!("unknown rendering intent {:?}", s)))?.try_into(),
+        }
+    }
+}
+
+impl Display for RenderingIntent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            RenderingIntent::Perceptual => "perceptual",
+            RenderingIntent::RelativeColorimetric => "relative colorimetric",
+            RenderingIntent::Saturation => "saturation",
+            RenderingIntent::AbsoluteColorimetric => "absolute colorimetric",
+        })
+    }
+}
+
+/// `cHRM`: CIE 1931 xy chromaticities times 100000.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chromaticities {
+    pub white: (u32, u32),
+    pub red: (u32, u32),
+    pub green: (u32, u32),
+    pub blue: (u32, u32),
+}
+
+/// `cICP`: coding-independent code points from ITU-T H.273.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CodingPoints {
+    pub primaries: u8,
+    pub transfer: u8,
+    /// Always 0 in PNG, which only stores RGB.
+    pub matrix: u8,
+    pub full_range: bool,
+}
+
+impl CodingPoints {
+    fn primaries_name(&self) -> Option<&'static str> {
+        match self.primaries {
+            1 => Some("BT.709"),
+            9 => Some("BT.2020"),
+            11 => Some("DCI-P3"),
+            12 => Some("Display P3"),
+            _ => None,
+        }
+    }
+
+    fn transfer_name(&self) -> Option<&'static str> {
+        match self.transfer {
+            1 | 6 | 14 | 15 => Some("BT.709"),
+            8 => Some("linear"),
+            13 => Some("sRGB"),
+            16 => Some("PQ"),
+            18 => Some("HLG"),
+            _ => None,
+        }
+    }
+}
+
+/// `mDCV`: the mastering display's primaries and white point in units of
+/// 0.00002, and its luminance range in units of 0.0001 cd/m².
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MasteringDisplay {
+    pub red: (u16, u16),
+    pub green: (u16, u16),
+    pub blue: (u16, u16),
+    pub white: (u16, u16),
+    pub max_luminance: u32,
+    pub min_luminance: u32,
+}
+
+/// `cLLI`: maximum content and frame-average light levels in units of
+/// 0.0001 cd/m².
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ContentLight {
+    pub max_content: u32,
+    pub max_frame_average: u32,
+}
+
+/// One of the color-space chunks, decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChunk {
+    /// `gAMA`: the file gamma times 100000.
+    Gamma(u32),
+    Chromaticities(Chromaticities),
+    Srgb(RenderingIntent),
+    CodingPoints(CodingPoints),
+    MasteringDisplay(MasteringDisplay),
+    ContentLight(ContentLight),
+}
+
+impl ColorChunk {
+    /// Decodes `data` as a chunk of type `chunk_type`.
+    pub fn decode(chunk_type: &str, data: &[u8]) -> Result<ColorChunk, ColorError> {
+        let expected = match chunk_type {
+            "gAMA" | "cICP" => 4,
+            "cHRM" => 32,
+            "sRGB" => 1,
+            "mDCV" => 24,
+            "cLLI" => 8,
+            _ => return Err(ColorError::UnknownChunk(chunk_type.to_string())),
+        };
+        if data.len() != expected {
+            return Err(ColorError::InvalidLength { chunk_type: chunk_type.to_string(), length: data.len() });
+        }
+        let pair = |at: usize| (be_u32(data, at), be_u32(data, at + 4));
+        let pair16 = |at: usize| (be_u16(data, at), be_u16(data, at + 2));
+        let ret = match chunk_type {
+            "gAMA" => ColorChunk::Gamma(be_u32(data, 0)),
+            "cHRM" => ColorChunk::Chromaticities(Chromaticities {
+                white: pair(0),
+                red: pair(8),
+                green: pair(16),
+                blue: pair(24),
+            }),
+            "sRGB" => ColorChunk::Srgb(RenderingIntent::try_from(data[0])?),
+            "cICP" => ColorChunk::CodingPoints(CodingPoints {
+                primaries: data[0],
+                transfer: data[1],
+                matrix: data[2],
+                full_range: data[3] == 1,
+            }),
+            "mDCV" => ColorChunk::MasteringDisplay(MasteringDisplay {
+                red: pair16(0),
+                green: pair16(4),
+                blue: pair16(8),
+                white: pair16(12),
+                max_luminance: be_u32(data, 16),
+                min_luminance: be_u32(data, 20),
+            }),
+            _ => ColorChunk::ContentLight(ContentLight {
+                max_content: be_u32(data, 0),
+                max_frame_average: be_u32(data, 4),
+            }),
+        };
+        if chunk_type == "cICP" && (data[3] > 1 || data[2] != 0) {
+            return Err(invalid("cICP must have matrix coefficients 0 and a full range flag of 0 or 1"));
+        }
+        ret.validate()?;
+        Ok(ret)
+    }
+
+    /// Builds a chunk from the command line form of its value:
+    ///
+    /// * `gAMA`: the file gamma, e.g. `0.45455`
+    /// * `cHRM`: `wx,wy,rx,ry,gx,gy,bx,by`
+    /// * `sRGB`: `perceptual`, `relative`, `saturation` or `absolute`
+    /// * `cICP`: `primaries,transfer,matrix,full_range`, e.g. `9,16,0,1`
+    /// * `mDCV`: `rx,ry,gx,gy,bx,by,wx,wy,max,min` with luminance in cd/m²
+    /// * `cLLI`: `max_content,max_frame_average` in cd/m²
+    pub fn parse(chunk_type: &str, value: &str) -> Result<ColorChunk, ColorError> {
+        let ret = match chunk_type {
+            "gAMA" => ColorChunk::Gamma(fixed(numbers(value, 1, "gAMA")?[0], 100000.0, "gamma")?),
+            "cHRM" => {
+                let v = numbers(value, 8, "cHRM")?;
+                let xy = |i: usize| -> Result<(u32, u32), ColorError> {
+                    Ok((fixed(v[i], 100000.0, "chromaticity")?, fixed(v[i + 1], 100000.0, "chromaticity")?))
+                };
+                ColorChunk::Chromaticities(Chromaticities { white: xy(0)?, red: xy(2)?, green: xy(4)?, blue: xy(6)? })
+            },
+            "sRGB" => ColorChunk::Srgb(RenderingIntent::from_str(value)?),
+            "cICP" => {
+                let v = value.split(',').map(|v| v.trim().parse::<u8>()).collect::<Result<Vec<_>, _>>();
+                match v.as_deref() {
+                    Ok(&[primaries, transfer, 0, full_range @ (0 | 1)]) => ColorChunk::CodingPoints(CodingPoints {
+                        primaries,
+                        transfer,
+                        matrix: 0,
+                        full_range: full_range == 1,
+                    }),
+                    _ => return Err(invalid("cICP takes primaries,transfer,0,full_range with full_range 0 or 1")),
+                }
+            },
+            "mDCV" => {
+                let v = numbers(value, 10, "mDCV")?;
+                let xy = |i: usize| -> Result<(u16, u16), ColorError> {
+                    Ok((fixed(v[i], 50000.0, "chromaticity")?, fixed(v[i + 1], 50000.0, "chromaticity")?))
+                };
+                ColorChunk::MasteringDisplay(MasteringDisplay {
+                    red: xy(0)?,
+                    green: xy(2)?,
+                    blue: xy(4)?,
+                    white: xy(6)?,
+                    max_luminance: fixed(v[8], 10000.0, "luminance")?,
+                    min_luminance: fixed(v[9], 10000.0, "luminance")?,
+                })
+            },
+            "cLLI" => {
+                let v = numbers(value, 2, "cLLI")?;
+                ColorChunk::ContentLight(ContentLight {
+                    max_content: fixed(v[0], 10000.0, "light level")?,
+                    max_frame_average: fixed(v[1], 10000.0, "light level")?,
+                })
+            },
+            _ => return Err(ColorError::UnknownChunk(chunk_type.to_string())),
+        };
+        ret.validate()?;
+        Ok(ret)
+    }
+
+    fn validate(&self) -> Result<(), ColorError> {
+        match self {
+            ColorChunk::Gamma(0) => Err(invalid("gamma must not be 0")),
+            ColorChunk::MasteringDisplay(display) if display.min_luminance >= display.max_luminance => {
+                Err(invalid("mDCV minimum luminance must be below the maximum"))
+            },
+            ColorChunk::ContentLight(light) if light.max_frame_average > light.max_content => {
+                Err(invalid("cLLI frame-average light level must not exceed the maximum"))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn chunk_type(&self) -> &'static str {
+        match self {
+            ColorChunk::Gamma(_) => "gAMA",
+            ColorChunk::Chromaticities(_) => "cHRM",
+            ColorChunk::Srgb(_) => "sRGB",
+            ColorChunk::CodingPoints(_) => "cICP",
+            ColorChunk::MasteringDisplay(_) => "mDCV",
+            ColorChunk::ContentLight(_) => "cLLI",
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = vec![];
+        match self {
+            ColorChunk::Gamma(gamma) => data.extend_from_slice(&gamma.to_be_bytes()),
+            ColorChunk::Chromaticities(c) => {
+                for (x, y) in [c.white, c.red, c.green, c.blue] {
+                    data.extend_from_slice(&x.to_be_bytes());
+                    data.extend_from_slice(&y.to_be_bytes());
+                }
+            },
+            ColorChunk::Srgb(intent) => data.push(*intent as u8),
+            ColorChunk::CodingPoints(c) => data.extend_from_slice(&[c.primaries, c.transfer, c.matrix, c.full_range as u8]),
+            ColorChunk::MasteringDisplay(d) => {
+                for (x, y) in [d.red, d.green, d.blue, d.white] {
+                    data.extend_from_slice(&x.to_be_bytes());
+                    data.extend_from_slice(&y.to_be_bytes());
+                }
+                data.extend_from_slice(&d.max_luminance.to_be_bytes());
+                data.extend_from_slice(&d.min_luminance.to_be_bytes());
+            },
+            ColorChunk::ContentLight(l) => {
+                data.extend_from_slice(&l.max_content.to_be_bytes());
+                data.extend_from_slice(&l.max_frame_average.to_be_bytes());
+            },
+        }
+        Chunk::new(ChunkType::from_str(self.chunk_type()).unwrap(), data)
+    }
+
+    /// Replaces the chunk of this type in `png`. `sRGB` is refused while
+    /// the PNG has an `iCCP` profile.
+    pub fn write_to_png(&self, png: &mut Png) -> Result<(), ColorError> {
+        if matches!(self, ColorChunk::Srgb(_)) && png.chunk_by_type("iCCP").is_some() {
+            return Err(ColorError::ConflictsWithIcc);
+        }
+        png.set_chunk(self.to_chunk());
+        Ok(())
+    }
+}
+
+impl Display for ColorChunk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let xy = |(x, y): (u32, u32)| format!("({:.4}, {:.4})", x as f64 / 100000.0, y as f64 / 100000.0);
+        let xy16 = |(x, y): (u16, u16)| format!("({:.4}, {:.4})", x as f64 / 50000.0, y as f64 / 50000.0);
+        let nits = |v: u32| v as f64 / 10000.0;
+        match self {
+            ColorChunk::Gamma(gamma) => {
+                write!(f, "gamma {:.5} (display {:.2})", *gamma as f64 / 100000.0, 100000.0 / *gamma as f64)
+            },
+            ColorChunk::Chromaticities(c) => write!(f, "white {} red {} green {} blue {}",
+                                                    xy(c.white), xy(c.red), xy(c.green), xy(c.blue)),
+            ColorChunk::Srgb(intent) => write!(f, "{}", intent),
+            ColorChunk::CodingPoints(c) => {
+                match c.primaries_name() {
+                    Some(name) => write!(f, "{} primaries", name)?,
+                    None => write!(f, "primaries {}", c.primaries)?,
+                }
+                match c.transfer_name() {
+                    Some(name) => write!(f, ", {} transfer", name)?,
+                    None => write!(f, ", transfer {}", c.transfer)?,
+                }
+                write!(f, ", {} range", if c.full_range { "full" } else { "narrow" })
+            },
+            ColorChunk::MasteringDisplay(d) => write!(f, "red {} green {} blue {} white {}, {}-{} cd/m²",
+                                                      xy16(d.red), xy16(d.green), xy16(d.blue), xy16(d.white),
+                                                      nits(d.min_luminance), nits(d.max_luminance)),
+            ColorChunk::ContentLight(l) => write!(f, "max {} cd/m², frame average {} cd/m²",
+                                                  nits(l.max_content), nits(l.max_frame_average)),
+        }
+    }
+}
+
+impl ChunkData for ColorChunk {
+    const CHUNK_TYPES: &'static [&'static str] = &["gAMA", "cHRM", "sRGB", "cICP", "mDCV", "cLLI"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        ColorChunk::decode(&chunk.chunk_type().to_string(), chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        self.to_chunk()
+    }
+
+    fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// `sBIT`: how many bits of each sample were significant in the source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignificantBits(pub Vec<u8>);
+
+impl SignificantBits {
+    /// Decodes `data` for an image with `header`: one value per channel,
+    /// with indexed images counting the three palette channels.
+    pub fn decode(data: &[u8], header: &ImageHeader) -> Result<SignificantBits, ColorError> {
+        let (channels, depth) = match header.is_indexed() {
+            true => (3, 8),
+            false => (header.channels() as usize, header.bit_depth),
+        };
+        if data.len() != channels {
+            return Err(ColorError::InvalidLength { chunk_type: "sBIT".to_string(), length: data.len() });
+        }
+        if data.iter().any(|&bits| bits == 0 || bits > depth) {
+            return Err(invalid(format!("sBIT values must be 1 to {}", depth)));
+        }
+        Ok(SignificantBits(data.to_vec()))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::from_str("sBIT").unwrap(), self.0.clone())
+    }
+}
+
+impl Display for SignificantBits {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let bits: Vec<String> = self.0.iter().map(|bits| bits.to_string()).collect();
+        write!(f, "{} significant bits", bits.join("/"))
+    }
+}
+
+/// The color chunks in `png` in [`COLOR_CHUNKS`] order, each decoded or
+/// with the reason it could not be.
+pub fn color_chunks(png: &Png) -> Vec<(&'static str, Result<ColorChunk, ColorError>)> {
+    COLOR_CHUNKS
+        .iter()
+        .filter_map(|&chunk_type| {
+            let chunk = png.chunk_by_type(chunk_type)?;
+            Some((chunk_type, ColorChunk::decode(chunk_type, chunk.data())))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let values = [
+            ("gAMA", "0.45455"),
+            ("cHRM", "0.3127,0.329,0.64,0.33,0.3,0.6,0.15,0.06"),
+            ("sRGB", "relative"),
+            ("cICP", "9,16,0,1"),
+            ("mDCV", "0.708,0.292,0.17,0.797,0.131,0.046,0.3127,0.329,1000,0.0001"),
+            ("cLLI", "1000,400"),
+        ];
+        for (chunk_type, value) in values {
+            let color = ColorChunk::parse(chunk_type, value).unwrap();
+            let chunk = color.to_chunk();
+            assert_eq!(chunk.chunk_type().to_string(), chunk_type);
+            assert_eq!(ColorChunk::decode(chunk_type, chunk.data()), Ok(color));
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let show = |chunk_type, value| ColorChunk::parse(chunk_type, value).unwrap().to_string();
+        assert_eq!(show("gAMA", "0.45455"), "gamma 0.45455 (display 2.20)");
+        assert_eq!(show("sRGB", "0"), "perceptual");
+        assert_eq!(show("cICP", "9,16,0,1"), "BT.2020 primaries, PQ transfer, full range");
+        assert_eq!(show("cICP", "200,1,0,0"), "primaries 200, BT.709 transfer, narrow range");
+        assert_eq!(show("cLLI", "1000,400"), "max 1000 cd/m², frame average 400 cd/m²");
+    }
+
+    #[test]
+    fn test_invalid_color() {
+        assert!(matches!(ColorChunk::parse("gAMA", "0"), Err(ColorError::InvalidValue(_))));
+        assert!(matches!(ColorChunk::parse("cHRM", "0.3,0.3"), Err(ColorError::InvalidValue(_))));
+        assert!(matches!(ColorChunk::parse("sRGB", "vivid"), Err(ColorError::InvalidValue(_))));
+        assert!(matches!(ColorChunk::parse("cICP", "9,16,1,1"), Err(ColorError::InvalidValue(_))));
+        assert!(matches!(ColorChunk::parse("mDCV", "2,0,0,0,0,0,0,0,1,0"), Err(ColorError::InvalidValue(_))));
+        assert!(matches!(ColorChunk::parse("cLLI", "100,400"), Err(ColorError::InvalidValue(_))));
+        assert_eq!(ColorChunk::parse("tIME", "0"), Err(ColorError::UnknownChunk("tIME".to_string())));
+        assert_eq!(ColorChunk::decode("sRGB", &[0, 0]),
+                   Err(ColorError::InvalidLength { chunk_type: "sRGB".to_string(), length: 2 }));
+        assert!(matches!(ColorChunk::decode("sRGB", &[4]), Err(ColorError::InvalidValue(_))));
+    }
+
+    #[test]
+    fn test_significant_bits() {
+        let rgba = ImageHeader { width: 1, height: 1, bit_depth: 8, color_type: 6, interlaced: false };
+        assert_eq!(SignificantBits::decode(&[5, 6, 5, 8], &rgba).unwrap().to_string(), "5/6/5/8 significant bits");
+        assert!(matches!(SignificantBits::decode(&[5, 6, 5], &rgba), Err(ColorError::InvalidLength { .. })));
+        assert!(matches!(SignificantBits::decode(&[5, 6, 5, 9], &rgba), Err(ColorError::InvalidValue(_))));
+        let indexed = ImageHeader { bit_depth: 2, color_type: 3, ..rgba };
+        assert!(SignificantBits::decode(&[8, 8, 8], &indexed).is_ok());
+    }
+
+    #[test]
+    fn test_write_to_png() {
+        let mut png = testing_png();
+        ColorChunk::parse("gAMA", "0.45455").unwrap().write_to_png(&mut png).unwrap();
+        ColorChunk::parse("gAMA", "1").unwrap().write_to_png(&mut png).unwrap();
+        ColorChunk::parse("cLLI", "1000,400").unwrap().write_to_png(&mut png).unwrap();
+        let found = color_chunks(&png);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0], ("gAMA", Ok(ColorChunk::Gamma(100000))));
+        assert_eq!(png.chunks().last().unwrap().chunk_type().to_string(), "IEND");
+
+        png.insert_chunk(1, Chunk::new(ChunkType::from_str("iCCP").unwrap(), vec![]));
+        assert_eq!(ColorChunk::Srgb(RenderingIntent::Perceptual).write_to_png(&mut png), Err(ColorError::ConflictsWithIcc));
+    }
+}
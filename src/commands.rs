@@ -15,4 +15,92 @@ pub enum Commands {
     Decode(args::Decode),
     Remove(args::Remove),
     Print(args::Print),
-}
\ No newline at end of file
+    Strip(args::Strip),
+    /// Show the chunks added, removed, moved or changed between two pngs
+    Diff(args::Diff),
+    /// Set the print resolution in pHYs
+    SetDpi(args::SetDpi),
+    /// Set the modification time in tIME
+    Touch(args::Touch),
+    /// Convert an iOS-optimized CgBI png to a standard png, or back
+    Normalize(args::Normalize),
+    /// Write out the data appended after IEND
+    ExtractTrailer(args::ExtractTrailer),
+    /// List, extract and tag frames of animated PNGs
+    #[command(subcommand)]
+    Apng(ApngCommands),
+    /// View and edit the EXIF tags in eXIf
+    #[command(subcommand)]
+    Exif(ExifCommands),
+    /// View and edit the XMP properties in iTXt
+    #[command(subcommand)]
+    Xmp(XmpCommands),
+    /// Extract or embed the ICC color profile in iCCP
+    #[command(subcommand)]
+    Icc(IccCommands),
+    /// View, set or clear gAMA, cHRM, sRGB, cICP, mDCV and cLLI
+    #[command(subcommand)]
+    Color(ColorCommands),
+    /// Show, render or edit the palette of indexed PNGs
+    #[command(subcommand)]
+    Palette(PaletteCommands),
+    /// Build or check files that are both a PNG and a ZIP
+    #[command(subcommand)]
+    Polyglot(PolyglotCommands),
+    #[cfg(feature = "serde")]
+    Dump(args::Dump),
+    #[cfg(feature = "serde")]
+    Build(args::Build),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ApngCommands {
+    List(args::ApngList),
+    Extract(args::ApngExtract),
+    Encode(args::ApngEncode),
+    Decode(args::ApngDecode),
+    Build(args::ApngBuild),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExifCommands {
+    Show(args::ExifShow),
+    Set(args::ExifSet),
+    Remove(args::ExifRemove),
+    /// Remove the GPS directory, i.e. the location the photo was taken at
+    StripGps(args::ExifStripGps),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum XmpCommands {
+    Show(args::XmpShow),
+    Set(args::XmpSet),
+    Remove(args::XmpRemove),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum IccCommands {
+    Extract(args::IccExtract),
+    Embed(args::IccEmbed),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ColorCommands {
+    Show(args::ColorShow),
+    Set(args::ColorSet),
+    Clear(args::ColorClear),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PaletteCommands {
+    Show(args::PaletteShow),
+    /// Render the palette as a png of color squares
+    Swatch(args::PaletteSwatch),
+    Set(args::PaletteSet),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PolyglotCommands {
+    Build(args::PolyglotBuild),
+    Verify(args::PolyglotVerify),
+}
@@ -0,0 +1,296 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::apng::Animation;
+use crate::bmp::Bmp;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::gif::Gif;
+use crate::jpeg::Jpeg;
+use crate::inspect;
+use crate::png::Png;
+use crate::tiff::Tiff;
+use crate::webp::Webp;
+use crate::Error;
+
+/// Image formats recognised by their magic bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Jpeg,
+    WebP,
+    Gif,
+    Bmp,
+    Tiff,
+}
+
+impl Format {
+    pub fn detect(bytes: &[u8]) -> Option<Format> {
+        if bytes.starts_with(&Png::STANDARD_HEADER) {
+            Some(Format::Png)
+        } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Format::Jpeg)
+        } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some(Format::WebP)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(Format::Gif)
+        } else if bytes.starts_with(b"BM") {
+            Some(Format::Bmp)
+        } else if bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*") {
+            Some(Format::Tiff)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Format::Png => "png",
+            Format::Jpeg => "jpeg",
+            Format::WebP => "webp",
+            Format::Gif => "gif",
+            Format::Bmp => "bmp",
+            Format::Tiff => "tiff",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One top-level piece of a container: a PNG chunk, a JPEG segment, a RIFF
+/// chunk and so on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub offset: usize,
+    pub name: String,
+    pub length: usize,
+    pub summary: Option<String>,
+}
+
+/// Where formats with more than one option store a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Carrier {
+    /// A private application segment or extension, the default.
+    App,
+    /// A comment segment or extension.
+    Comment,
+}
+
+impl FromStr for Carrier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "app" => Ok(Carrier::App),
+            "comment" => Ok(Carrier::Comment),
+            _ => Err(format!("unknown carrier {:?}, expected app or comment", s)),
+        }
+    }
+}
+
+/// What every carrier format supports. Messages are addressed by a
+/// four-letter key; each format decides how the key is stored (the chunk
+/// type for PNG, for example).
+pub trait Container {
+    fn parse(bytes: &[u8]) -> Result<Self, Error>
+    where
+        Self: Sized;
+
+    fn format(&self) -> Format;
+
+    fn segments(&self) -> Vec<Segment>;
+
+    /// Segments that carry metadata rather than image data, for formats
+    /// where those are worth listing on their own (GIF extensions).
+    fn metadata_segments(&self) -> Vec<Segment> {
+        vec![]
+    }
+
+    /// Stores `message` under `key`. Returns the names of segments the format
+    /// had to drop as a side effect.
+    fn insert_message(&mut self, key: &str, message: &[u8]) -> Result<Vec<String>, Error>;
+
+    /// Like `insert_message`, but stores the message in the named carrier
+    /// (`app` or `comment`, for example) on formats that have more than one.
+    fn insert_message_in(&mut self, key: &str, message: &[u8], carrier: &str) -> Result<Vec<String>, Error> {
+        let _ = (key, message);
+        Err(Error::UnsupportedCarrier { format: self.format(), carrier: carrier.to_string() })
+    }
+
+    fn extract_message(&self, key: &str) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Removes the message stored under `key`. Returns the names of segments
+    /// the format had to drop as a side effect.
+    fn remove_message(&mut self, key: &str) -> Result<Vec<String>, Error>;
+
+    fn to_bytes(&self) -> Vec<u8>;
+}
+
+/// Detects the format of `bytes` and parses it.
+pub fn open(bytes: &[u8]) -> Result<Box<dyn Container>, Error> {
+    match Format::detect(bytes) {
+        Some(Format::Png) => Ok(Box::new(Png::parse(bytes)?)),
+        Some(Format::Jpeg) => Ok(Box::new(Jpeg::parse(bytes)?)),
+        Some(Format::WebP) => Ok(Box::new(Webp::parse(bytes)?)),
+        Some(Format::Gif) => Ok(Box::new(Gif::parse(bytes)?)),
+        Some(Format::Bmp) => Ok(Box::new(Bmp::parse(bytes)?)),
+        Some(Format::Tiff) => Ok(Box::new(Tiff::parse(bytes)?)),
+        None => Err(Error::UnknownFormat),
+    }
+}
+
+/// Message keys follow the PNG chunk type rules: four ASCII letters.
+pub fn parse_key(key: &str) -> Result<[u8; 4], Error> {
+    Ok(ChunkType::from_str(key)?.bytes())
+}
+
+fn chunk_names(chunks: Vec<Chunk>) -> Vec<String> {
+    chunks.iter().map(|chunk| chunk.chunk_type().to_string()).collect()
+}
+
+/// Edits must not leave an animated PNG with a broken animation.
+fn check_animation(png: &Png) -> Result<(), Error> {
+    if Animation::is_animated(png) {
+        Animation::from_png(png)?;
+    }
+    Ok(())
+}
+
+impl Container for Png {
+    fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Png::try_from(bytes)?)
+    }
+
+    fn format(&self) -> Format {
+        Format::Png
+    }
+
+    fn segments(&self) -> Vec<Segment> {
+        let mut segments: Vec<Segment> = inspect::inspect(self)
+            .into_iter()
+            .map(|row| Segment {
+                offset: row.offset,
+                name: row.chunk_type,
+                length: row.length as usize,
+                summary: row.summary,
+            })
+            .collect();
+        if !self.trailer().is_empty() {
+            segments.push(Segment {
+                offset: self.trailer_offset(),
+                name: "trailer".to_string(),
+                length: self.trailer().len(),
+                summary: crate::trailer::TrailerKind::detect(self.trailer()).map(|kind| kind.to_string()),
+            });
+        }
+        segments
+    }
+
+    fn insert_message(&mut self, key: &str, message: &[u8]) -> Result<Vec<String>, Error> {
+        let chunk_type = crate::message_chunk_type(key)?;
+        let mut edited = self.clone();
+        let chunk = Chunk::new(chunk_type, message.to_vec());
+        let (_, dropped) = edited.edit_critical(|png| {
+            // a message already stored under `key` is replaced where it is
+            match png.chunks().iter().position(|other| *other.chunk_type() == chunk_type) {
+                Some(idx) => {
+                    png.remove_chunk_at(idx);
+                    png.insert_chunk(idx, chunk);
+                },
+                None => png.append_chunk(chunk),
+            }
+        });
+        check_animation(&edited)?;
+        *self = edited;
+        Ok(chunk_names(dropped))
+    }
+
+    fn extract_message(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let chunk_type = crate::message_chunk_type(key)?;
+        Ok(self.chunk_by_type(&chunk_type.to_string()).map(|chunk| chunk.data().to_vec()))
+    }
+
+    fn remove_message(&mut self, key: &str) -> Result<Vec<String>, Error> {
+        let chunk_type = crate::message_chunk_type(key)?;
+        let mut edited = self.clone();
+        let (removed, dropped) = edited.edit_critical(|png| png.remove_chunk(&chunk_type.to_string()));
+        removed?;
+        check_animation(&edited)?;
+        *self = edited;
+        Ok(chunk_names(dropped))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(Format::detect(&Png::STANDARD_HEADER), Some(Format::Png));
+        assert_eq!(Format::detect(&[0xFF, 0xD8, 0xFF, 0xE0]), Some(Format::Jpeg));
+        assert_eq!(Format::detect(b"RIFF\x10\0\0\0WEBPVP8 "), Some(Format::WebP));
+        assert_eq!(Format::detect(b"RIFF\x10\0\0\0WAVEfmt "), None);
+        assert_eq!(Format::detect(b"GIF89a"), Some(Format::Gif));
+        assert_eq!(Format::detect(b"MM\0*\0\0\0\x08"), Some(Format::Tiff));
+        assert_eq!(Format::detect(b"hello"), None);
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("ruSt").unwrap(), *b"ruSt");
+        assert!(parse_key("ru5t").is_err());
+    }
+
+    #[test]
+    fn test_open_unknown_format() {
+        assert!(matches!(open(b"hello"), Err(Error::UnknownFormat)));
+    }
+
+    #[test]
+    fn test_png_container() {
+        let mut png = open(&Png::STANDARD_HEADER).unwrap();
+        assert_eq!(png.format(), Format::Png);
+        png.insert_message("ruSt", b"hi").unwrap();
+        assert_eq!(png.segments()[0].name, "ruSt");
+        assert_eq!(png.segments()[0].offset, 8);
+
+        let mut png = open(&png.to_bytes()).unwrap();
+        assert_eq!(png.extract_message("ruSt").unwrap(), Some(b"hi".to_vec()));
+        png.insert_message("abCd", b"x").unwrap();
+        png.insert_message("ruSt", b"bye").unwrap();
+        let mut png = open(&png.to_bytes()).unwrap();
+        let names: Vec<String> = png.segments().into_iter().map(|segment| segment.name).collect();
+        assert_eq!(names, ["ruSt", "abCd"]);
+        assert_eq!(png.extract_message("ruSt").unwrap(), Some(b"bye".to_vec()));
+        png.remove_message("ruSt").unwrap();
+        assert_eq!(png.extract_message("ruSt").unwrap(), None);
+        assert!(matches!(png.insert_message_in("ruSt", b"hi", "app"), Err(Error::UnsupportedCarrier { .. })));
+    }
+
+    #[test]
+    fn test_png_container_keeps_animation_valid() {
+        use crate::apng::AnimationControl;
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            AnimationControl { num_frames: 0, num_plays: 0 }.to_chunk(),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ]);
+        png.insert_message("ruSt", b"hi").unwrap();
+        png.remove_message("ruSt").unwrap();
+        png.remove_chunk("acTL").unwrap();
+        png.insert_chunk(1, AnimationControl { num_frames: 1, num_plays: 0 }.to_chunk());
+        let before = png.as_bytes();
+        assert!(matches!(png.insert_message("ruSt", b"hi"), Err(Error::Apng(_))));
+        assert_eq!(png.as_bytes(), before);
+
+        png.append_chunk(Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hi".to_vec()));
+        let before = png.as_bytes();
+        assert!(matches!(png.remove_message("ruSt"), Err(Error::Apng(_))));
+        assert_eq!(png.as_bytes(), before);
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use crate::bytes::latin1;
+use crate::chunk::Chunk;
+use crate::inspect::{shorten, summarize};
+use crate::pixels;
+use crate::png::{ImageHeader, Png};
+use crate::text::{self, InternationalText};
+
+/// One field of a chunk that differs, e.g. `width` of `IHDR`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// How the decoded pixels compare when the `IDAT` bytes differ. Interlaced
+/// images are compared deinterlaced, and indexed ones by their colors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PixelMatch {
+    Identical,
+    Different,
+    /// `IHDR` changed in more than interlacing, so the pixels are not
+    /// comparable byte for byte.
+    HeaderChanged,
+    /// One of the images could not be decoded.
+    Undecodable(String),
+}
+
+/// The `IDAT` chunks of one image: how many and how many bytes in all.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageDataSize {
+    pub chunks: usize,
+    pub bytes: usize,
+}
+
+impl Display for ImageDataSize {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let plural = if self.chunks == 1 { "" } else { "s" };
+        write!(f, "{} chunk{}, {} bytes", self.chunks, plural, self.bytes)
+    }
+}
+
+/// A difference between two PNGs. Chunks are labelled by type, plus the
+/// keyword for text chunks; indices are positions in the chunk lists.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    Added { index: usize, label: String, summary: Option<String> },
+    Removed { index: usize, label: String, summary: Option<String> },
+    /// The chunk is in both, but in a different order relative to the
+    /// others.
+    Moved { label: String, from: usize, to: usize },
+    /// The data differs; `fields` is empty when the type has no known
+    /// fields.
+    Changed { label: String, fields: Vec<FieldChange>, before: usize, after: usize },
+    /// The `IDAT` bytes or their split into chunks differ.
+    ImageData { before: ImageDataSize, after: ImageDataSize, pixels: PixelMatch },
+    Trailer { before: usize, after: usize },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added { index, label, summary } => {
+                write!(f, "+ {} at {}", label, index)?;
+                summary.iter().try_for_each(|summary| write!(f, ": {}", summary))
+            },
+            Change::Removed { index, label, summary } => {
+                write!(f, "- {} at {}", label, index)?;
+                summary.iter().try_for_each(|summary| write!(f, ": {}", summary))
+            },
+            Change::Moved { label, from, to } => write!(f, "> {} moved from {} to {}", label, from, to),
+            Change::Changed { label, fields, before, after } if fields.is_empty() => {
+                write!(f, "~ {}: data changed, {} -> {} bytes", label, before, after)
+            },
+            Change::Changed { label, fields, .. } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|change| format!("{} {} -> {}", change.field, change.before, change.after))
+                    .collect();
+                write!(f, "~ {}: {}", label, fields.join(", "))
+            },
+            Change::ImageData { before, after, pixels } => {
+                write!(f, "~ IDAT: {} -> {}; ", before, after)?;
+                match pixels {
+                    PixelMatch::Identical => write!(f, "decoded pixels identical"),
+                    PixelMatch::Different => write!(f, "decoded pixels differ"),
+                    PixelMatch::HeaderChanged => write!(f, "image header changed"),
+                    PixelMatch::Undecodable(e) => write!(f, "pixels not compared: {}", e),
+                }
+            },
+            Change::Trailer { before, after } => write!(f, "~ trailer: {} -> {} bytes after IEND", before, after),
+        }
+    }
+}
+
+fn keyword(chunk: &Chunk) -> Option<String> {
+    match &chunk.chunk_type().bytes() {
+        b"tEXt" | b"zTXt" | b"iTXt" => {
+            let data = chunk.data();
+            let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+            Some(latin1(&data[..end]))
+        },
+        _ => None,
+    }
+}
+
+fn label(chunk: &Chunk) -> String {
+    match keyword(chunk) {
+        Some(keyword) => format!("{} {}", chunk.chunk_type(), keyword),
+        None => chunk.chunk_type().to_string(),
+    }
+}
+
+fn field(fields: &mut Vec<FieldChange>, name: &str, before: impl Display, after: impl Display) {
+    let (before, after) = (before.to_string(), after.to_string());
+    if before != after {
+        fields.push(FieldChange { field: name.to_string(), before, after });
+    }
+}
+
+fn quoted(text: &str) -> String {
+    format!("{:?}", shorten(text, 40))
+}
+
+/// The text of a `tEXt` or `zTXt` chunk, if it decodes.
+fn plain_text(chunk: &Chunk) -> Option<String> {
+    let data = chunk.data();
+    let sep = data.iter().position(|&b| b == 0)?;
+    match &chunk.chunk_type().bytes() {
+        b"tEXt" => Some(latin1(&data[sep + 1..])),
+        _ => text::inflate(data.get(sep + 2..)?).ok().map(|text| latin1(&text)),
+    }
+}
+
+/// The fields that differ between two chunks of the same type, for the
+/// types whose layout we know.
+fn field_changes(before: &Chunk, after: &Chunk) -> Vec<FieldChange> {
+    let mut fields = vec![];
+    match &before.chunk_type().bytes() {
+        b"IHDR" => {
+            if let (Ok(a), Ok(b)) = (ImageHeader::try_from(before.data()), ImageHeader::try_from(after.data())) {
+                field(&mut fields, "width", a.width, b.width);
+                field(&mut fields, "height", a.height, b.height);
+                field(&mut fields, "bit depth", a.bit_depth, b.bit_depth);
+                field(&mut fields, "color type", a.color_type, b.color_type);
+                field(&mut fields, "interlaced", a.interlaced, b.interlaced);
+            }
+        },
+        b"tEXt" | b"zTXt" => {
+            if let (Some(a), Some(b)) = (plain_text(before), plain_text(after)) {
+                field(&mut fields, "text", quoted(&a), quoted(&b));
+            }
+        },
+        b"iTXt" => {
+            if let (Ok(a), Ok(b)) = (InternationalText::try_from(before.data()), InternationalText::try_from(after.data())) {
+                field(&mut fields, "language", quoted(&a.language_tag), quoted(&b.language_tag));
+                field(&mut fields, "translated keyword", quoted(&a.translated_keyword), quoted(&b.translated_keyword));
+                field(&mut fields, "compressed", a.compressed, b.compressed);
+                field(&mut fields, "text", quoted(&a.text), quoted(&b.text));
+            }
+        },
+        _ => {
+            if let (Some(a), Some(b)) = (summarize(before), summarize(after)) {
+                field(&mut fields, "value", a, b);
+            }
+        },
+    }
+    fields
+}
+
+/// The non-`IDAT` chunks with their index and an alignment key: the label
+/// and how many chunks with the same label came before.
+fn keyed(png: &Png) -> Vec<(usize, &Chunk, (String, usize))> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    png.chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| &chunk.chunk_type().bytes() != b"IDAT")
+        .map(|(index, chunk)| {
+            let label = label(chunk);
+            let count = seen.entry(label.clone()).or_default();
+            *count += 1;
+            (index, chunk, (label, *count - 1))
+        })
+        .collect()
+}
+
+/// Marks the elements of a longest increasing subsequence of `values`.
+fn longest_increasing(values: &[usize]) -> Vec<bool> {
+    // tails[k] is the index of the smallest tail of an increasing run of
+    // length k + 1.
+    let mut tails: Vec<usize> = vec![];
+    let mut previous = vec![None; values.len()];
+    for (i, value) in values.iter().enumerate() {
+        let k = tails.partition_point(|&t| values[t] < *value);
+        previous[i] = k.checked_sub(1).map(|k| tails[k]);
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+    let mut keep = vec![false; values.len()];
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        keep[i] = true;
+        next = previous[i];
+    }
+    keep
+}
+
+/// The pixels as rows of the full image, with palette indices replaced by
+/// the RGBA colors they stand for.
+fn comparable_pixels(png: &Png, header: &ImageHeader) -> Result<Vec<u8>, pixels::PixelError> {
+    let rows = pixels::deinterlace(&pixels::decode(png, header)?, header);
+    if header.color_type != 3 {
+        return Ok(rows);
+    }
+    let data = |chunk_type: &str| png.chunk_by_type(chunk_type).map(|chunk| chunk.data()).unwrap_or_default();
+    pixels::expand_palette(&rows, header, data("PLTE"), data("tRNS"))
+}
+
+fn image_data_change(before: &Png, after: &Png) -> Option<Change> {
+    let size = |png: &Png| ImageDataSize {
+        chunks: png.chunks().iter().filter(|chunk| &chunk.chunk_type().bytes() == b"IDAT").count(),
+        bytes: pixels::image_data(png).len(),
+    };
+    let (size_before, size_after) = (size(before), size(after));
+    if size_before.chunks == size_after.chunks && pixels::image_data(before) == pixels::image_data(after) {
+        return None;
+    }
+    let pixels = match (before.image_header(), after.image_header()) {
+        (Ok(a), Ok(b)) if ImageHeader { interlaced: false, ..a } != ImageHeader { interlaced: false, ..b } => {
+            PixelMatch::HeaderChanged
+        },
+        (Ok(a), Ok(b)) => match (comparable_pixels(before, &a), comparable_pixels(after, &b)) {
+            (Ok(a), Ok(b)) if a == b => PixelMatch::Identical,
+            (Ok(_), Ok(_)) => PixelMatch::Different,
+            (Err(e), _) | (_, Err(e)) => PixelMatch::Undecodable(e.to_string()),
+        },
+        (Err(e), _) | (_, Err(e)) => PixelMatch::Undecodable(e.to_string()),
+    };
+    Some(Change::ImageData { before: size_before, after: size_after, pixels })
+}
+
+/// What changed from `before` to `after`: removed chunks in their old
+/// order, then added, changed and moved chunks in their new order, then the
+/// image data and the trailer. `IDAT` chunks are compared as one stream.
+pub fn diff(before: &Png, after: &Png) -> Vec<Change> {
+    let old = keyed(before);
+    let new = keyed(after);
+    let old_by_key: HashMap<&(String, usize), usize> = old.iter().enumerate().map(|(i, (_, _, key))| (key, i)).collect();
+    let new_keys: HashMap<&(String, usize), usize> = new.iter().enumerate().map(|(i, (_, _, key))| (key, i)).collect();
+
+    let mut changes = vec![];
+    for (index, chunk, key) in &old {
+        if !new_keys.contains_key(key) {
+            changes.push(Change::Removed { index: *index, label: key.0.clone(), summary: summarize(chunk) });
+        }
+    }
+
+    // Chunks in both keep their relative order along the longest run whose
+    // old positions increase; the rest were moved.
+    let paired: Vec<usize> = new.iter().filter_map(|(_, _, key)| old_by_key.get(key).copied()).collect();
+    let mut in_order = longest_increasing(&paired).into_iter();
+    for (index, chunk, key) in &new {
+        let Some(&old_position) = old_by_key.get(key) else {
+            changes.push(Change::Added { index: *index, label: key.0.clone(), summary: summarize(chunk) });
+            continue;
+        };
+        let (old_index, old_chunk, _) = old[old_position];
+        if old_chunk.data() != chunk.data() {
+            changes.push(Change::Changed {
+                label: key.0.clone(),
+                fields: field_changes(old_chunk, chunk),
+                before: old_chunk.data().len(),
+                after: chunk.data().len(),
+            });
+        }
+        if !in_order.next().unwrap_or(true) {
+            changes.push(Change::Moved { label: key.0.clone(), from: old_index, to: *index });
+        }
+    }
+
+    changes.extend(image_data_change(before, after));
+    if before.trailer() != after.trailer() {
+        changes.push(Change::Trailer { before: before.trailer().len(), after: after.trailer().len() });
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::registry::ChunkData;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn header(width: u32) -> Chunk {
+        ImageHeader { width, height: 1, bit_depth: 8, color_type: 0, interlaced: false }.encode()
+    }
+
+    fn image(rows: &[u8], compression: flate2::Compression) -> Chunk {
+        let mut encoder = flate2::write::ZlibEncoder::new(vec![], compression);
+        std::io::Write::write_all(&mut encoder, rows).unwrap();
+        chunk("IDAT", &encoder.finish().unwrap())
+    }
+
+    #[test]
+    fn test_longest_increasing() {
+        assert_eq!(longest_increasing(&[0, 1, 2]), vec![true, true, true]);
+        assert_eq!(longest_increasing(&[2, 0, 1]), vec![false, true, true]);
+        assert_eq!(longest_increasing(&[1, 0]), vec![false, true]);
+    }
+
+    #[test]
+    fn test_identical() {
+        let png = Png::from_chunks(vec![header(2), chunk("tEXt", b"a\0b"), image(&[0, 1, 2], flate2::Compression::fast()), chunk("IEND", b"")]);
+        assert!(diff(&png, &png).is_empty());
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = Png::from_chunks(vec![
+            header(2),
+            chunk("gAMA", &45455u32.to_be_bytes()),
+            chunk("tEXt", b"Title\0old"),
+            chunk("tEXt", b"Author\0me"),
+            chunk("tIME", &[7, 232, 2, 29, 0, 0, 0]),
+            image(&[0, 1, 2], flate2::Compression::fast()),
+            chunk("IEND", b""),
+        ]);
+        let mut after = Png::from_chunks(vec![
+            header(2),
+            chunk("gAMA", &100000u32.to_be_bytes()),
+            chunk("tEXt", b"Author\0me"),
+            chunk("tEXt", b"Title\0new"),
+            chunk("ruSt", b"hi"),
+            image(&[0, 1, 2], flate2::Compression::best()),
+            image(&[], flate2::Compression::none()),
+            chunk("IEND", b""),
+        ]);
+        after.set_trailer(b"PK".to_vec());
+        let changes: Vec<String> = diff(&before, &after).iter().map(|change| change.to_string()).collect();
+        assert_eq!(changes[0], "- tIME at 4: 2024-02-29 00:00:00 UTC");
+        assert!(changes[1].starts_with("~ gAMA: value "));
+        assert_eq!(changes[2], "> tEXt Author moved from 3 to 2");
+        assert_eq!(changes[3], "~ tEXt Title: text \"old\" -> \"new\"");
+        assert_eq!(changes[4], "+ ruSt at 4: \"hi\"");
+        assert!(changes[5].starts_with("~ IDAT: 1 chunk, ") && changes[5].ends_with("decoded pixels identical"));
+        assert_eq!(changes[6], "~ trailer: 0 -> 2 bytes after IEND");
+        assert_eq!(changes.len(), 7);
+    }
+
+    #[test]
+    fn test_header_and_pixels() {
+        let before = Png::from_chunks(vec![header(2), image(&[0, 1, 2], flate2::Compression::fast()), chunk("IEND", b"")]);
+        let changed = Png::from_chunks(vec![header(2), image(&[0, 1, 3], flate2::Compression::fast()), chunk("IEND", b"")]);
+        assert!(diff(&before, &changed)[0].to_string().ends_with("decoded pixels differ"));
+        let wider = Png::from_chunks(vec![header(3), image(&[0, 1, 2, 3], flate2::Compression::fast()), chunk("IEND", b"")]);
+        let changes = diff(&before, &wider);
+        assert_eq!(changes[0].to_string(), "~ IHDR: width 2 -> 3");
+        assert_eq!(changes[1].to_string(), "~ IDAT: 1 chunk, 11 bytes -> 1 chunk, 12 bytes; image header changed");
+    }
+
+    #[test]
+    fn test_interlaced_and_indexed_pixels() {
+        let square = |color_type, interlaced| {
+            ImageHeader { width: 2, height: 2, bit_depth: 8, color_type, interlaced }.encode()
+        };
+        let fast = flate2::Compression::fast();
+        let before = Png::from_chunks(vec![square(0, false), image(&[0, 1, 2, 0, 3, 4], fast), chunk("IEND", b"")]);
+        let after = Png::from_chunks(vec![square(0, true), image(&[0, 1, 0, 2, 0, 3, 4], fast), chunk("IEND", b"")]);
+        assert!(matches!(diff(&before, &after)[1], Change::ImageData { pixels: PixelMatch::Identical, .. }));
+
+        let indexed = |palette: &[u8], compression| {
+            Png::from_chunks(vec![
+                square(3, false),
+                chunk("PLTE", palette),
+                image(&[0, 0, 1, 0, 1, 0], compression),
+                chunk("IEND", b""),
+            ])
+        };
+        let before = indexed(&[0, 0, 0, 255, 255, 255], flate2::Compression::fast());
+        let same = indexed(&[0, 0, 0, 255, 255, 255], flate2::Compression::best());
+        let recolored = indexed(&[0, 0, 0, 255, 0, 0], flate2::Compression::best());
+        assert!(matches!(diff(&before, &same)[0], Change::ImageData { pixels: PixelMatch::Identical, .. }));
+        assert!(matches!(diff(&before, &recolored)[1], Change::ImageData { pixels: PixelMatch::Different, .. }));
+    }
+}
@@ -0,0 +1,655 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::registry::{ChunkData, DecodeError};
+
+/// Why an `eXIf` payload could not be read, or a tag could not be edited.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExifError {
+    /// The data does not start with a TIFF header.
+    InvalidHeader,
+    /// The IFD or value at `offset` runs past the end of the data.
+    Truncated { offset: usize },
+    /// The IFD does not exist, or has no such tag.
+    TagNotFound { ifd: IfdKind, tag: u16 },
+    /// The tag name is not one we know.
+    UnknownTag(String),
+    /// The value cannot be written as the tag's field type.
+    InvalidValue { field_type: u16, value: String },
+}
+
+impl Display for ExifError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExifError::InvalidHeader => write!(f, "exif data does not start with a tiff header"),
+            ExifError::Truncated { offset } => write!(f, "exif data at offset {} is truncated", offset),
+            ExifError::TagNotFound { ifd, tag } => write!(f, "no tag {} in {}", tag_name(*ifd, *tag), ifd),
+            ExifError::UnknownTag(name) => write!(f, "unknown exif tag {:?}", name),
+            ExifError::InvalidValue { field_type, value } => {
+                write!(f, "{:?} is not a valid {} value", value, type_name(*field_type))
+            },
+        }
+    }
+}
+
+impl std::error::Error for ExifError {}
+
+/// The directories of an EXIF block. Their pointer tags are managed by
+/// `Exif` and never show up as entries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IfdKind {
+    Ifd0,
+    Exif,
+    Interop,
+    Gps,
+    /// The thumbnail directory.
+    Ifd1,
+}
+
+impl IfdKind {
+    /// The order directories are written in.
+    pub const ALL: [IfdKind; 5] = [IfdKind::Ifd0, IfdKind::Exif, IfdKind::Interop, IfdKind::Gps, IfdKind::Ifd1];
+}
+
+impl Display for IfdKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            IfdKind::Ifd0 => "ifd0",
+            IfdKind::Exif => "exif",
+            IfdKind::Interop => "interop",
+            IfdKind::Gps => "gps",
+            IfdKind::Ifd1 => "ifd1",
+        };
+        f.pad(name)
+    }
+}
+
+impl FromStr for IfdKind {
+    type Err = ExifError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        IfdKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ExifError::UnknownTag(s.to_string()))
+    }
+}
+
+const EXIF_POINTER: u16 = 34665;
+const GPS_POINTER: u16 = 34853;
+const INTEROP_POINTER: u16 = 40965;
+const THUMBNAIL_OFFSET: u16 = 513;
+const THUMBNAIL_LENGTH: u16 = 514;
+
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+const UNDEFINED: u16 = 7;
+
+/// Commonly edited tags: directory, number, name and field type.
+const KNOWN_TAGS: &[(IfdKind, u16, &str, u16)] = &[
+    (IfdKind::Ifd0, 270, "ImageDescription", ASCII),
+    (IfdKind::Ifd0, 271, "Make", ASCII),
+    (IfdKind::Ifd0, 272, "Model", ASCII),
+    (IfdKind::Ifd0, 274, "Orientation", SHORT),
+    (IfdKind::Ifd0, 282, "XResolution", RATIONAL),
+    (IfdKind::Ifd0, 283, "YResolution", RATIONAL),
+    (IfdKind::Ifd0, 296, "ResolutionUnit", SHORT),
+    (IfdKind::Ifd0, 305, "Software", ASCII),
+    (IfdKind::Ifd0, 306, "DateTime", ASCII),
+    (IfdKind::Ifd0, 315, "Artist", ASCII),
+    (IfdKind::Ifd0, 33432, "Copyright", ASCII),
+    (IfdKind::Exif, 33434, "ExposureTime", RATIONAL),
+    (IfdKind::Exif, 33437, "FNumber", RATIONAL),
+    (IfdKind::Exif, 34855, "ISOSpeedRatings", SHORT),
+    (IfdKind::Exif, 36864, "ExifVersion", UNDEFINED),
+    (IfdKind::Exif, 36867, "DateTimeOriginal", ASCII),
+    (IfdKind::Exif, 36868, "DateTimeDigitized", ASCII),
+    (IfdKind::Exif, 37386, "FocalLength", RATIONAL),
+    (IfdKind::Exif, 37500, "MakerNote", UNDEFINED),
+    (IfdKind::Exif, 37510, "UserComment", UNDEFINED),
+    (IfdKind::Exif, 42016, "ImageUniqueID", ASCII),
+    (IfdKind::Exif, 42033, "BodySerialNumber", ASCII),
+    (IfdKind::Exif, 42036, "LensModel", ASCII),
+    (IfdKind::Gps, 0, "GPSVersionID", 1),
+    (IfdKind::Gps, 1, "GPSLatitudeRef", ASCII),
+    (IfdKind::Gps, 2, "GPSLatitude", RATIONAL),
+    (IfdKind::Gps, 3, "GPSLongitudeRef", ASCII),
+    (IfdKind::Gps, 4, "GPSLongitude", RATIONAL),
+    (IfdKind::Gps, 5, "GPSAltitudeRef", 1),
+    (IfdKind::Gps, 6, "GPSAltitude", RATIONAL),
+    (IfdKind::Gps, 7, "GPSTimeStamp", RATIONAL),
+    (IfdKind::Gps, 29, "GPSDateStamp", ASCII),
+];
+
+/// The name of a tag, or its number if we don't know it.
+pub fn tag_name(ifd: IfdKind, tag: u16) -> String {
+    KNOWN_TAGS
+        .iter()
+        .find(|&&(kind, number, _, _)| kind == ifd && number == tag)
+        .map(|&(_, _, name, _)| name.to_string())
+        .unwrap_or_else(|| tag.to_string())
+}
+
+/// Looks up a tag by name (case-insensitive), returning its directory,
+/// number and field type.
+pub fn find_tag(name: &str) -> Option<(IfdKind, u16, u16)> {
+    KNOWN_TAGS
+        .iter()
+        .find(|&&(_, _, known, _)| known.eq_ignore_ascii_case(name))
+        .map(|&(kind, number, _, field_type)| (kind, number, field_type))
+}
+
+fn type_name(field_type: u16) -> &'static str {
+    match field_type {
+        1 => "BYTE",
+        ASCII => "ASCII",
+        SHORT => "SHORT",
+        LONG => "LONG",
+        RATIONAL => "RATIONAL",
+        6 => "SBYTE",
+        UNDEFINED => "UNDEFINED",
+        8 => "SSHORT",
+        9 => "SLONG",
+        10 => "SRATIONAL",
+        11 => "FLOAT",
+        12 => "DOUBLE",
+        _ => "unknown type",
+    }
+}
+
+fn type_size(field_type: u16) -> usize {
+    match field_type {
+        3 | 8 => 2,
+        4 | 9 | 11 => 4,
+        5 | 10 | 12 => 8,
+        _ => 1,
+    }
+}
+
+/// One tag. `data` is the value in the byte order of the `Exif` it came
+/// from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExifEntry {
+    pub tag: u16,
+    pub field_type: u16,
+    pub count: u32,
+    pub data: Vec<u8>,
+}
+
+/// A TIFF-structured EXIF block, as stored in `eXIf`, parsed into its
+/// directories. Offsets are recomputed when writing, so values that hold
+/// absolute offsets of their own (some maker notes) may not survive edits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Exif {
+    little_endian: bool,
+    ifds: Vec<(IfdKind, Vec<ExifEntry>)>,
+    thumbnail: Option<Vec<u8>>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl Reader<'_> {
+    fn bytes(&self, at: usize, len: usize) -> Result<&[u8], ExifError> {
+        self.data.get(at..at.saturating_add(len)).filter(|b| b.len() == len).ok_or(ExifError::Truncated { offset: at })
+    }
+
+    fn u16(&self, at: usize) -> Result<u16, ExifError> {
+        let b = self.bytes(at, 2)?;
+        Ok(if self.little_endian { u16::from_le_bytes([b[0], b[1]]) } else { u16::from_be_bytes([b[0], b[1]]) })
+    }
+
+    fn u32(&self, at: usize) -> Result<u32, ExifError> {
+        let b: [u8; 4] = self.bytes(at, 4)?.try_into().unwrap();
+        Ok(if self.little_endian { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) })
+    }
+
+    /// Reads the entries of the IFD at `offset` and the offset of the next.
+    fn ifd(&self, offset: usize) -> Result<(Vec<ExifEntry>, u32), ExifError> {
+        let count = self.u16(offset)? as usize;
+        let mut entries = vec![];
+        for i in 0..count {
+            let at = offset + 2 + i * 12;
+            let tag = self.u16(at)?;
+            let field_type = self.u16(at + 2)?;
+            let count = self.u32(at + 4)?;
+            let len = type_size(field_type) * count as usize;
+            let data = if len <= 4 {
+                self.bytes(at + 8, len)?
+            } else {
+                self.bytes(self.u32(at + 8)? as usize, len)?
+            };
+            entries.push(ExifEntry { tag, field_type, count, data: data.to_vec() });
+        }
+        Ok((entries, self.u32(offset + 2 + count * 12)?))
+    }
+}
+
+impl TryFrom<&[u8]> for Exif {
+    type Error = ExifError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let little_endian = if value.starts_with(b"II*\0") {
+            true
+        } else if value.starts_with(b"MM\0*") {
+            false
+        } else {
+            return Err(ExifError::InvalidHeader);
+        };
+        let reader = Reader { data: value, little_endian };
+        let mut exif = Exif { little_endian, ifds: vec![], thumbnail: None };
+
+        let (mut ifd0, next) = reader.ifd(reader.u32(4)? as usize)?;
+        let mut pending = vec![];
+        for entry in ifd0.iter() {
+            match entry.tag {
+                EXIF_POINTER => pending.push((IfdKind::Exif, exif.entry_u32(entry))),
+                GPS_POINTER => pending.push((IfdKind::Gps, exif.entry_u32(entry))),
+                _ => {},
+            }
+        }
+        ifd0.retain(|entry| !matches!(entry.tag, EXIF_POINTER | GPS_POINTER));
+        exif.ifds.push((IfdKind::Ifd0, ifd0));
+        if next != 0 {
+            pending.push((IfdKind::Ifd1, Some(next)));
+        }
+
+        while let Some((kind, offset)) = pending.pop() {
+            let Some(offset) = offset else { continue };
+            let (mut entries, _) = reader.ifd(offset as usize)?;
+            if kind == IfdKind::Exif {
+                if let Some(entry) = entries.iter().find(|entry| entry.tag == INTEROP_POINTER) {
+                    pending.push((IfdKind::Interop, exif.entry_u32(entry)));
+                }
+                entries.retain(|entry| entry.tag != INTEROP_POINTER);
+            }
+            if kind == IfdKind::Ifd1 {
+                let offset = entries.iter().find(|entry| entry.tag == THUMBNAIL_OFFSET).and_then(|entry| exif.entry_u32(entry));
+                let length = entries.iter().find(|entry| entry.tag == THUMBNAIL_LENGTH).and_then(|entry| exif.entry_u32(entry));
+                if let (Some(offset), Some(length)) = (offset, length) {
+                    exif.thumbnail = Some(reader.bytes(offset as usize, length as usize)?.to_vec());
+                    entries.retain(|entry| !matches!(entry.tag, THUMBNAIL_OFFSET | THUMBNAIL_LENGTH));
+                }
+            }
+            exif.ifds.push((kind, entries));
+        }
+        exif.ifds.sort_by_key(|(kind, _)| *kind);
+        Ok(exif)
+    }
+}
+
+impl Exif {
+    /// An empty EXIF block with an empty IFD0.
+    pub fn new(little_endian: bool) -> Exif {
+        Exif { little_endian, ifds: vec![(IfdKind::Ifd0, vec![])], thumbnail: None }
+    }
+
+    pub fn is_little_endian(&self) -> bool {
+        self.little_endian
+    }
+
+    fn u16_from(&self, b: &[u8]) -> u16 {
+        if self.little_endian { u16::from_le_bytes([b[0], b[1]]) } else { u16::from_be_bytes([b[0], b[1]]) }
+    }
+
+    fn u32_from(&self, b: &[u8]) -> u32 {
+        let b: [u8; 4] = b[..4].try_into().unwrap();
+        if self.little_endian { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) }
+    }
+
+    fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        if self.little_endian { value.to_le_bytes() } else { value.to_be_bytes() }
+    }
+
+    fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        if self.little_endian { value.to_le_bytes() } else { value.to_be_bytes() }
+    }
+
+    fn entry_u32(&self, entry: &ExifEntry) -> Option<u32> {
+        match (entry.field_type, entry.data.len()) {
+            (SHORT, 2..) => Some(self.u16_from(&entry.data) as u32),
+            (LONG | 13, 4..) => Some(self.u32_from(&entry.data)),
+            _ => None,
+        }
+    }
+
+    /// The directories present, in write order.
+    pub fn ifds(&self) -> &[(IfdKind, Vec<ExifEntry>)] {
+        &self.ifds
+    }
+
+    pub fn thumbnail(&self) -> Option<&[u8]> {
+        self.thumbnail.as_deref()
+    }
+
+    pub fn get(&self, ifd: IfdKind, tag: u16) -> Option<&ExifEntry> {
+        self.ifds
+            .iter()
+            .find(|(kind, _)| *kind == ifd)
+            .and_then(|(_, entries)| entries.iter().find(|entry| entry.tag == tag))
+    }
+
+    /// Adds or replaces a tag, creating its directory if needed.
+    pub fn set(&mut self, ifd: IfdKind, entry: ExifEntry) {
+        let idx = match self.ifds.iter().position(|(kind, _)| *kind == ifd) {
+            Some(idx) => idx,
+            None => {
+                self.ifds.push((ifd, vec![]));
+                self.ifds.sort_by_key(|(kind, _)| *kind);
+                self.ifds.iter().position(|(kind, _)| *kind == ifd).unwrap()
+            },
+        };
+        let entries = &mut self.ifds[idx].1;
+        entries.retain(|other| other.tag != entry.tag);
+        let at = entries.partition_point(|other| other.tag < entry.tag);
+        entries.insert(at, entry);
+    }
+
+    pub fn remove(&mut self, ifd: IfdKind, tag: u16) -> Result<ExifEntry, ExifError> {
+        let entries = self
+            .ifds
+            .iter_mut()
+            .find(|(kind, _)| *kind == ifd)
+            .map(|(_, entries)| entries)
+            .ok_or(ExifError::TagNotFound { ifd, tag })?;
+        let idx = entries.iter().position(|entry| entry.tag == tag).ok_or(ExifError::TagNotFound { ifd, tag })?;
+        Ok(entries.remove(idx))
+    }
+
+    /// Drops a whole directory; the GPS one to remove location data.
+    /// Returns whether it was there.
+    pub fn remove_ifd(&mut self, ifd: IfdKind) -> bool {
+        let before = self.ifds.len();
+        self.ifds.retain(|(kind, _)| *kind != ifd || ifd == IfdKind::Ifd0);
+        if ifd == IfdKind::Exif {
+            self.ifds.retain(|(kind, _)| *kind != IfdKind::Interop);
+        }
+        if ifd == IfdKind::Ifd1 {
+            self.thumbnail = None;
+        }
+        self.ifds.len() != before
+    }
+
+    fn rationals(&self, entry: &ExifEntry) -> Vec<(u32, u32)> {
+        entry.data.chunks_exact(8).map(|b| (self.u32_from(&b[0..4]), self.u32_from(&b[4..8]))).collect()
+    }
+
+    /// Decimal degrees from the GPS directory, negative for south and west.
+    pub fn gps_location(&self) -> Option<(f64, f64)> {
+        let coordinate = |value_tag: u16, ref_tag: u16, negative: u8| {
+            let parts = self.rationals(self.get(IfdKind::Gps, value_tag)?);
+            let degrees = parts
+                .iter()
+                .zip([1.0, 60.0, 3600.0])
+                .map(|(&(num, den), scale)| if den == 0 { 0.0 } else { num as f64 / den as f64 / scale })
+                .sum::<f64>();
+            let sign = match self.get(IfdKind::Gps, ref_tag).and_then(|entry| entry.data.first()) {
+                Some(&r) if r == negative => -1.0,
+                _ => 1.0,
+            };
+            Some(degrees * sign)
+        };
+        Some((coordinate(2, 1, b'S')?, coordinate(4, 3, b'W')?))
+    }
+
+    /// Renders a value for display.
+    pub fn display_value(&self, entry: &ExifEntry) -> String {
+        let data = &entry.data;
+        let join = |values: Vec<String>| values.join(", ");
+        match entry.field_type {
+            ASCII => String::from_utf8_lossy(data).trim_end_matches('\0').to_string(),
+            SHORT => join(data.chunks_exact(2).map(|b| self.u16_from(b).to_string()).collect()),
+            LONG => join(data.chunks_exact(4).map(|b| self.u32_from(b).to_string()).collect()),
+            8 => join(data.chunks_exact(2).map(|b| (self.u16_from(b) as i16).to_string()).collect()),
+            9 => join(data.chunks_exact(4).map(|b| (self.u32_from(b) as i32).to_string()).collect()),
+            RATIONAL => join(self.rationals(entry).iter().map(|(n, d)| format!("{}/{}", n, d)).collect()),
+            10 => join(self.rationals(entry).iter().map(|&(n, d)| format!("{}/{}", n as i32, d as i32)).collect()),
+            _ if data.iter().all(|b| b.is_ascii_graphic() || *b == b' ') => format!("\"{}\"", String::from_utf8_lossy(data)),
+            _ => {
+                let hex: Vec<String> = data.iter().take(16).map(|b| format!("{:02x}", b)).collect();
+                let more = if data.len() > 16 { format!("... ({} bytes)", data.len()) } else { String::new() };
+                format!("{}{}", hex.join(" "), more)
+            },
+        }
+    }
+
+    /// Builds an entry from text: a string for ASCII and UNDEFINED,
+    /// comma-separated numbers for integers, and `num/den` or decimals for
+    /// rationals.
+    pub fn parse_value(&self, tag: u16, field_type: u16, value: &str) -> Result<ExifEntry, ExifError> {
+        let invalid = || ExifError::InvalidValue { field_type, value: value.to_string() };
+        let items = || value.split(',').map(|item| item.trim());
+        let mut data = vec![];
+        let count = match field_type {
+            ASCII => {
+                data.extend_from_slice(value.as_bytes());
+                data.push(0);
+                data.len()
+            },
+            1 | UNDEFINED => {
+                data.extend_from_slice(value.as_bytes());
+                data.len()
+            },
+            SHORT => {
+                for item in items() {
+                    data.extend_from_slice(&self.u16_bytes(item.parse().map_err(|_| invalid())?));
+                }
+                data.len() / 2
+            },
+            LONG => {
+                for item in items() {
+                    data.extend_from_slice(&self.u32_bytes(item.parse().map_err(|_| invalid())?));
+                }
+                data.len() / 4
+            },
+            RATIONAL => {
+                for item in items() {
+                    let (num, den) = match item.split_once('/') {
+                        Some((num, den)) => (num.parse().map_err(|_| invalid())?, den.parse().map_err(|_| invalid())?),
+                        None => {
+                            let value: f64 = item.parse().map_err(|_| invalid())?;
+                            if !(0.0..=u32::MAX as f64 / 10000.0).contains(&value) {
+                                return Err(invalid());
+                            }
+                            ((value * 10000.0).round() as u32, 10000)
+                        },
+                    };
+                    data.extend_from_slice(&self.u32_bytes(num));
+                    data.extend_from_slice(&self.u32_bytes(den));
+                }
+                data.len() / 8
+            },
+            _ => return Err(invalid()),
+        };
+        Ok(ExifEntry { tag, field_type, count: count as u32, data })
+    }
+
+    /// The entries of each directory as they will be written, including
+    /// pointer tags with placeholder values.
+    fn entries_for_writing(&self) -> Vec<(IfdKind, Vec<ExifEntry>)> {
+        let has = |ifd: IfdKind| self.ifds.iter().any(|(kind, _)| *kind == ifd);
+        let pointer = |tag| ExifEntry { tag, field_type: LONG, count: 1, data: vec![0; 4] };
+        let mut ret = vec![];
+        for (kind, entries) in &self.ifds {
+            let mut entries = entries.clone();
+            match kind {
+                IfdKind::Ifd0 => {
+                    if has(IfdKind::Exif) {
+                        entries.push(pointer(EXIF_POINTER));
+                    }
+                    if has(IfdKind::Gps) {
+                        entries.push(pointer(GPS_POINTER));
+                    }
+                },
+                IfdKind::Exif if has(IfdKind::Interop) => entries.push(pointer(INTEROP_POINTER)),
+                IfdKind::Ifd1 if self.thumbnail.is_some() => {
+                    entries.push(pointer(THUMBNAIL_OFFSET));
+                    entries.push(pointer(THUMBNAIL_LENGTH));
+                },
+                _ => {},
+            }
+            entries.sort_by_key(|entry| entry.tag);
+            ret.push((*kind, entries));
+        }
+        ret
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ifds = self.entries_for_writing();
+        let block_len = |entries: &[ExifEntry]| {
+            2 + entries.len() * 12 + 4
+                + entries.iter().filter(|entry| entry.data.len() > 4).map(|entry| entry.data.len().div_ceil(2) * 2).sum::<usize>()
+        };
+        let mut offsets = vec![];
+        let mut offset = 8;
+        for (kind, entries) in &ifds {
+            offsets.push((*kind, offset as u32));
+            offset += block_len(entries);
+        }
+        let thumbnail_offset = offset as u32;
+        let offset_of = |ifd: IfdKind| offsets.iter().find(|(kind, _)| *kind == ifd).map(|(_, offset)| *offset);
+
+        for (_, entries) in ifds.iter_mut() {
+            for entry in entries.iter_mut() {
+                let value = match entry.tag {
+                    EXIF_POINTER => offset_of(IfdKind::Exif),
+                    GPS_POINTER => offset_of(IfdKind::Gps),
+                    INTEROP_POINTER => offset_of(IfdKind::Interop),
+                    THUMBNAIL_OFFSET if entry.data == [0; 4] => Some(thumbnail_offset),
+                    THUMBNAIL_LENGTH if entry.data == [0; 4] => self.thumbnail.as_ref().map(|t| t.len() as u32),
+                    _ => None,
+                };
+                if let Some(value) = value {
+                    entry.data = self.u32_bytes(value).to_vec();
+                }
+            }
+        }
+
+        let mut out = if self.little_endian { b"II*\0".to_vec() } else { b"MM\0*".to_vec() };
+        out.extend_from_slice(&self.u32_bytes(8));
+        for (kind, entries) in &ifds {
+            let start = out.len();
+            let mut data_offset = start + 2 + entries.len() * 12 + 4;
+            let mut values = vec![];
+            out.extend_from_slice(&self.u16_bytes(entries.len() as u16));
+            for entry in entries {
+                out.extend_from_slice(&self.u16_bytes(entry.tag));
+                out.extend_from_slice(&self.u16_bytes(entry.field_type));
+                out.extend_from_slice(&self.u32_bytes(entry.count));
+                if entry.data.len() <= 4 {
+                    let mut inline = entry.data.clone();
+                    inline.resize(4, 0);
+                    out.extend_from_slice(&inline);
+                } else {
+                    out.extend_from_slice(&self.u32_bytes(data_offset as u32));
+                    values.extend_from_slice(&entry.data);
+                    if entry.data.len() % 2 == 1 {
+                        values.push(0);
+                    }
+                    data_offset += entry.data.len().div_ceil(2) * 2;
+                }
+            }
+            let next = if *kind == IfdKind::Ifd0 { offset_of(IfdKind::Ifd1).unwrap_or(0) } else { 0 };
+            out.extend_from_slice(&self.u32_bytes(next));
+            out.extend_from_slice(&values);
+        }
+        if let Some(thumbnail) = &self.thumbnail {
+            out.extend_from_slice(thumbnail);
+        }
+        out
+    }
+}
+
+impl ChunkData for Exif {
+    const CHUNK_TYPES: &'static [&'static str] = &["eXIf"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        Exif::try_from(chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        Chunk::new(ChunkType::from_str("eXIf").unwrap(), self.as_bytes())
+    }
+
+    fn summary(&self) -> String {
+        let tags: usize = self.ifds().iter().map(|(_, entries)| entries.len()).sum();
+        let ifds: Vec<String> = self.ifds().iter().map(|(kind, _)| kind.to_string()).collect();
+        format!("{} tags in {}", tags, ifds.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_exif(little_endian: bool) -> Exif {
+        let mut exif = Exif::new(little_endian);
+        exif.set(IfdKind::Ifd0, exif.parse_value(271, ASCII, "Rusty Cameras").unwrap());
+        exif.set(IfdKind::Ifd0, exif.parse_value(274, SHORT, "1").unwrap());
+        exif.set(IfdKind::Exif, exif.parse_value(36867, ASCII, "2024:01:02 03:04:05").unwrap());
+        exif.set(IfdKind::Interop, exif.parse_value(1, ASCII, "R98").unwrap());
+        exif.set(IfdKind::Gps, exif.parse_value(1, ASCII, "S").unwrap());
+        exif.set(IfdKind::Gps, exif.parse_value(2, RATIONAL, "33/1, 51/1, 36/1").unwrap());
+        exif.set(IfdKind::Gps, exif.parse_value(3, ASCII, "E").unwrap());
+        exif.set(IfdKind::Gps, exif.parse_value(4, RATIONAL, "151.2").unwrap());
+        exif.set(IfdKind::Ifd1, exif.parse_value(259, SHORT, "6").unwrap());
+        exif.thumbnail = Some(vec![0xFF, 0xD8, 0xFF, 0xD9]);
+        exif
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for little_endian in [true, false] {
+            let exif = testing_exif(little_endian);
+            let bytes = exif.as_bytes();
+            let parsed = Exif::try_from(bytes.as_slice()).unwrap();
+            assert_eq!(parsed, exif);
+            assert_eq!(parsed.as_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_invalid_exif() {
+        assert_eq!(Exif::try_from(&b"Exif\0\0II*\0"[..]), Err(ExifError::InvalidHeader));
+        let bytes = testing_exif(true).as_bytes();
+        assert!(matches!(Exif::try_from(&bytes[..40]), Err(ExifError::Truncated { .. })));
+    }
+
+    #[test]
+    fn test_values() {
+        let exif = testing_exif(false);
+        assert_eq!(exif.display_value(exif.get(IfdKind::Ifd0, 271).unwrap()), "Rusty Cameras");
+        assert_eq!(exif.display_value(exif.get(IfdKind::Gps, 2).unwrap()), "33/1, 51/1, 36/1");
+        assert_eq!(exif.display_value(exif.get(IfdKind::Gps, 4).unwrap()), "1512000/10000");
+        let (lat, lon) = exif.gps_location().unwrap();
+        assert!((lat + 33.86).abs() < 1e-9 && (lon - 151.2).abs() < 1e-9);
+        assert!(matches!(exif.parse_value(274, SHORT, "one"), Err(ExifError::InvalidValue { .. })));
+    }
+
+    #[test]
+    fn test_tag_names() {
+        assert_eq!(find_tag("gpslatitude"), Some((IfdKind::Gps, 2, RATIONAL)));
+        assert_eq!(tag_name(IfdKind::Gps, 2), "GPSLatitude");
+        assert_eq!(tag_name(IfdKind::Ifd0, 2), "2");
+        assert_eq!(IfdKind::from_str("GPS").unwrap(), IfdKind::Gps);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut exif = testing_exif(true);
+        exif.remove(IfdKind::Ifd0, 271).unwrap();
+        assert_eq!(exif.remove(IfdKind::Ifd0, 271), Err(ExifError::TagNotFound { ifd: IfdKind::Ifd0, tag: 271 }));
+        assert!(exif.remove_ifd(IfdKind::Gps));
+        assert!(!exif.remove_ifd(IfdKind::Gps));
+
+        let parsed = Exif::try_from(exif.as_bytes().as_slice()).unwrap();
+        assert_eq!(parsed.gps_location(), None);
+        assert!(parsed.get(IfdKind::Ifd0, GPS_POINTER).is_none());
+        assert_eq!(parsed.get(IfdKind::Interop, 1).map(|entry| parsed.display_value(entry)), Some("R98".to_string()));
+    }
+}
@@ -0,0 +1,422 @@
+use std::fmt::{Display, Formatter};
+use crate::container::{self, Carrier, Container, Format, Segment};
+use crate::Error;
+
+/// Why a byte slice could not be read as a `Gif`, or a message could not be
+/// found in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GifError {
+    /// The file does not start with `GIF87a` or `GIF89a`.
+    NotGif,
+    /// The block starting at `offset` runs past the end of the file.
+    Truncated { offset: usize },
+    /// The byte at `offset` is not an extension, image or trailer introducer.
+    UnknownBlock { offset: usize, introducer: u8 },
+    /// No extension carries a message under this key.
+    MessageNotFound(String),
+}
+
+impl Display for GifError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GifError::NotGif => write!(f, "not a gif: bad signature"),
+            GifError::Truncated { offset } => write!(f, "gif block at offset {} is truncated", offset),
+            GifError::UnknownBlock { offset, introducer } => {
+                write!(f, "unknown gif block 0x{:02x} at offset {}", introducer, offset)
+            },
+            GifError::MessageNotFound(key) => write!(f, "no {} message found", key),
+        }
+    }
+}
+
+impl std::error::Error for GifError {}
+
+/// A block between the logical screen and the trailer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GifBlock {
+    /// An extension (`0x21`): its label and data sub-blocks.
+    Extension { label: u8, sub_blocks: Vec<Vec<u8>> },
+    /// An image (`0x2C`). Everything after the introducer, from the
+    /// descriptor to the block terminator of the LZW stream, is kept
+    /// verbatim.
+    Image { raw: Vec<u8> },
+}
+
+impl GifBlock {
+    pub const GRAPHIC_CONTROL: u8 = 0xF9;
+    pub const COMMENT: u8 = 0xFE;
+    pub const APPLICATION: u8 = 0xFF;
+    pub const PLAIN_TEXT: u8 = 0x01;
+
+    /// Builds an extension, splitting `data` into sub-blocks of at most 255
+    /// bytes.
+    pub fn extension(label: u8, data: &[u8]) -> GifBlock {
+        GifBlock::Extension { label, sub_blocks: data.chunks(255).map(|chunk| chunk.to_vec()).collect() }
+    }
+
+    /// The application identifier and authentication code of an
+    /// application extension.
+    pub fn application_id(&self) -> Option<&[u8]> {
+        match self {
+            GifBlock::Extension { label: GifBlock::APPLICATION, sub_blocks } => {
+                sub_blocks.first().filter(|block| block.len() == 11).map(|block| block.as_slice())
+            },
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            GifBlock::Image { .. } => "Image".to_string(),
+            GifBlock::Extension { label, .. } => match *label {
+                GifBlock::GRAPHIC_CONTROL => "GraphicControl".to_string(),
+                GifBlock::COMMENT => "Comment".to_string(),
+                GifBlock::APPLICATION => "Application".to_string(),
+                GifBlock::PLAIN_TEXT => "PlainText".to_string(),
+                label => format!("Extension 0x{:02X}", label),
+            },
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        match self {
+            GifBlock::Image { raw } => {
+                let mut ret = vec![0x2C];
+                ret.extend_from_slice(raw);
+                ret
+            },
+            GifBlock::Extension { label, sub_blocks } => {
+                let mut ret = vec![0x21, *label];
+                for block in sub_blocks {
+                    ret.push(block.len() as u8);
+                    ret.extend_from_slice(block);
+                }
+                ret.push(0);
+                ret
+            },
+        }
+    }
+
+    fn summary(&self) -> Option<String> {
+        match self {
+            GifBlock::Image { raw } if raw.len() >= 9 => {
+                let width = u16::from_le_bytes([raw[4], raw[5]]);
+                let height = u16::from_le_bytes([raw[6], raw[7]]);
+                Some(format!("{}x{} at {},{}", width, height,
+                             u16::from_le_bytes([raw[0], raw[1]]), u16::from_le_bytes([raw[2], raw[3]])))
+            },
+            GifBlock::Extension { label: GifBlock::GRAPHIC_CONTROL, sub_blocks } => {
+                let block = sub_blocks.first().filter(|block| block.len() >= 3)?;
+                Some(format!("delay {}0ms", u16::from_le_bytes([block[1], block[2]])))
+            },
+            GifBlock::Extension { label: GifBlock::COMMENT, sub_blocks } => {
+                let text: Vec<u8> = sub_blocks.concat();
+                Some(format!("\"{}\"", String::from_utf8_lossy(&text)))
+            },
+            GifBlock::Extension { label: GifBlock::APPLICATION, .. } => {
+                self.application_id().map(|id| String::from_utf8_lossy(id).to_string())
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Application extensions written by us are identified by `RUSH` and the
+/// message key, with this authentication code.
+const MESSAGE_AUTH: &[u8; 3] = b"MSG";
+/// Comment extensions written by us start with `RUSH`, the key and a NUL.
+const MESSAGE_COMMENT_ID: &[u8; 4] = b"RUSH";
+
+/// Reads sub-blocks starting at `idx` up to and including the terminator.
+/// `start` is the offset of the enclosing block, for errors.
+fn read_sub_blocks(value: &[u8], start: usize, mut idx: usize) -> Result<(Vec<Vec<u8>>, usize), GifError> {
+    let mut blocks = vec![];
+    loop {
+        let len = *value.get(idx).ok_or(GifError::Truncated { offset: start })? as usize;
+        idx += 1;
+        if len == 0 {
+            return Ok((blocks, idx));
+        }
+        let block = value.get(idx..idx + len).ok_or(GifError::Truncated { offset: start })?;
+        blocks.push(block.to_vec());
+        idx += len;
+    }
+}
+
+/// A GIF file: header, logical screen descriptor with its global color
+/// table, and the blocks up to the trailer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Gif {
+    version: [u8; 6],
+    screen: [u8; 7],
+    global_color_table: Vec<u8>,
+    blocks: Vec<GifBlock>,
+    trailer: Vec<u8>,
+}
+
+impl TryFrom<&[u8]> for Gif {
+    type Error = GifError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if !(value.starts_with(b"GIF87a") || value.starts_with(b"GIF89a")) {
+            return Err(GifError::NotGif);
+        }
+        let version = <[u8; 6]>::try_from(&value[0..6]).unwrap();
+        let screen = <[u8; 7]>::try_from(value.get(6..13).ok_or(GifError::Truncated { offset: 6 })?).unwrap();
+        let mut idx = 13;
+        let mut global_color_table = vec![];
+        if screen[4] & 0x80 != 0 {
+            let len = 3 << ((screen[4] & 0x07) + 1);
+            global_color_table = value.get(idx..idx + len).ok_or(GifError::Truncated { offset: idx })?.to_vec();
+            idx += len;
+        }
+        let mut blocks = vec![];
+        loop {
+            let start = idx;
+            match value.get(idx) {
+                Some(0x3B) => {
+                    return Ok(Gif { version, screen, global_color_table, blocks, trailer: value[idx + 1..].to_vec() });
+                },
+                Some(0x21) => {
+                    let label = *value.get(idx + 1).ok_or(GifError::Truncated { offset: start })?;
+                    let (sub_blocks, end) = read_sub_blocks(value, start, idx + 2)?;
+                    blocks.push(GifBlock::Extension { label, sub_blocks });
+                    idx = end;
+                },
+                Some(0x2C) => {
+                    let descriptor = value.get(idx + 1..idx + 10).ok_or(GifError::Truncated { offset: start })?;
+                    let mut end = idx + 10;
+                    if descriptor[8] & 0x80 != 0 {
+                        end += 3 << ((descriptor[8] & 0x07) + 1);
+                    }
+                    // LZW minimum code size, then the data sub-blocks
+                    let (_, end) = read_sub_blocks(value, start, end + 1)?;
+                    blocks.push(GifBlock::Image { raw: value[idx + 1..end].to_vec() });
+                    idx = end;
+                },
+                Some(&introducer) => return Err(GifError::UnknownBlock { offset: idx, introducer }),
+                None => return Err(GifError::Truncated { offset: idx }),
+            }
+        }
+    }
+}
+
+impl Gif {
+    pub fn blocks(&self) -> &[GifBlock] {
+        &self.blocks
+    }
+
+    pub fn extensions(&self) -> impl Iterator<Item = &GifBlock> {
+        self.blocks.iter().filter(|block| matches!(block, GifBlock::Extension { .. }))
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = self.version.to_vec();
+        ret.extend_from_slice(&self.screen);
+        ret.extend_from_slice(&self.global_color_table);
+        for block in &self.blocks {
+            ret.extend_from_slice(&block.as_bytes());
+        }
+        ret.push(0x3B);
+        ret.extend_from_slice(&self.trailer);
+        ret
+    }
+
+    fn message_data(block: &GifBlock, key: &[u8; 4]) -> Option<Vec<u8>> {
+        match block {
+            GifBlock::Extension { label: GifBlock::APPLICATION, sub_blocks } => {
+                let id = block.application_id()?;
+                (id[..4] == *MESSAGE_COMMENT_ID && &id[4..8] == key && &id[8..] == MESSAGE_AUTH)
+                    .then(|| sub_blocks[1..].concat())
+            },
+            GifBlock::Extension { label: GifBlock::COMMENT, sub_blocks } => {
+                let data = sub_blocks.concat();
+                let header_len = MESSAGE_COMMENT_ID.len() + 5;
+                (data.len() >= header_len && data[..4] == *MESSAGE_COMMENT_ID && &data[4..8] == key && data[8] == 0)
+                    .then(|| data[header_len..].to_vec())
+            },
+            _ => None,
+        }
+    }
+
+    /// Stores `message` in an application or comment extension placed just
+    /// before the trailer, replacing any message already stored under `key`.
+    /// Extensions need GIF89a, so a GIF87a file is upgraded.
+    pub fn insert_message_with(&mut self, key: &[u8; 4], message: &[u8], carrier: Carrier) {
+        let block = match carrier {
+            Carrier::App => {
+                let mut id = MESSAGE_COMMENT_ID.to_vec();
+                id.extend_from_slice(key);
+                id.extend_from_slice(MESSAGE_AUTH);
+                let mut sub_blocks = vec![id];
+                sub_blocks.extend(message.chunks(255).map(|chunk| chunk.to_vec()));
+                GifBlock::Extension { label: GifBlock::APPLICATION, sub_blocks }
+            },
+            Carrier::Comment => {
+                let mut data = MESSAGE_COMMENT_ID.to_vec();
+                data.extend_from_slice(key);
+                data.push(0);
+                data.extend_from_slice(message);
+                GifBlock::extension(GifBlock::COMMENT, &data)
+            },
+        };
+        self.version = *b"GIF89a";
+        self.blocks.retain(|block| Gif::message_data(block, key).is_none());
+        self.blocks.push(block);
+    }
+
+    pub fn message(&self, key: &[u8; 4]) -> Option<Vec<u8>> {
+        self.blocks.iter().find_map(|block| Gif::message_data(block, key))
+    }
+
+    /// Removes every extension carrying a message under `key`.
+    pub fn remove_message(&mut self, key: &[u8; 4]) -> Result<(), GifError> {
+        let before = self.blocks.len();
+        self.blocks.retain(|block| Gif::message_data(block, key).is_none());
+        if self.blocks.len() == before {
+            Err(GifError::MessageNotFound(String::from_utf8_lossy(key).to_string()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Container for Gif {
+    fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Gif::try_from(bytes)?)
+    }
+
+    fn format(&self) -> Format {
+        Format::Gif
+    }
+
+    fn segments(&self) -> Vec<Segment> {
+        let width = u16::from_le_bytes([self.screen[0], self.screen[1]]);
+        let height = u16::from_le_bytes([self.screen[2], self.screen[3]]);
+        let mut ret = vec![
+            Segment {
+                offset: 0,
+                name: "Header".to_string(),
+                length: 6,
+                summary: Some(String::from_utf8_lossy(&self.version).to_string()),
+            },
+            Segment {
+                offset: 6,
+                name: "Screen".to_string(),
+                length: 7 + self.global_color_table.len(),
+                summary: Some(format!("{}x{}, {} global colors", width, height, self.global_color_table.len() / 3)),
+            },
+        ];
+        let mut offset = 13 + self.global_color_table.len();
+        for block in &self.blocks {
+            let length = block.as_bytes().len();
+            ret.push(Segment { offset, name: block.name(), length, summary: block.summary() });
+            offset += length;
+        }
+        ret.push(Segment { offset, name: "Trailer".to_string(), length: 1, summary: None });
+        ret
+    }
+
+    fn metadata_segments(&self) -> Vec<Segment> {
+        self.segments()
+            .into_iter()
+            .filter(|segment| !matches!(segment.name.as_str(), "Header" | "Screen" | "Image" | "Trailer"))
+            .collect()
+    }
+
+    fn insert_message(&mut self, key: &str, message: &[u8]) -> Result<Vec<String>, Error> {
+        self.insert_message_with(&container::parse_key(key)?, message, Carrier::App);
+        Ok(vec![])
+    }
+
+    fn insert_message_in(&mut self, key: &str, message: &[u8], carrier: &str) -> Result<Vec<String>, Error> {
+        let carrier = carrier
+            .parse()
+            .map_err(|_| Error::UnsupportedCarrier { format: Format::Gif, carrier: carrier.to_string() })?;
+        self.insert_message_with(&container::parse_key(key)?, message, carrier);
+        Ok(vec![])
+    }
+
+    fn extract_message(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.message(&container::parse_key(key)?))
+    }
+
+    fn remove_message(&mut self, key: &str) -> Result<Vec<String>, Error> {
+        Gif::remove_message(self, &container::parse_key(key)?)?;
+        Ok(vec![])
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_gif() -> Vec<u8> {
+        let mut bytes = b"GIF87a".to_vec();
+        // 2x1 screen with a 2-color global table
+        bytes.extend_from_slice(&[2, 0, 1, 0, 0x80, 0, 0]);
+        bytes.extend_from_slice(&[0, 0, 0, 255, 255, 255]);
+        // an existing comment
+        bytes.extend_from_slice(&[0x21, 0xFE, 2, b'h', b'i', 0]);
+        // image descriptor, LZW code size and one data sub-block
+        bytes.extend_from_slice(&[0x2C, 0, 0, 0, 0, 2, 0, 1, 0, 0]);
+        bytes.extend_from_slice(&[2, 3, 0x44, 0x01, 0x05, 0]);
+        bytes.push(0x3B);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let bytes = testing_gif();
+        let gif = Gif::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(gif.blocks().len(), 2);
+        assert_eq!(gif.extensions().count(), 1);
+        assert_eq!(gif.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_invalid_gif() {
+        assert_eq!(Gif::try_from(&b"GIF90a"[..]), Err(GifError::NotGif));
+        let bytes = testing_gif();
+        assert_eq!(Gif::try_from(&bytes[..bytes.len() - 3]), Err(GifError::Truncated { offset: 25 }));
+        let mut bytes = testing_gif();
+        bytes[19] = 0x99;
+        assert_eq!(Gif::try_from(bytes.as_slice()), Err(GifError::UnknownBlock { offset: 19, introducer: 0x99 }));
+    }
+
+    #[test]
+    fn test_application_message() {
+        let mut gif = Gif::try_from(testing_gif().as_slice()).unwrap();
+        let message: Vec<u8> = (0..600).map(|i| i as u8).collect();
+        gif.insert_message_with(b"ruSt", &message, Carrier::App);
+
+        let mut gif = Gif::try_from(gif.as_bytes().as_slice()).unwrap();
+        assert!(gif.as_bytes().starts_with(b"GIF89a"));
+        match gif.blocks().last().unwrap() {
+            GifBlock::Extension { sub_blocks, .. } => {
+                let lens: Vec<usize> = sub_blocks.iter().map(|block| block.len()).collect();
+                assert_eq!(lens, vec![11, 255, 255, 90]);
+            },
+            block => panic!("unexpected block {:?}", block),
+        }
+        assert_eq!(gif.message(b"ruSt"), Some(message));
+        gif.remove_message(b"ruSt").unwrap();
+        assert_eq!(gif.message(b"ruSt"), None);
+    }
+
+    #[test]
+    fn test_comment_message() {
+        let mut gif = Gif::try_from(testing_gif().as_slice()).unwrap();
+        gif.insert_message_with(b"ruSt", b"stale", Carrier::App);
+        gif.insert_message_with(b"ruSt", b"hello", Carrier::Comment);
+        let mut gif = Gif::try_from(gif.as_bytes().as_slice()).unwrap();
+        assert_eq!(gif.message(b"ruSt"), Some(b"hello".to_vec()));
+        assert_eq!(gif.metadata_segments().len(), 2);
+        gif.remove_message(b"ruSt").unwrap();
+        assert_eq!(gif.remove_message(b"ruSt"), Err(GifError::MessageNotFound("ruSt".to_string())));
+    }
+}
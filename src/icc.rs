@@ -0,0 +1,271 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::bytes::get_be_u32;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::registry::{ChunkData, DecodeError};
+use crate::text::{deflate, inflate};
+
+/// Why an `iCCP` chunk or the profile inside it could not be used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IccError {
+    /// Profile names are 1 to 79 Latin-1 characters.
+    InvalidName,
+    /// The null separator or compression method is missing.
+    Truncated,
+    /// The compression method is unknown, or the data does not inflate.
+    InvalidCompression,
+    /// The profile header is shorter than 128 bytes or lacks the `acsp`
+    /// signature.
+    InvalidProfile,
+    /// A PNG may not carry both `iCCP` and `sRGB`.
+    ConflictsWithSrgb,
+}
+
+impl Display for IccError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IccError::InvalidName => write!(f, "icc profile name must be 1 to 79 latin-1 characters"),
+            IccError::Truncated => write!(f, "iCCP chunk is truncated"),
+            IccError::InvalidCompression => write!(f, "iCCP chunk has invalid compressed data"),
+            IccError::InvalidProfile => write!(f, "not an icc profile"),
+            IccError::ConflictsWithSrgb => write!(f, "png already has an sRGB chunk, which may not appear with iCCP"),
+        }
+    }
+}
+
+impl std::error::Error for IccError {}
+
+/// The fields of the 128-byte profile header worth showing, plus the
+/// profile description tag.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IccHeader {
+    pub size: u32,
+    pub version: (u8, u8),
+    /// Device class, e.g. `mntr` for displays.
+    pub device_class: String,
+    /// Data color space, e.g. `RGB ` or `GRAY`.
+    pub color_space: String,
+    /// Profile connection space, `XYZ ` or `Lab `.
+    pub connection_space: String,
+    pub description: Option<String>,
+}
+
+impl Display for IccHeader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(description) = &self.description {
+            write!(f, "{:?}, ", description)?;
+        }
+        write!(f, "{} {} v{}.{}, {} bytes",
+               self.color_space.trim_end(), self.device_class, self.version.0, self.version.1, self.size)
+    }
+}
+
+fn signature(data: &[u8], offset: usize) -> String {
+    data[offset..offset + 4].iter().map(|&b| b as char).collect()
+}
+
+/// Reads a `desc` (v2) or `mluc` (v4) tag, preferring the first record.
+fn description(tag: &[u8]) -> Option<String> {
+    match tag.get(..4)? {
+        b"desc" => {
+            let len = get_be_u32(tag, 8)? as usize;
+            let text = tag.get(12..12 + len)?;
+            Some(text.iter().take_while(|&&b| b != 0).map(|&b| b as char).collect())
+        },
+        b"mluc" => {
+            let records = get_be_u32(tag, 8)?;
+            if records == 0 {
+                return None;
+            }
+            let len = get_be_u32(tag, 20)? as usize;
+            let offset = get_be_u32(tag, 24)? as usize;
+            let units: Vec<u16> = tag.get(offset..offset + len)?
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&units).trim_end_matches('\0').to_string())
+        },
+        _ => None,
+    }
+}
+
+/// The `iCCP` chunk: a named, zlib-compressed ICC profile.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IccProfile {
+    pub name: String,
+    pub profile: Vec<u8>,
+}
+
+impl TryFrom<&[u8]> for IccProfile {
+    type Error = IccError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let sep = data.iter().position(|&b| b == 0).ok_or(IccError::Truncated)?;
+        if sep == 0 || sep > 79 {
+            return Err(IccError::InvalidName);
+        }
+        match data.get(sep + 1) {
+            Some(0) => {},
+            Some(_) => return Err(IccError::InvalidCompression),
+            None => return Err(IccError::Truncated),
+        }
+        Ok(IccProfile {
+            name: data[..sep].iter().map(|&b| b as char).collect(),
+            profile: inflate(&data[sep + 2..]).map_err(|_| IccError::InvalidCompression)?,
+        })
+    }
+}
+
+impl IccProfile {
+    pub fn new(name: &str, profile: Vec<u8>) -> Result<IccProfile, IccError> {
+        if name.is_empty() || name.chars().count() > 79 || name.chars().any(|c| c as u32 > 0xFF || c == '\0') {
+            return Err(IccError::InvalidName);
+        }
+        Ok(IccProfile { name: name.to_string(), profile })
+    }
+
+    /// Reads the profile in the PNG's `iCCP` chunk, if there is one.
+    pub fn from_png(png: &Png) -> Result<Option<IccProfile>, IccError> {
+        png.chunk_by_type("iCCP").map(|chunk| IccProfile::try_from(chunk.data())).transpose()
+    }
+
+    /// Stores the profile in `iCCP`, replacing any existing one. A PNG
+    /// with `sRGB` is rejected unless `replace_srgb` is set, in which case
+    /// the `sRGB` chunk is removed.
+    pub fn write_to_png(&self, png: &mut Png, replace_srgb: bool) -> Result<(), IccError> {
+        if png.chunk_by_type("sRGB").is_some() {
+            if !replace_srgb {
+                return Err(IccError::ConflictsWithSrgb);
+            }
+            png.strip_chunks(|chunk| &chunk.chunk_type().bytes() == b"sRGB");
+        }
+        png.set_chunk(self.to_chunk());
+        Ok(())
+    }
+
+    pub fn header(&self) -> Result<IccHeader, IccError> {
+        let data = &self.profile;
+        if data.len() < 128 || &data[36..40] != b"acsp" {
+            return Err(IccError::InvalidProfile);
+        }
+        let description = get_be_u32(data, 128).and_then(|count| {
+            (0..count as usize).find_map(|i| {
+                let entry = 132 + i * 12;
+                if data.get(entry..entry + 4)? != b"desc" {
+                    return None;
+                }
+                let offset = get_be_u32(data, entry + 4)? as usize;
+                let size = get_be_u32(data, entry + 8)? as usize;
+                description(data.get(offset..offset.checked_add(size)?)?)
+            })
+        });
+        Ok(IccHeader {
+            size: get_be_u32(data, 0).unwrap(),
+            version: (data[8], data[9] >> 4),
+            device_class: signature(data, 12),
+            color_space: signature(data, 16),
+            connection_space: signature(data, 20),
+            description,
+        })
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data: Vec<u8> = self.name.chars().map(|c| c as u8).collect();
+        data.push(0);
+        data.push(0);
+        data.extend_from_slice(&deflate(&self.profile));
+        Chunk::new(ChunkType::from_str("iCCP").unwrap(), data)
+    }
+}
+
+impl ChunkData for IccProfile {
+    const CHUNK_TYPES: &'static [&'static str] = &["iCCP"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        IccProfile::try_from(chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        self.to_chunk()
+    }
+
+    fn summary(&self) -> String {
+        match self.header() {
+            Ok(header) => format!("{}: {}", self.name, header),
+            Err(e) => format!("{}: {}", self.name, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal v4 display profile with only an `mluc` description.
+    fn testing_profile() -> Vec<u8> {
+        let text: Vec<u8> = "Rush RGB".encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+        let mut tag = b"mluc\0\0\0\0".to_vec();
+        tag.extend_from_slice(&1u32.to_be_bytes());
+        tag.extend_from_slice(&12u32.to_be_bytes());
+        tag.extend_from_slice(b"enUS");
+        tag.extend_from_slice(&(text.len() as u32).to_be_bytes());
+        tag.extend_from_slice(&28u32.to_be_bytes());
+        tag.extend_from_slice(&text);
+
+        let mut profile = vec![0; 128];
+        profile[8] = 4;
+        profile[9] = 0x30;
+        profile[12..16].copy_from_slice(b"mntr");
+        profile[16..20].copy_from_slice(b"RGB ");
+        profile[20..24].copy_from_slice(b"XYZ ");
+        profile[36..40].copy_from_slice(b"acsp");
+        profile.extend_from_slice(&1u32.to_be_bytes());
+        profile.extend_from_slice(b"desc");
+        profile.extend_from_slice(&144u32.to_be_bytes());
+        profile.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+        profile.extend_from_slice(&tag);
+        let size = profile.len() as u32;
+        profile[..4].copy_from_slice(&size.to_be_bytes());
+        profile
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("sRGB").unwrap(), vec![0]),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let icc = IccProfile::new("Rush RGB", testing_profile()).unwrap();
+        assert_eq!(IccProfile::try_from(icc.to_chunk().data()).unwrap(), icc);
+        let header = icc.header().unwrap();
+        assert_eq!(header.description.as_deref(), Some("Rush RGB"));
+        assert_eq!(header.to_string(), "\"Rush RGB\", RGB mntr v4.3, 188 bytes");
+    }
+
+    #[test]
+    fn test_invalid_icc() {
+        assert_eq!(IccProfile::try_from(&b"\0\0"[..]), Err(IccError::InvalidName));
+        assert_eq!(IccProfile::try_from(&b"Name\0"[..]), Err(IccError::Truncated));
+        assert_eq!(IccProfile::try_from(&b"Name\0\x01"[..]), Err(IccError::InvalidCompression));
+        assert_eq!(IccProfile::new("", vec![]), Err(IccError::InvalidName));
+        assert_eq!(IccProfile::new("x", vec![0; 128]).unwrap().header(), Err(IccError::InvalidProfile));
+    }
+
+    #[test]
+    fn test_write_to_png() {
+        let mut png = testing_png();
+        let icc = IccProfile::new("Rush RGB", testing_profile()).unwrap();
+        assert_eq!(icc.write_to_png(&mut png, false), Err(IccError::ConflictsWithSrgb));
+        icc.write_to_png(&mut png, true).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "iCCP", "IDAT", "IEND"]);
+        assert_eq!(IccProfile::from_png(&png), Ok(Some(icc)));
+    }
+}
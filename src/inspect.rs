@@ -0,0 +1,283 @@
+use std::fmt::Write;
+use crate::bytes::latin1;
+use crate::chunk::Chunk;
+use crate::color::SignificantBits;
+use crate::container::Segment;
+use crate::png::Png;
+use crate::registry::Registry;
+use crate::trailer::TrailerKind;
+
+/// One row of the `print` table.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ChunkInfo {
+    pub offset: usize,
+    pub chunk_type: String,
+    pub length: u32,
+    pub crc: u32,
+    pub computed_crc: u32,
+    pub critical: bool,
+    pub public: bool,
+    pub reserved_bit_valid: bool,
+    pub safe_to_copy: bool,
+    pub summary: Option<String>,
+}
+
+impl ChunkInfo {
+    /// Property bits as four columns: `C`ritical, `P`ublic, `R`eserved bit
+    /// valid, `S`afe-to-copy, with `-` where the bit is not set.
+    pub fn flags(&self) -> String {
+        [(self.critical, 'C'), (self.public, 'P'), (self.reserved_bit_valid, 'R'), (self.safe_to_copy, 'S')]
+            .iter()
+            .map(|&(set, c)| if set { c } else { '-' })
+            .collect()
+    }
+}
+
+pub fn inspect(png: &Png) -> Vec<ChunkInfo> {
+    inspect_with(png, Registry::builtin())
+}
+
+/// Like `inspect`, summarizing chunks with the decoders in `registry`.
+pub fn inspect_with(png: &Png, registry: &Registry) -> Vec<ChunkInfo> {
+    let mut offset = png.header().len();
+    let mut rows = vec![];
+    for chunk in png.chunks() {
+        let chunk_type = chunk.chunk_type();
+        rows.push(ChunkInfo {
+            offset,
+            chunk_type: chunk_type.to_string(),
+            length: chunk.length(),
+            crc: chunk.crc(),
+            computed_crc: chunk.computed_crc(),
+            critical: chunk_type.is_critical(),
+            public: chunk_type.is_public(),
+            reserved_bit_valid: chunk_type.is_reserved_bit_valid(),
+            safe_to_copy: chunk_type.is_safe_to_copy(),
+            summary: summarize_with(registry, chunk),
+        });
+        offset += chunk.length() as usize + 12;
+    }
+    rows
+}
+
+pub(crate) fn shorten(s: &str, max: usize) -> String {
+    if s.chars().count() > max {
+        format!("{}...", s.chars().take(max).collect::<String>())
+    } else {
+        s.to_string()
+    }
+}
+
+/// A one-line decoding of the chunks whose layout we know.
+pub fn summarize(chunk: &Chunk) -> Option<String> {
+    summarize_with(Registry::builtin(), chunk)
+}
+
+/// Like `summarize`, but with the decoders in `registry`. Chunks that need
+/// other chunks to decode, like `PLTE`, are summarized from their size.
+pub fn summarize_with(registry: &Registry, chunk: &Chunk) -> Option<String> {
+    if let Some(summary) = registry.summarize(chunk) {
+        return Some(summary);
+    }
+    let data = chunk.data();
+    match &chunk.chunk_type().bytes() {
+        b"PLTE" => Some(format!("{} entries", data.len() / 3)),
+        b"hIST" => Some(format!("{} entries", data.len() / 2)),
+        b"sBIT" => Some(SignificantBits(data.to_vec()).to_string()),
+        b"tEXt" => {
+            let sep = data.iter().position(|&b| b == 0)?;
+            Some(format!("{}: {}", latin1(&data[..sep]), shorten(&latin1(&data[sep + 1..]), 40)))
+        },
+        b"zTXt" => {
+            let sep = data.iter().position(|&b| b == 0)?;
+            Some(format!("{} (compressed text)", latin1(&data[..sep])))
+        },
+        _ if !chunk.chunk_type().is_public() => {
+            chunk.data_as_string().ok().map(|s| format!("\"{}\"", shorten(&s, 40)))
+        },
+        _ => None,
+    }
+}
+
+pub fn render_table(rows: &[ChunkInfo]) -> String {
+    let mut out = String::new();
+    writeln!(out, "{:>8}  {:<4}  {:>8}  {:<8}  {:<8}  {:<4}  summary",
+             "offset", "type", "length", "crc", "computed", "flags").unwrap();
+    for row in rows {
+        let computed = if row.crc == row.computed_crc {
+            "ok".to_string()
+        } else {
+            format!("{:08x}", row.computed_crc)
+        };
+        let line = format!("{:>8}  {:<4}  {:>8}  {:08x}  {:<8}  {:<4}  {}",
+                           row.offset, row.chunk_type, row.length, row.crc, computed, row.flags(),
+                           row.summary.as_deref().unwrap_or(""));
+        writeln!(out, "{}", line.trim_end()).unwrap();
+    }
+    out
+}
+
+/// The generic `print` table for formats other than PNG.
+pub fn render_segments(segments: &[Segment]) -> String {
+    let mut out = String::new();
+    writeln!(out, "{:>8}  {:<14}  {:>8}  summary", "offset", "segment", "length").unwrap();
+    for segment in segments {
+        let line = format!("{:>8}  {:<14}  {:>8}  {}",
+                           segment.offset, segment.name, segment.length,
+                           segment.summary.as_deref().unwrap_or(""));
+        writeln!(out, "{}", line.trim_end()).unwrap();
+    }
+    out
+}
+
+/// Classic 16-bytes-per-line dump with offsets relative to the chunk data.
+pub fn hex_dump(data: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, line) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = line
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        writeln!(out, "{:08x}  {:<47}  |{}|", i * 16, hex.join(" "), ascii).unwrap();
+    }
+    out
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Renders the rows as a JSON object with a `chunks` array and a `trailer`
+/// object (or `null`) for any data after `IEND`. `hex` holds the chunk data
+/// to attach, by row index, for the chunks selected with `--hex`.
+pub fn render_json(rows: &[ChunkInfo], hex: &[Option<&[u8]>], trailer: &[u8], trailer_offset: usize) -> String {
+    let mut items = vec![];
+    for (i, row) in rows.iter().enumerate() {
+        let mut item = format!(
+            "{{\"offset\":{},\"type\":{},\"length\":{},\"crc\":{},\"computed_crc\":{},\
+             \"critical\":{},\"public\":{},\"reserved_bit_valid\":{},\"safe_to_copy\":{},\"summary\":{}",
+            row.offset,
+            json_string(&row.chunk_type),
+            row.length,
+            row.crc,
+            row.computed_crc,
+            row.critical,
+            row.public,
+            row.reserved_bit_valid,
+            row.safe_to_copy,
+            row.summary.as_deref().map(json_string).unwrap_or_else(|| "null".to_string()),
+        );
+        if let Some(Some(data)) = hex.get(i) {
+            let data: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+            write!(item, ",\"data_hex\":\"{}\"", data).unwrap();
+        }
+        item.push('}');
+        items.push(item);
+    }
+    // Bytes after IEND show up as a last entry of type "trailer".
+    if !trailer.is_empty() {
+        items.push(format!(
+            "{{\"offset\":{},\"type\":\"trailer\",\"length\":{},\"kind\":{}}}",
+            trailer_offset,
+            trailer.len(),
+            TrailerKind::detect(trailer).map(|kind| json_string(kind.extension())).unwrap_or_else(|| "null".to_string()),
+        ));
+    }
+    format!("[{}]", items.join(","))
+}
+
+pub fn render_segments_json(segments: &[Segment]) -> String {
+    let items: Vec<String> = segments
+        .iter()
+        .map(|segment| {
+            format!("{{\"offset\":{},\"name\":{},\"length\":{},\"summary\":{}}}",
+                    segment.offset,
+                    json_string(&segment.name),
+                    segment.length,
+                    segment.summary.as_deref().map(json_string).unwrap_or_else(|| "null".to_string()))
+        })
+        .collect();
+    format!("[{}]", items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 50, 0, 0, 0, 40, 8, 6, 0, 0, 0]),
+            chunk("tEXt", b"Title\0dice"),
+            chunk("ruSt", b"hey"),
+            chunk("IEND", &[]),
+        ])
+    }
+
+    #[test]
+    fn test_inspect_offsets_and_flags() {
+        let rows = inspect(&testing_png());
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0].offset, 8);
+        assert_eq!(rows[1].offset, 8 + 13 + 12);
+        assert_eq!(rows[0].flags(), "CPR-");
+        assert_eq!(rows[2].flags(), "--RS");
+        assert_eq!(rows[0].crc, rows[0].computed_crc);
+    }
+
+    #[test]
+    fn test_summaries() {
+        let rows = inspect(&testing_png());
+        assert_eq!(rows[0].summary.as_deref(), Some("50x40 8-bit rgba, non-interlaced"));
+        assert_eq!(rows[1].summary.as_deref(), Some("Title: dice"));
+        assert_eq!(rows[2].summary.as_deref(), Some("\"hey\""));
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let dump = hex_dump(b"hello\0");
+        assert_eq!(dump, "00000000  68 65 6c 6c 6f 00                                |hello.|\n");
+    }
+
+    #[test]
+    fn test_render_segments() {
+        let segments = vec![Segment { offset: 2, name: "APP0".to_string(), length: 16, summary: None }];
+        assert_eq!(render_segments(&segments).lines().nth(1), Some("       2  APP0                  16"));
+        assert_eq!(render_segments_json(&segments), "[{\"offset\":2,\"name\":\"APP0\",\"length\":16,\"summary\":null}]");
+    }
+
+    #[test]
+    fn test_render_json() {
+        let png = testing_png();
+        let rows = inspect(&png);
+        let hex = vec![None, None, Some(png.chunks()[2].data()), None];
+        let json = render_json(&rows, &hex, &[], 0);
+        assert!(json.starts_with("[{\"offset\":8,\"type\":\"IHDR\""));
+        assert!(json.contains("\"summary\":\"Title: dice\""));
+        assert!(json.contains("\"data_hex\":\"686579\""));
+        assert!(json.contains("\"summary\":null"));
+        assert!(json.ends_with("\"summary\":null}]"));
+
+        let json = render_json(&rows, &hex, b"PK\x05\x06", 120);
+        assert!(json.ends_with("},{\"offset\":120,\"type\":\"trailer\",\"length\":4,\"kind\":\"zip\"}]"));
+    }
+}
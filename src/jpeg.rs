@@ -0,0 +1,457 @@
+use std::fmt::{Display, Formatter};
+use crate::container::{self, Carrier, Container, Format, Segment};
+use crate::Error;
+
+/// Why a byte slice could not be read as a `Jpeg`, or a message could not be
+/// found in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JpegError {
+    /// The file does not start with an SOI marker.
+    MissingSoi,
+    /// Expected a `0xFF` marker prefix at `offset`.
+    InvalidMarker { offset: usize },
+    /// The segment starting at `offset` runs past the end of the file.
+    Truncated { offset: usize },
+    /// A message does not fit in 65535 segments.
+    MessageTooLarge,
+    /// No segment carries a message under this key.
+    MessageNotFound(String),
+    /// Some parts of a split message are missing.
+    IncompleteMessage(String),
+}
+
+impl Display for JpegError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JpegError::MissingSoi => write!(f, "not a jpeg: missing SOI marker"),
+            JpegError::InvalidMarker { offset } => write!(f, "expected a jpeg marker at offset {}", offset),
+            JpegError::Truncated { offset } => write!(f, "jpeg segment at offset {} is truncated", offset),
+            JpegError::MessageTooLarge => write!(f, "message is too large for a jpeg"),
+            JpegError::MessageNotFound(key) => write!(f, "no {} message found", key),
+            JpegError::IncompleteMessage(key) => write!(f, "parts of the {} message are missing", key),
+        }
+    }
+}
+
+impl std::error::Error for JpegError {}
+
+/// A marker segment. For SOS, `scan` holds the entropy-coded data that
+/// follows the header up to the next marker, passed through untouched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JpegSegment {
+    marker: u8,
+    data: Vec<u8>,
+    scan: Vec<u8>,
+}
+
+impl JpegSegment {
+    pub const SOI: u8 = 0xD8;
+    pub const EOI: u8 = 0xD9;
+    pub const SOS: u8 = 0xDA;
+    pub const DQT: u8 = 0xDB;
+    pub const DHT: u8 = 0xC4;
+    pub const COM: u8 = 0xFE;
+    pub const APP0: u8 = 0xE0;
+    /// The segment payload can't exceed this, since the length field counts
+    /// its own two bytes.
+    pub const MAX_DATA_LENGTH: usize = 65533;
+
+    pub fn new(marker: u8, data: Vec<u8>) -> JpegSegment {
+        JpegSegment { marker, data, scan: vec![] }
+    }
+
+    pub fn marker(&self) -> u8 {
+        self.marker
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn scan(&self) -> &[u8] {
+        &self.scan
+    }
+
+    /// Markers without a length field.
+    pub fn is_standalone(marker: u8) -> bool {
+        matches!(marker, 0x01 | 0xD0..=0xD9)
+    }
+
+    pub fn is_app(&self) -> bool {
+        (0xE0..=0xEF).contains(&self.marker)
+    }
+
+    pub fn name(&self) -> String {
+        match self.marker {
+            JpegSegment::SOI => "SOI".to_string(),
+            JpegSegment::EOI => "EOI".to_string(),
+            JpegSegment::SOS => "SOS".to_string(),
+            JpegSegment::DQT => "DQT".to_string(),
+            JpegSegment::DHT => "DHT".to_string(),
+            JpegSegment::COM => "COM".to_string(),
+            0xDD => "DRI".to_string(),
+            0xC8 => "JPG".to_string(),
+            0xCC => "DAC".to_string(),
+            m @ 0xC0..=0xCF => format!("SOF{}", m - 0xC0),
+            m @ 0xD0..=0xD7 => format!("RST{}", m - 0xD0),
+            m @ 0xE0..=0xEF => format!("APP{}", m - 0xE0),
+            m => format!("0x{:02X}", m),
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = vec![0xFF, self.marker];
+        if !JpegSegment::is_standalone(self.marker) {
+            ret.extend_from_slice(&(self.data.len() as u16 + 2).to_be_bytes());
+            ret.extend_from_slice(&self.data);
+        }
+        ret.extend_from_slice(&self.scan);
+        ret
+    }
+
+    fn summary(&self) -> Option<String> {
+        if let Some((key, index, count)) = parse_message_header(&self.data) {
+            return Some(format!("{} message part {}/{}", String::from_utf8_lossy(&key), index + 1, count));
+        }
+        match self.marker {
+            0xC0..=0xCF if !matches!(self.marker, 0xC4 | 0xC8 | 0xCC) && self.data.len() >= 6 => {
+                let height = u16::from_be_bytes([self.data[1], self.data[2]]);
+                let width = u16::from_be_bytes([self.data[3], self.data[4]]);
+                Some(format!("{}x{} {}-bit, {} components", width, height, self.data[0], self.data[5]))
+            },
+            JpegSegment::SOS => Some(format!("{} bytes of entropy-coded data", self.scan.len())),
+            JpegSegment::COM => Some(format!("\"{}\"", String::from_utf8_lossy(&self.data))),
+            _ if self.is_app() => {
+                let id_end = self.data.iter().position(|&b| b == 0).unwrap_or(self.data.len()).min(32);
+                Some(String::from_utf8_lossy(&self.data[..id_end]).to_string())
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Every message segment starts with this identifier, the 4-byte key and a
+/// big-endian part index and part count.
+const MESSAGE_ID: &[u8; 5] = b"RUSH\0";
+const MESSAGE_HEADER_LENGTH: usize = MESSAGE_ID.len() + 4 + 2 + 2;
+const MESSAGE_MARKER: u8 = 0xEF;
+
+fn parse_message_header(data: &[u8]) -> Option<([u8; 4], u16, u16)> {
+    if data.len() < MESSAGE_HEADER_LENGTH || !data.starts_with(MESSAGE_ID) {
+        return None;
+    }
+    let key = <[u8; 4]>::try_from(&data[5..9]).unwrap();
+    let index = u16::from_be_bytes([data[9], data[10]]);
+    let count = u16::from_be_bytes([data[11], data[12]]);
+    Some((key, index, count))
+}
+
+/// A JPEG file as its list of marker segments, from SOI to EOI, plus any
+/// bytes found after EOI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Jpeg {
+    segments: Vec<JpegSegment>,
+    trailer: Vec<u8>,
+}
+
+impl TryFrom<&[u8]> for Jpeg {
+    type Error = JpegError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if !value.starts_with(&[0xFF, JpegSegment::SOI]) {
+            return Err(JpegError::MissingSoi);
+        }
+        let mut segments = vec![JpegSegment::new(JpegSegment::SOI, vec![])];
+        let mut idx = 2;
+        while idx < value.len() {
+            let start = idx;
+            if value[idx] != 0xFF {
+                return Err(JpegError::InvalidMarker { offset: idx });
+            }
+            // any number of 0xFF fill bytes may precede a marker
+            while idx < value.len() && value[idx] == 0xFF {
+                idx += 1;
+            }
+            let marker = *value.get(idx).ok_or(JpegError::Truncated { offset: start })?;
+            idx += 1;
+            if marker == JpegSegment::EOI {
+                segments.push(JpegSegment::new(marker, vec![]));
+                return Ok(Jpeg { segments, trailer: value[idx..].to_vec() });
+            }
+            if JpegSegment::is_standalone(marker) {
+                segments.push(JpegSegment::new(marker, vec![]));
+                continue;
+            }
+            let length = value
+                .get(idx..idx + 2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+                .filter(|&length| length >= 2 && idx + length <= value.len())
+                .ok_or(JpegError::Truncated { offset: start })?;
+            let mut segment = JpegSegment::new(marker, value[idx + 2..idx + length].to_vec());
+            idx += length;
+            if marker == JpegSegment::SOS {
+                let scan_start = idx;
+                while idx < value.len() {
+                    if value[idx] == 0xFF && idx + 1 < value.len() {
+                        let next = value[idx + 1];
+                        if next != 0x00 && !(0xD0..=0xD7).contains(&next) && next != 0xFF {
+                            break;
+                        }
+                    }
+                    idx += 1;
+                }
+                segment.scan = value[scan_start..idx].to_vec();
+            }
+            segments.push(segment);
+        }
+        Err(JpegError::Truncated { offset: value.len() })
+    }
+}
+
+impl Jpeg {
+    pub fn from_segments(segments: Vec<JpegSegment>) -> Jpeg {
+        Jpeg { segments, trailer: vec![] }
+    }
+
+    pub fn segments(&self) -> &[JpegSegment] {
+        &self.segments
+    }
+
+    /// Bytes after EOI, if any.
+    pub fn trailer(&self) -> &[u8] {
+        &self.trailer
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret: Vec<u8> = self.segments.iter().flat_map(|segment| segment.as_bytes()).collect();
+        ret.extend_from_slice(&self.trailer);
+        ret
+    }
+
+    fn is_message_for(segment: &JpegSegment, key: &[u8; 4]) -> bool {
+        matches!(segment.marker, MESSAGE_MARKER | JpegSegment::COM)
+            && parse_message_header(&segment.data).map(|(k, _, _)| &k == key).unwrap_or(false)
+    }
+
+    /// Stores `message` in APP15 or COM segments right after SOI and the
+    /// leading APPn segments (so JFIF/Exif stay first), splitting it when
+    /// it does not fit in a single segment. Any message already stored under
+    /// `key` is replaced.
+    pub fn insert_message_with(&mut self, key: &[u8; 4], message: &[u8], carrier: Carrier) -> Result<(), JpegError> {
+        let marker = match carrier {
+            Carrier::App => MESSAGE_MARKER,
+            Carrier::Comment => JpegSegment::COM,
+        };
+        let capacity = JpegSegment::MAX_DATA_LENGTH - MESSAGE_HEADER_LENGTH;
+        let parts: Vec<&[u8]> = if message.is_empty() { vec![&[]] } else { message.chunks(capacity).collect() };
+        let count = u16::try_from(parts.len()).map_err(|_| JpegError::MessageTooLarge)?;
+        self.segments.retain(|segment| !Jpeg::is_message_for(segment, key));
+        let idx = self
+            .segments
+            .iter()
+            .position(|segment| segment.marker != JpegSegment::SOI && !segment.is_app())
+            .unwrap_or(self.segments.len());
+        let new_segments = parts.into_iter().enumerate().map(|(index, part)| {
+            let mut data = MESSAGE_ID.to_vec();
+            data.extend_from_slice(key);
+            data.extend_from_slice(&(index as u16).to_be_bytes());
+            data.extend_from_slice(&count.to_be_bytes());
+            data.extend_from_slice(part);
+            JpegSegment::new(marker, data)
+        });
+        self.segments.splice(idx..idx, new_segments);
+        Ok(())
+    }
+
+    /// Reassembles the message stored under `key` from its parts.
+    pub fn message(&self, key: &[u8; 4]) -> Result<Option<Vec<u8>>, JpegError> {
+        let mut parts: Vec<(u16, u16, &[u8])> = self
+            .segments
+            .iter()
+            .filter(|segment| Jpeg::is_message_for(segment, key))
+            .map(|segment| {
+                let (_, index, count) = parse_message_header(&segment.data).unwrap();
+                (index, count, &segment.data[MESSAGE_HEADER_LENGTH..])
+            })
+            .collect();
+        if parts.is_empty() {
+            return Ok(None);
+        }
+        parts.sort_by_key(|&(index, _, _)| index);
+        let count = parts[0].1;
+        let complete = parts.len() == count as usize
+            && parts.iter().enumerate().all(|(i, &(index, c, _))| index as usize == i && c == count);
+        if !complete {
+            return Err(JpegError::IncompleteMessage(String::from_utf8_lossy(key).to_string()));
+        }
+        Ok(Some(parts.into_iter().flat_map(|(_, _, data)| data.iter().copied()).collect()))
+    }
+
+    /// Removes every part of the message stored under `key`.
+    pub fn remove_message(&mut self, key: &[u8; 4]) -> Result<(), JpegError> {
+        let before = self.segments.len();
+        self.segments.retain(|segment| !Jpeg::is_message_for(segment, key));
+        if self.segments.len() == before {
+            Err(JpegError::MessageNotFound(String::from_utf8_lossy(key).to_string()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Container for Jpeg {
+    fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Jpeg::try_from(bytes)?)
+    }
+
+    fn format(&self) -> Format {
+        Format::Jpeg
+    }
+
+    fn segments(&self) -> Vec<Segment> {
+        let mut offset = 0;
+        let mut ret = vec![];
+        for segment in &self.segments {
+            let length = segment.as_bytes().len();
+            ret.push(Segment {
+                offset,
+                name: segment.name(),
+                length,
+                summary: segment.summary(),
+            });
+            offset += length;
+        }
+        ret
+    }
+
+    fn insert_message(&mut self, key: &str, message: &[u8]) -> Result<Vec<String>, Error> {
+        self.insert_message_with(&container::parse_key(key)?, message, Carrier::App)?;
+        Ok(vec![])
+    }
+
+    fn insert_message_in(&mut self, key: &str, message: &[u8], carrier: &str) -> Result<Vec<String>, Error> {
+        let carrier = carrier
+            .parse()
+            .map_err(|_| Error::UnsupportedCarrier { format: Format::Jpeg, carrier: carrier.to_string() })?;
+        self.insert_message_with(&container::parse_key(key)?, message, carrier)?;
+        Ok(vec![])
+    }
+
+    fn extract_message(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.message(&container::parse_key(key)?)?)
+    }
+
+    fn remove_message(&mut self, key: &str) -> Result<Vec<String>, Error> {
+        Jpeg::remove_message(self, &container::parse_key(key)?)?;
+        Ok(vec![])
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_jpeg() -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xD8];
+        // APP0 JFIF
+        bytes.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x10]);
+        bytes.extend_from_slice(b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+        // DQT with a dummy table
+        bytes.extend_from_slice(&[0xFF, 0xDB, 0x00, 0x05, 0x00, 0x01, 0x02]);
+        // SOF0: 8-bit, 16x8, 1 component
+        bytes.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x08, 0x00, 0x10, 0x01, 0x01, 0x11, 0x00]);
+        // DHT with a dummy table
+        bytes.extend_from_slice(&[0xFF, 0xC4, 0x00, 0x04, 0x00, 0x00]);
+        // SOS followed by entropy-coded data with a stuffed byte and a restart marker
+        bytes.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00]);
+        bytes.extend_from_slice(&[0x12, 0xFF, 0x00, 0x34, 0xFF, 0xD0, 0x56]);
+        bytes.extend_from_slice(&[0xFF, 0xD9]);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_segments() {
+        let jpeg = Jpeg::try_from(testing_jpeg().as_slice()).unwrap();
+        let names: Vec<String> = jpeg.segments().iter().map(|segment| segment.name()).collect();
+        assert_eq!(names, vec!["SOI", "APP0", "DQT", "SOF0", "DHT", "SOS", "EOI"]);
+        assert_eq!(jpeg.segments()[5].scan(), &[0x12, 0xFF, 0x00, 0x34, 0xFF, 0xD0, 0x56]);
+        assert_eq!(jpeg.segments()[3].summary().as_deref(), Some("16x8 8-bit, 1 components"));
+    }
+
+    #[test]
+    fn test_as_bytes_round_trip() {
+        let mut bytes = testing_jpeg();
+        bytes.extend_from_slice(b"trailing");
+        let jpeg = Jpeg::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(jpeg.trailer(), b"trailing");
+        assert_eq!(jpeg.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_invalid_jpeg() {
+        assert_eq!(Jpeg::try_from(&b"\xFF\xD9"[..]), Err(JpegError::MissingSoi));
+        let bytes = testing_jpeg();
+        assert_eq!(Jpeg::try_from(&bytes[..30]), Err(JpegError::Truncated { offset: 27 }));
+        assert!(Jpeg::try_from(&bytes[..bytes.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn test_message_after_app_segments() {
+        let mut jpeg = Jpeg::try_from(testing_jpeg().as_slice()).unwrap();
+        jpeg.insert_message_with(b"ruSt", b"hello", Carrier::App).unwrap();
+        assert_eq!(jpeg.segments()[2].name(), "APP15");
+
+        let jpeg = Jpeg::try_from(jpeg.as_bytes().as_slice()).unwrap();
+        assert_eq!(jpeg.message(b"ruSt").unwrap(), Some(b"hello".to_vec()));
+        assert_eq!(jpeg.message(b"abcd").unwrap(), None);
+    }
+
+    #[test]
+    fn test_large_message_is_split() {
+        let mut jpeg = Jpeg::try_from(testing_jpeg().as_slice()).unwrap();
+        let message: Vec<u8> = (0..150_000).map(|i| i as u8).collect();
+        jpeg.insert_message_with(b"ruSt", &message, Carrier::Comment).unwrap();
+        let parts = jpeg.segments().iter().filter(|segment| segment.marker() == JpegSegment::COM).count();
+        assert_eq!(parts, 3);
+
+        let mut jpeg = Jpeg::try_from(jpeg.as_bytes().as_slice()).unwrap();
+        assert_eq!(jpeg.message(b"ruSt").unwrap(), Some(message));
+        jpeg.remove_message(b"ruSt").unwrap();
+        assert_eq!(jpeg.as_bytes(), testing_jpeg());
+        assert_eq!(jpeg.remove_message(b"ruSt"), Err(JpegError::MessageNotFound("ruSt".to_string())));
+    }
+
+    #[test]
+    fn test_insert_replaces_message() {
+        let mut jpeg = Jpeg::try_from(testing_jpeg().as_slice()).unwrap();
+        jpeg.insert_message_with(b"ruSt", &vec![1; 70_000], Carrier::App).unwrap();
+        jpeg.insert_message_with(b"abcd", b"other", Carrier::App).unwrap();
+        jpeg.insert_message_with(b"ruSt", b"hello", Carrier::Comment).unwrap();
+        assert_eq!(jpeg.message(b"ruSt").unwrap(), Some(b"hello".to_vec()));
+        assert_eq!(jpeg.message(b"abcd").unwrap(), Some(b"other".to_vec()));
+        assert_eq!(jpeg.segments().iter().filter(|segment| Jpeg::is_message_for(segment, b"ruSt")).count(), 1);
+    }
+
+    #[test]
+    fn test_container_carrier() {
+        let mut jpeg = Jpeg::try_from(testing_jpeg().as_slice()).unwrap();
+        jpeg.insert_message_in("ruSt", b"hello", "comment").unwrap();
+        assert_eq!(jpeg.segments()[2].name(), "COM");
+        assert_eq!(
+            jpeg.insert_message_in("ruSt", b"hello", "lsb"),
+            Err(Error::UnsupportedCarrier { format: Format::Jpeg, carrier: "lsb".to_string() })
+        );
+    }
+
+    #[test]
+    fn test_incomplete_message() {
+        let mut jpeg = Jpeg::try_from(testing_jpeg().as_slice()).unwrap();
+        jpeg.insert_message_with(b"ruSt", &vec![0; 70_000], Carrier::App).unwrap();
+        jpeg.segments.remove(2);
+        assert_eq!(jpeg.message(b"ruSt"), Err(JpegError::IncompleteMessage("ruSt".to_string())));
+    }
+}
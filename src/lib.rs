@@ -0,0 +1,280 @@
+//! Hide messages in PNG, JPEG, WebP, GIF, BMP and TIFF files by storing them in their own
+//! chunks or segments.
+//!
+//! `embed`, `extract` and `remove` work on raw file bytes of any supported
+//! [`container::Format`]. For anything finer grained, parse a [`Png`] and
+//! work with its [`Chunk`]s directly.
+//!
+//! ```
+//! # fn main() -> Result<(), rush_png::Error> {
+//! # let image = rush_png::Png::from_chunks(vec![]).as_bytes();
+//! let with_message = rush_png::embed(&image, "ruSt", b"hello")?;
+//! assert_eq!(rush_png::extract(&with_message, "ruSt")?, Some(b"hello".to_vec()));
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod apng;
+pub mod atomic;
+pub mod batch;
+pub mod bmp;
+mod bytes;
+pub mod cgbi;
+pub mod chunk;
+pub mod chunk_type;
+pub mod color;
+pub mod container;
+pub mod diff;
+pub mod exif;
+pub mod gif;
+pub mod icc;
+pub mod inspect;
+pub mod jpeg;
+pub mod palette;
+pub mod physical;
+pub mod pixels;
+pub mod polyglot;
+pub mod png;
+pub mod registry;
+pub mod strip;
+pub mod text;
+pub mod time;
+pub mod tiff;
+pub mod trailer;
+pub mod webp;
+pub mod xmp;
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+pub use crate::apng::{Animation, ApngError};
+pub use crate::bmp::{Bmp, BmpError};
+pub use crate::cgbi::CgbiError;
+pub use crate::chunk::{Chunk, ChunkError};
+pub use crate::chunk_type::{ChunkType, ParseChunkTypeError};
+pub use crate::color::{ColorChunk, ColorError};
+pub use crate::container::{Container, Format};
+pub use crate::exif::{Exif, ExifError};
+pub use crate::gif::{Gif, GifError};
+pub use crate::icc::{IccError, IccProfile};
+pub use crate::jpeg::{Jpeg, JpegError};
+pub use crate::palette::{PaletteError, PaletteInfo};
+pub use crate::physical::{PhysicalError, PixelDimensions};
+pub use crate::polyglot::{Placement, PolyglotError};
+pub use crate::png::{ImageHeader, Png, PngError};
+pub use crate::registry::{ChunkData, DecodeError, Registry};
+pub use crate::text::{InternationalText, TextError};
+pub use crate::tiff::{Tiff, TiffError};
+pub use crate::trailer::TrailerKind;
+pub use crate::time::{TimeError, Timestamp};
+pub use crate::webp::{Webp, WebpError};
+pub use crate::xmp::{Xmp, XmpError, XmpValue};
+
+/// Errors returned by `embed`, `extract` and `remove`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The input does not match the magic bytes of any known format.
+    UnknownFormat,
+    /// The format was recognised but cannot be edited yet.
+    UnsupportedFormat(Format),
+    /// The input is not a readable PNG.
+    Png(PngError),
+    /// The PNG is animated and the animation is not valid.
+    Apng(ApngError),
+    /// The input is not a readable JPEG, or the message is missing.
+    Jpeg(JpegError),
+    /// The input is not a readable WebP, or the message is missing.
+    Webp(WebpError),
+    /// The input is not a readable GIF, or the message is missing.
+    Gif(GifError),
+    /// The input is not an uncompressed BMP, or the message is missing.
+    Bmp(BmpError),
+    /// The input is not a readable TIFF, or the message is missing.
+    Tiff(TiffError),
+    /// The chunk type is not four ASCII letters.
+    ChunkType(ParseChunkTypeError),
+    /// The chunk type has its reserved bit set (lowercase third letter),
+    /// which would make the resulting file invalid.
+    ReservedChunkType(ChunkType),
+    /// The format has no carrier by this name.
+    UnsupportedCarrier { format: Format, carrier: String },
+    /// The chunk type is critical (uppercase first letter), so decoders that
+    /// do not know it would refuse to display the file.
+    CriticalChunkType(ChunkType),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::UnknownFormat => write!(f, "unknown file format"),
+            Error::UnsupportedFormat(format) => write!(f, "{} files are not supported yet", format),
+            Error::Png(e) => write!(f, "{}", e),
+            Error::Apng(e) => write!(f, "{}", e),
+            Error::Jpeg(e) => write!(f, "{}", e),
+            Error::Webp(e) => write!(f, "{}", e),
+            Error::Gif(e) => write!(f, "{}", e),
+            Error::Bmp(e) => write!(f, "{}", e),
+            Error::Tiff(e) => write!(f, "{}", e),
+            Error::ChunkType(e) => write!(f, "{}", e),
+            Error::ReservedChunkType(chunk_type) => {
+                write!(f, "{} has the reserved bit set and would make the png invalid", chunk_type)
+            },
+            Error::UnsupportedCarrier { format, carrier } => {
+                write!(f, "{} files cannot carry a message in {:?}", format, carrier)
+            },
+            Error::CriticalChunkType(chunk_type) => {
+                write!(f, "{} is a critical chunk type and cannot carry a message", chunk_type)
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Png(e) => Some(e),
+            Error::Apng(e) => Some(e),
+            Error::Jpeg(e) => Some(e),
+            Error::Webp(e) => Some(e),
+            Error::Gif(e) => Some(e),
+            Error::Bmp(e) => Some(e),
+            Error::Tiff(e) => Some(e),
+            Error::ChunkType(e) => Some(e),
+            Error::UnknownFormat
+            | Error::UnsupportedFormat(_)
+            | Error::ReservedChunkType(_)
+            | Error::UnsupportedCarrier { .. }
+            | Error::CriticalChunkType(_) => None,
+        }
+    }
+}
+
+impl From<PngError> for Error {
+    fn from(e: PngError) -> Self {
+        Error::Png(e)
+    }
+}
+
+impl From<ApngError> for Error {
+    fn from(e: ApngError) -> Self {
+        Error::Apng(e)
+    }
+}
+
+impl From<JpegError> for Error {
+    fn from(e: JpegError) -> Self {
+        Error::Jpeg(e)
+    }
+}
+
+impl From<WebpError> for Error {
+    fn from(e: WebpError) -> Self {
+        Error::Webp(e)
+    }
+}
+
+impl From<GifError> for Error {
+    fn from(e: GifError) -> Self {
+        Error::Gif(e)
+    }
+}
+
+impl From<BmpError> for Error {
+    fn from(e: BmpError) -> Self {
+        Error::Bmp(e)
+    }
+}
+
+impl From<TiffError> for Error {
+    fn from(e: TiffError) -> Self {
+        Error::Tiff(e)
+    }
+}
+
+impl From<ParseChunkTypeError> for Error {
+    fn from(e: ParseChunkTypeError) -> Self {
+        Error::ChunkType(e)
+    }
+}
+
+/// Parses a chunk type to carry a message, rejecting ones that would make
+/// the file invalid.
+pub fn message_chunk_type(chunk_type: &str) -> Result<ChunkType, Error> {
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+    if !chunk_type.is_valid() {
+        Err(Error::ReservedChunkType(chunk_type))
+    } else if chunk_type.is_critical() {
+        Err(Error::CriticalChunkType(chunk_type))
+    } else {
+        Ok(chunk_type)
+    }
+}
+
+/// Returns a copy of `image` with `message` stored under `key`; for PNG
+/// that is a new `key` chunk just before `IEND`.
+pub fn embed(image: &[u8], key: &str, message: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut container = container::open(image)?;
+    container.insert_message(key, message)?;
+    Ok(container.to_bytes())
+}
+
+/// Reads the message stored under `key`, if there is one.
+pub fn extract(image: &[u8], key: &str) -> Result<Option<Vec<u8>>, Error> {
+    container::open(image)?.extract_message(key)
+}
+
+/// Returns a copy of `image` without the message stored under `key`.
+pub fn remove(image: &[u8], key: &str) -> Result<Vec<u8>, Error> {
+    let mut container = container::open(image)?;
+    container.remove_message(key)?;
+    Ok(container.to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Vec<u8> {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_embed_extract_remove() {
+        let png = embed(&testing_png(), "ruSt", b"hello").unwrap();
+        assert_eq!(extract(&png, "ruSt").unwrap(), Some(b"hello".to_vec()));
+
+        let png = remove(&png, "ruSt").unwrap();
+        assert_eq!(png, testing_png());
+        assert_eq!(extract(&png, "ruSt").unwrap(), None);
+    }
+
+    #[test]
+    fn test_embed_rejects_bad_chunk_types() {
+        assert!(matches!(embed(&testing_png(), "ruSTy", b""), Err(Error::ChunkType(_))));
+        assert!(matches!(embed(&testing_png(), "rust", b""), Err(Error::ReservedChunkType(_))));
+    }
+
+    #[test]
+    fn test_embed_rejects_critical_chunk_types() {
+        for key in ["IDAT", "IEND", "RUST", "RuSt"] {
+            assert!(matches!(embed(&testing_png(), key, b""), Err(Error::CriticalChunkType(_))), "{}", key);
+        }
+        assert_eq!(
+            message_chunk_type("IEND").unwrap_err().to_string(),
+            "IEND is a critical chunk type and cannot carry a message"
+        );
+    }
+
+    #[test]
+    fn test_not_a_png() {
+        assert_eq!(extract(b"BM\0\0", "ruSt"), Err(Error::Bmp(BmpError::Truncated)));
+        assert_eq!(extract(b"GIF89a", "ruSt"), Err(Error::Gif(GifError::Truncated { offset: 6 })));
+        assert_eq!(extract(b"\xFF\xD8\xFF\xE0", "ruSt"), Err(Error::Jpeg(JpegError::Truncated { offset: 2 })));
+        assert_eq!(extract(b"\x89PNG\r\n\x1a", "ruSt"), Err(Error::UnknownFormat));
+        assert_eq!(remove(&testing_png(), "ruSt"), Err(Error::Png(PngError::ChunkNotFound("ruSt".to_string()))));
+    }
+}
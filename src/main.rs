@@ -1,57 +1,651 @@
 mod args;
-mod chunk;
-mod chunk_type;
 mod commands;
-mod png;
 
-use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 use std::str::FromStr;
-use clap::{Args, Command, Parser, Subcommand, ArgMatches};
-use crate::chunk::Chunk;
-use crate::chunk_type::ChunkType;
-use anyhow::{Result, Error};
+use clap::Parser;
+use rush_png::{atomic, batch, cgbi, color, container, inspect, palette, png, polyglot, trailer, Animation, Chunk, ChunkType, ColorChunk, Exif, Format};
+use rush_png::{IccProfile, PaletteInfo, Placement, PixelDimensions, Timestamp, TrailerKind, Xmp, XmpValue};
+use rush_png::atomic::WriteOptions;
+use rush_png::exif::{self, IfdKind};
+use rush_png::strip::{ChunkPattern, StripFilter};
+use anyhow::{bail, Result};
 use std::fs;
+use std::path::{Path, PathBuf};
 // pub type Error = Box<dyn std::error::Error>;
 // pub type Result<T> = std::result::Result<T, Error>;
 
 
+fn write_options(output: &args::Output) -> WriteOptions {
+    WriteOptions { output: output.output.clone(), backup: output.backup.clone() }
+}
+
+/// `--output` names one file, so it cannot go with several inputs.
+fn check_single_output(inputs: &[PathBuf], output: &args::Output) -> Result<()> {
+    if output.output.is_some() && !matches!(inputs, [path] if path.is_file()) {
+        bail!("--output only works with a single input file");
+    }
+    Ok(())
+}
+
+fn dropped_lines(dropped: &[String]) -> Vec<String> {
+    dropped
+        .iter()
+        .map(|name| format!("dropped {}: unsafe-to-copy after critical chunks changed", name))
+        .collect()
+}
+
+fn print_dropped(dropped: &[Chunk]) {
+    let names: Vec<String> = dropped.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+    for line in dropped_lines(&names) {
+        println!("{}", line);
+    }
+}
+
+fn encode_file(path: &Path, encode: &args::Encode) -> Result<String> {
+    let data = fs::read(path)?;
+    let mut image = container::open(data.as_slice())?;
+    let dropped = match &encode.carrier {
+        Some(carrier) => image.insert_message_in(&encode.chunk_type, encode.message.as_bytes(), carrier)?,
+        None => image.insert_message(&encode.chunk_type, encode.message.as_bytes())?,
+    };
+    let mut bytes = image.to_bytes();
+    if encode.touch {
+        if image.format() != Format::Png {
+            bail!("--touch only works on png files");
+        }
+        let mut p = png::Png::try_from(bytes.as_slice())?;
+        p.set_chunk(Timestamp::now().to_chunk());
+        bytes = p.as_bytes();
+    }
+    atomic::write(path, &bytes, &write_options(&encode.output))?;
+    let mut lines = dropped_lines(&dropped);
+    lines.push("your message injected!".to_string());
+    Ok(lines.join("\n"))
+}
+
+fn decode_file(path: &Path, decode: &args::Decode) -> Result<String> {
+    let data = fs::read(path)?;
+    let image = container::open(data.as_slice())?;
+    let mut out = match image.extract_message(&decode.chunk_type)? {
+        Some(msg) => format!("message: {}", String::from_utf8(msg)?),
+        None => "nothing found".to_string(),
+    };
+    let extensions = image.metadata_segments();
+    if !extensions.is_empty() {
+        out.push_str(&format!("\n\n{} extension blocks:\n", image.format()));
+        out.push_str(inspect::render_segments(&extensions).trim_end());
+    }
+    Ok(out)
+}
+
+fn remove_file(path: &Path, remove: &args::Remove) -> Result<String> {
+    let data = fs::read(path)?;
+    let mut image = container::open(data.as_slice())?;
+    let dropped = image.remove_message(&remove.chunk_type)?;
+    atomic::write(path, &image.to_bytes(), &write_options(&remove.output))?;
+    let mut lines = dropped_lines(&dropped);
+    lines.push("your message removed!".to_string());
+    Ok(lines.join("\n"))
+}
+
+/// Runs `task` on every file the inputs expand to. A single plain file
+/// runs as before; anything else prints one result per file, as files
+/// finish, and a summary.
+fn run_batch<F>(inputs: &[PathBuf], options: &args::Batch, task: F) -> Result<()>
+where
+    F: Fn(&Path) -> Result<String> + Sync,
+{
+    if let [path] = inputs {
+        if path.is_file() {
+            println!("{}", task(path)?);
+            return Ok(());
+        }
+    }
+    let paths = batch::expand(inputs, options.recursive)?;
+    let jobs = options.jobs.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+    let outcomes = batch::run(&paths, jobs, options.continue_on_error, task, |path, result| match result {
+        Ok(out) => println!("{}: {}", path.display(), out.replace('\n', "\n  ")),
+        Err(e) => eprintln!("{}: error: {:#}", path.display(), e),
+    });
+    let failed = outcomes.iter().filter(|outcome| matches!(outcome.result, Some(Err(_)))).count();
+    let skipped = outcomes.iter().filter(|outcome| outcome.result.is_none()).count();
+    let mut summary = format!("{} files: {} ok, {} failed", paths.len(), paths.len() - failed - skipped, failed);
+    if skipped > 0 {
+        summary.push_str(&format!(", {} skipped", skipped));
+    }
+    println!("{}", summary);
+    if failed > 0 {
+        bail!("{} of {} files failed", failed, paths.len());
+    }
+    Ok(())
+}
+
+fn read_animation(path: &std::path::Path) -> Result<(png::Png, Animation)> {
+    let data = fs::read(path)?;
+    let p = png::Png::try_from(data.as_slice())?;
+    let animation = Animation::from_png(&p)?;
+    Ok((p, animation))
+}
+
+fn run_apng(command: &commands::ApngCommands) -> Result<()> {
+    match command {
+        commands::ApngCommands::List(list) => {
+            let (_, animation) = read_animation(&list.file_path)?;
+            println!("{}", animation.control);
+            println!("{:>5}  {:<9}  {:<9}  {:>8}  {:<10}  blend", "frame", "size", "offset", "delay", "dispose");
+            for (idx, frame) in animation.frames.iter().enumerate() {
+                let fc = &frame.control;
+                let line = format!("{:>5}  {:<9}  {:<9}  {:>7.3}s  {:<10}  {:<6}  {}",
+                                   idx,
+                                   format!("{}x{}", fc.width, fc.height),
+                                   format!("{},{}", fc.x_offset, fc.y_offset),
+                                   fc.delay(), fc.dispose_op, fc.blend_op,
+                                   if frame.is_default_image { "default image" } else { "" });
+                println!("{}", line.trim_end());
+            }
+        },
+        commands::ApngCommands::Extract(extract) => {
+            let (p, animation) = read_animation(&extract.file_path)?;
+            let frame = animation.extract_frame(&p, extract.frame)?;
+            fs::write(&extract.output, frame.as_bytes())?;
+            println!("frame {} written to {}", extract.frame, extract.output.display())
+        },
+        commands::ApngCommands::Encode(encode) => {
+            let (mut p, animation) = read_animation(&encode.file_path)?;
+            let chunk_type = rush_png::message_chunk_type(&encode.chunk_type)?;
+            animation.insert_frame_chunk(&mut p, encode.frame, Chunk::new(chunk_type, encode.message.as_bytes().to_vec()))?;
+            atomic::replace(&encode.file_path, &p.as_bytes())?;
+            println!("your message injected into frame {}!", encode.frame)
+        },
+        commands::ApngCommands::Decode(decode) => {
+            let (p, animation) = read_animation(&decode.file_path)?;
+            let chunk_type = rush_png::message_chunk_type(&decode.chunk_type)?;
+            match animation.frame_chunk(&p, decode.frame, &chunk_type)? {
+                Some(chunk) => println!("message: {}", chunk.data_as_string()?),
+                None => println!("nothing found"),
+            }
+        },
+        commands::ApngCommands::Build(build) => {
+            let mut frames = vec![];
+            for frame in &build.frames {
+                let (path, delay) = match frame.rsplit_once(':') {
+                    Some((path, delay)) if delay.parse::<u16>().is_ok() => (path, delay.parse()?),
+                    _ => (frame.as_str(), build.delay),
+                };
+                let data = fs::read(path)?;
+                frames.push((png::Png::try_from(data.as_slice())?, delay));
+            }
+            let frames: Vec<(&png::Png, u16)> = frames.iter().map(|(p, delay)| (p, *delay)).collect();
+            let p = Animation::build(&frames, build.plays)?;
+            fs::write(&build.output, p.as_bytes())?;
+            println!("animation with {} frames written to {}", frames.len(), build.output.display())
+        },
+    }
+    Ok(())
+}
+
+/// Resolves a tag name or number to its directory, number and the field
+/// type to use for new values.
+fn resolve_exif_tag(tag: &str, ifd: &Option<String>) -> Result<(IfdKind, u16, Option<u16>)> {
+    let ifd = ifd.as_deref().map(IfdKind::from_str).transpose()?;
+    if let Some((kind, number, field_type)) = exif::find_tag(tag) {
+        return Ok((ifd.unwrap_or(kind), number, Some(field_type)));
+    }
+    let number = match tag.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => tag.parse(),
+    };
+    match number {
+        Ok(number) => Ok((ifd.unwrap_or(IfdKind::Ifd0), number, None)),
+        Err(_) => bail!(exif::ExifError::UnknownTag(tag.to_string())),
+    }
+}
+
+fn read_exif(path: &std::path::Path) -> Result<(png::Png, Option<Exif>)> {
+    let data = fs::read(path)?;
+    let p = png::Png::try_from(data.as_slice())?;
+    let exif = p.chunk_by_type("eXIf").map(|chunk| Exif::try_from(chunk.data())).transpose()?;
+    Ok((p, exif))
+}
+
+/// Stores `exif` in `eXIf`, which `set_chunk` keeps before `IDAT`.
+fn write_exif(path: &std::path::Path, mut p: png::Png, exif: &Exif) -> Result<()> {
+    p.set_chunk(Chunk::new(ChunkType::from_str("eXIf")?, exif.as_bytes()));
+    atomic::replace(path, &p.as_bytes())?;
+    Ok(())
+}
+
+fn run_exif(command: &commands::ExifCommands) -> Result<()> {
+    match command {
+        commands::ExifCommands::Show(show) => {
+            let Some(exif) = read_exif(&show.file_path)?.1 else {
+                println!("no exif found");
+                return Ok(());
+            };
+            if exif.ifds().iter().all(|(_, entries)| entries.is_empty()) && exif.thumbnail().is_none() {
+                println!("no exif tags");
+            }
+            for (kind, entries) in exif.ifds() {
+                for entry in entries {
+                    println!("{:<8} {:<20} {}", kind, exif::tag_name(*kind, entry.tag), exif.display_value(entry));
+                }
+            }
+            if let Some(thumbnail) = exif.thumbnail() {
+                println!("{:<8} {:<20} {} bytes", IfdKind::Ifd1, "thumbnail", thumbnail.len());
+            }
+            if let Some((lat, lon)) = exif.gps_location() {
+                println!("\nlocation: {:.6}, {:.6}", lat, lon);
+            }
+        },
+        commands::ExifCommands::Set(set) => {
+            let (p, exif) = read_exif(&set.file_path)?;
+            let mut exif = exif.unwrap_or_else(|| Exif::new(false));
+            let (kind, tag, known_type) = resolve_exif_tag(&set.tag, &set.ifd)?;
+            let field_type = exif.get(kind, tag).map(|entry| entry.field_type).or(known_type).unwrap_or(2);
+            let entry = exif.parse_value(tag, field_type, &set.value)?;
+            exif.set(kind, entry);
+            write_exif(&set.file_path, p, &exif)?;
+            println!("{} set in {}", exif::tag_name(kind, tag), kind)
+        },
+        commands::ExifCommands::Remove(remove) => {
+            let (p, exif) = read_exif(&remove.file_path)?;
+            let Some(mut exif) = exif else { bail!("no exif found") };
+            let (kind, tag, _) = resolve_exif_tag(&remove.tag, &remove.ifd)?;
+            exif.remove(kind, tag)?;
+            write_exif(&remove.file_path, p, &exif)?;
+            println!("{} removed from {}", exif::tag_name(kind, tag), kind)
+        },
+        commands::ExifCommands::StripGps(strip) => {
+            let (p, exif) = read_exif(&strip.file_path)?;
+            let Some(mut exif) = exif else { bail!("no exif found") };
+            if exif.remove_ifd(IfdKind::Gps) {
+                write_exif(&strip.file_path, p, &exif)?;
+                println!("gps data removed")
+            } else {
+                println!("no gps data found")
+            }
+        },
+    }
+    Ok(())
+}
+
+fn read_xmp(path: &std::path::Path) -> Result<(png::Png, Option<Xmp>)> {
+    let data = fs::read(path)?;
+    let p = png::Png::try_from(data.as_slice())?;
+    let xmp = Xmp::from_png(&p)?;
+    Ok((p, xmp))
+}
+
+fn run_xmp(command: &commands::XmpCommands) -> Result<()> {
+    match command {
+        commands::XmpCommands::Show(show) => {
+            let Some(xmp) = read_xmp(&show.file_path)?.1 else {
+                println!("no xmp found");
+                return Ok(());
+            };
+            if xmp.properties().is_empty() {
+                println!("no xmp properties");
+            }
+            for (name, value) in xmp.properties() {
+                println!("{:<24} {}", name, value);
+            }
+        },
+        commands::XmpCommands::Set(set) => {
+            let (mut p, xmp) = read_xmp(&set.file_path)?;
+            let mut xmp = xmp.unwrap_or_default();
+            for namespace in &set.namespace {
+                let Some((prefix, uri)) = namespace.split_once('=') else {
+                    bail!("namespace {:?} must look like prefix=uri", namespace)
+                };
+                xmp.declare_namespace(prefix, uri);
+            }
+            xmp.set(&set.property, XmpValue::for_property(&set.property, &set.values))?;
+            xmp.write_to_png(&mut p);
+            atomic::replace(&set.file_path, &p.as_bytes())?;
+            println!("{} set", set.property)
+        },
+        commands::XmpCommands::Remove(remove) => {
+            let (mut p, xmp) = read_xmp(&remove.file_path)?;
+            let Some(mut xmp) = xmp else { bail!("no xmp found") };
+            if xmp.remove(&remove.property).is_none() {
+                bail!("xmp property {} not found", remove.property)
+            }
+            xmp.write_to_png(&mut p);
+            atomic::replace(&remove.file_path, &p.as_bytes())?;
+            println!("{} removed", remove.property)
+        },
+    }
+    Ok(())
+}
+
+fn run_icc(command: &commands::IccCommands) -> Result<()> {
+    match command {
+        commands::IccCommands::Extract(extract) => {
+            let data = fs::read(&extract.file_path)?;
+            let p = png::Png::try_from(data.as_slice())?;
+            let Some(icc) = IccProfile::from_png(&p)? else { bail!("no icc profile found") };
+            if p.chunk_by_type("sRGB").is_some() {
+                eprintln!("warning: png has both iCCP and sRGB");
+            }
+            fs::write(&extract.output, &icc.profile)?;
+            match icc.header() {
+                Ok(header) => println!("{}: {}", icc.name, header),
+                Err(e) => println!("{}: {}", icc.name, e),
+            }
+        },
+        commands::IccCommands::Embed(embed) => {
+            let profile = fs::read(&embed.profile)?;
+            let name = match &embed.name {
+                Some(name) => name.clone(),
+                None => embed.profile.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default(),
+            };
+            let icc = IccProfile::new(&name, profile)?;
+            let header = icc.header()?;
+            let data = fs::read(&embed.file_path)?;
+            let mut p = png::Png::try_from(data.as_slice())?;
+            icc.write_to_png(&mut p, embed.replace_srgb)?;
+            atomic::replace(&embed.file_path, &p.as_bytes())?;
+            println!("{}: {} embedded", icc.name, header)
+        },
+    }
+    Ok(())
+}
+
+fn run_color(command: &commands::ColorCommands) -> Result<()> {
+    match command {
+        commands::ColorCommands::Show(show) => {
+            let data = fs::read(&show.file_path)?;
+            let p = png::Png::try_from(data.as_slice())?;
+            let found = color::color_chunks(&p);
+            if found.is_empty() {
+                println!("no color chunks");
+            }
+            for (chunk_type, color) in found {
+                match color {
+                    Ok(color) => println!("{}  {}", chunk_type, color),
+                    Err(e) => println!("{}  invalid: {}", chunk_type, e),
+                }
+            }
+        },
+        commands::ColorCommands::Set(set) => {
+            let color = ColorChunk::parse(&set.chunk_type, &set.value)?;
+            let data = fs::read(&set.file_path)?;
+            let mut p = png::Png::try_from(data.as_slice())?;
+            color.write_to_png(&mut p)?;
+            atomic::replace(&set.file_path, &p.as_bytes())?;
+            println!("{} set to {}", color.chunk_type(), color)
+        },
+        commands::ColorCommands::Clear(clear) => {
+            for chunk_type in &clear.chunk_types {
+                if !color::COLOR_CHUNKS.contains(&chunk_type.as_str()) {
+                    bail!(color::ColorError::UnknownChunk(chunk_type.clone()));
+                }
+            }
+            let data = fs::read(&clear.file_path)?;
+            let mut p = png::Png::try_from(data.as_slice())?;
+            let removed = p.strip_chunks(|chunk| {
+                let chunk_type = chunk.chunk_type().to_string();
+                match clear.chunk_types.is_empty() {
+                    true => color::COLOR_CHUNKS.contains(&chunk_type.as_str()),
+                    false => clear.chunk_types.contains(&chunk_type),
+                }
+            });
+            for chunk in &removed {
+                println!("removed {}", chunk.chunk_type());
+            }
+            if removed.is_empty() {
+                println!("no color chunks removed");
+            } else {
+                atomic::replace(&clear.file_path, &p.as_bytes())?;
+            }
+        },
+    }
+    Ok(())
+}
+
+fn read_palette(path: &std::path::Path) -> Result<(png::Png, PaletteInfo)> {
+    let data = fs::read(path)?;
+    let p = png::Png::try_from(data.as_slice())?;
+    let info = PaletteInfo::from_png(&p)?;
+    Ok((p, info))
+}
+
+fn run_palette(command: &commands::PaletteCommands) -> Result<()> {
+    match command {
+        commands::PaletteCommands::Show(show) => {
+            let (_, info) = read_palette(&show.file_path)?;
+            match &info.palette {
+                Some(entries) => {
+                    let alpha = info.alpha();
+                    println!("{:>5}  {:<9}  {:>5}  {:>5}", "index", "color", "alpha", "count");
+                    for (index, [r, g, b]) in entries.0.iter().enumerate() {
+                        let count = info.histogram.as_ref().map(|h| h.0[index].to_string()).unwrap_or_default();
+                        println!("{:>5}  #{:02x}{:02x}{:02x}    {:>5}  {:>5}", index, r, g, b, alpha[index], count);
+                    }
+                },
+                None => println!("no palette"),
+            }
+            if let Some(transparency) = info.transparency.as_ref().filter(|_| !info.header.is_indexed()) {
+                println!("tRNS  {}", transparency);
+            }
+            if let Some(background) = &info.background {
+                println!("bKGD  {}", background);
+            }
+            for suggested in &info.suggested {
+                println!("sPLT  {}", suggested);
+            }
+        },
+        commands::PaletteCommands::Swatch(swatch) => {
+            let (_, info) = read_palette(&swatch.file_path)?;
+            let Some(entries) = &info.palette else { bail!(palette::PaletteError::MissingPalette) };
+            if !(1..=palette::MAX_SWATCH_SIZE).contains(&swatch.size) {
+                bail!("swatch size must be between 1 and {}", palette::MAX_SWATCH_SIZE);
+            }
+            fs::write(&swatch.output, entries.swatch(&info.alpha(), swatch.size).as_bytes())?;
+            println!("{} colors written to {}", entries.0.len(), swatch.output.display())
+        },
+        commands::PaletteCommands::Set(set) => {
+            let (mut p, mut info) = read_palette(&set.file_path)?;
+            let (rgb, alpha) = palette::parse_color(&set.color)?;
+            info.set_entry(set.index, rgb, alpha)?;
+            let dropped = info.write_to_png(&mut p);
+            atomic::replace(&set.file_path, &p.as_bytes())?;
+            print_dropped(&dropped);
+            println!("entry {} set to {}", set.index, set.color)
+        },
+    }
+    Ok(())
+}
+
+fn run_polyglot(command: &commands::PolyglotCommands) -> Result<()> {
+    match command {
+        commands::PolyglotCommands::Build(build) => {
+            let placement = Placement::from_str(&build.placement).map_err(anyhow::Error::msg)?;
+            let data = fs::read(&build.file_path)?;
+            let p = png::Png::try_from(data.as_slice())?;
+            let zip = fs::read(&build.zip)?;
+            let built = polyglot::build(&p, &zip, placement)?;
+            fs::write(&build.output, built.as_bytes())?;
+            let verification = polyglot::verify(&built.as_bytes())?;
+            println!("{} chunks and {} zip entries written to {}",
+                     verification.chunks, verification.entries.len(), build.output.display())
+        },
+        commands::PolyglotCommands::Verify(verify) => {
+            let data = fs::read(&verify.file_path)?;
+            let verification = polyglot::verify(&data)?;
+            println!("png: {} chunks, crcs ok", verification.chunks);
+            println!("zip: {} entries", verification.entries.len());
+            for entry in &verification.entries {
+                println!("  {:>10}  {:>10}  {}", entry.size, entry.local_offset, entry.name);
+            }
+        },
+    }
+    Ok(())
+}
+
 // TODO: refactor
 fn main() -> Result<()> {
     let cli = commands::Cli::parse();
     match &cli.command {
         commands::Commands::Encode(encode) => {
-            // file input
-            let path = &encode.file_path;
+            let mut paths = vec![encode.file_path.clone()];
+            paths.extend(encode.more_paths.iter().cloned());
+            check_single_output(&paths, &encode.output)?;
+            run_batch(&paths, &encode.batch, |path| encode_file(path, encode))?
+        },
+        commands::Commands::Decode(decode) => run_batch(&decode.file_paths, &decode.batch, |path| decode_file(path, decode))?,
+        commands::Commands::Remove(remove) => {
+            check_single_output(&remove.file_paths, &remove.output)?;
+            run_batch(&remove.file_paths, &remove.batch, |path| remove_file(path, remove))?
+        },
+        commands::Commands::Print(print) => {
+            let path = &print.file_path;
             let data = fs::read(path)?;
+            if Format::detect(&data) != Some(Format::Png) {
+                if !print.hex.is_empty() {
+                    bail!("--hex is only supported for png files");
+                }
+                let segments = container::open(data.as_slice())?.segments();
+                if print.json {
+                    println!("{}", inspect::render_segments_json(&segments));
+                } else {
+                    print!("{}", inspect::render_segments(&segments));
+                }
+                return Ok(());
+            }
+            let p = png::Png::from_bytes_unchecked(data.as_slice())?;
+            let patterns = print.hex.iter().map(|s| ChunkPattern::from_str(s)).collect::<Result<Vec<_>, _>>()?;
+            let selected: Vec<Option<&[u8]>> = p
+                .chunks()
+                .iter()
+                .map(|chunk| {
+                    patterns.iter().any(|pattern| pattern.matches(chunk.chunk_type())).then(|| chunk.data())
+                })
+                .collect();
+            let rows = inspect::inspect(&p);
+            if print.json {
+                println!("{}", inspect::render_json(&rows, &selected, p.trailer(), p.trailer_offset()));
+            } else {
+                print!("{}", inspect::render_table(&rows));
+                if !p.trailer().is_empty() {
+                    println!("{}", trailer::describe(p.trailer(), p.trailer_offset()));
+                }
+                for (row, data) in rows.iter().zip(selected.iter()) {
+                    if let Some(data) = data {
+                        println!("\n{} at offset {}:", row.chunk_type, row.offset);
+                        print!("{}", inspect::hex_dump(data));
+                    }
+                }
+            }
+        },
+        #[cfg(feature = "serde")]
+        commands::Commands::Dump(dump) => {
+            let data = fs::read(&dump.file_path)?;
+            let p = png::Png::try_from(data.as_slice())?;
+            match dump.format.as_str() {
+                "json" => println!("{}", serde_json::to_string_pretty(&p)?),
+                "yaml" => print!("{}", serde_norway::to_string(&p)?),
+                other => bail!("unknown format {:?}, expected json or yaml", other),
+            }
+        },
+        #[cfg(feature = "serde")]
+        commands::Commands::Build(build) => {
+            let description = fs::read_to_string(&build.description)?;
+            let p: png::Png = match build.format.as_str() {
+                "json" => serde_json::from_str(&description)?,
+                "yaml" => serde_norway::from_str(&description)?,
+                other => bail!("unknown format {:?}, expected json or yaml", other),
+            };
+            fs::write(&build.output, p.as_bytes())?;
+            println!("png built from {} chunks", p.chunks().len())
+        },
+        commands::Commands::SetDpi(set_dpi) => {
+            let dims = PixelDimensions::from_dpi(set_dpi.dpi, set_dpi.vertical.unwrap_or(set_dpi.dpi))?;
+            let data = fs::read(&set_dpi.file_path)?;
             let mut p = png::Png::try_from(data.as_slice())?;
-            p.append_chunk(Chunk::new(ChunkType::from_str(encode.chunk_type.as_str())?, encode.message.clone().into_bytes()));
-            fs::write(path, p.as_bytes())?;
-            println!("your message injected!")
+            p.set_chunk(dims.to_chunk());
+            atomic::replace(&set_dpi.file_path, &p.as_bytes())?;
+            println!("resolution set to {}", dims)
         },
-        commands::Commands::Decode(decode) => {
-            let path = &decode.file_path;
-            let data = fs::read(path)?;
+        commands::Commands::Touch(touch) => {
+            let time = match &touch.time {
+                Some(time) => Timestamp::from_str(time)?,
+                None => Timestamp::now(),
+            };
+            let data = fs::read(&touch.file_path)?;
             let mut p = png::Png::try_from(data.as_slice())?;
-            if let Some(msg) = p.chunk_by_type(&decode.chunk_type) {
-                println!("message: {}", String::from_utf8(Vec::from(msg.data()))?)
+            p.set_chunk(time.to_chunk());
+            atomic::replace(&touch.file_path, &p.as_bytes())?;
+            println!("modification time set to {}", time)
+        },
+        commands::Commands::Diff(diff) => {
+            let before = png::Png::from_bytes_unchecked(fs::read(&diff.before)?.as_slice())?;
+            let after = png::Png::from_bytes_unchecked(fs::read(&diff.after)?.as_slice())?;
+            let changes = rush_png::diff::diff(&before, &after);
+            if changes.is_empty() {
+                println!("no differences");
+            }
+            for change in &changes {
+                println!("{}", change);
+            }
+        },
+        commands::Commands::Normalize(normalize) => {
+            let data = fs::read(&normalize.file_path)?;
+            let p = png::Png::try_from(data.as_slice())?;
+            if normalize.to_cgbi {
+                let (converted, dropped) = cgbi::to_cgbi(&p)?;
+                atomic::replace(&normalize.file_path, &converted.as_bytes())?;
+                print_dropped(&dropped);
+                println!("converted to apple cgbi");
+            } else if cgbi::is_cgbi(&p) {
+                let (converted, dropped) = cgbi::normalize(&p)?;
+                atomic::replace(&normalize.file_path, &converted.as_bytes())?;
+                print_dropped(&dropped);
+                println!("converted to a standard png");
             } else {
-                println!("nothing found")
+                println!("already a standard png");
             }
         },
-        commands::Commands::Remove(remove) => {
-            let path = &remove.file_path;
-            let data = fs::read(path)?;
-            let mut p = png::Png::try_from(data.as_slice())?;
-            p.remove_chunk(&remove.chunk_type)?;
-            fs::write(path, p.as_bytes())?;
-            println!("your message removed!")
+        commands::Commands::ExtractTrailer(extract) => {
+            let data = fs::read(&extract.file_path)?;
+            let p = png::Png::from_bytes_unchecked(data.as_slice())?;
+            if p.trailer().is_empty() {
+                bail!("no data after IEND");
+            }
+            fs::write(&extract.output, p.trailer())?;
+            println!("wrote {} bytes to {}", p.trailer().len(), extract.output.display());
+            if extract.detect {
+                match TrailerKind::detect(p.trailer()) {
+                    Some(kind) => println!("looks like a {} (.{})", kind, kind.extension()),
+                    None => println!("unknown format"),
+                }
+            }
         },
-        commands::Commands::Print(print) => {
-            let path = &print.file_path;
+        commands::Commands::Apng(apng) => run_apng(apng)?,
+        commands::Commands::Exif(exif) => run_exif(exif)?,
+        commands::Commands::Xmp(xmp) => run_xmp(xmp)?,
+        commands::Commands::Icc(icc) => run_icc(icc)?,
+        commands::Commands::Color(color) => run_color(color)?,
+        commands::Commands::Palette(palette) => run_palette(palette)?,
+        commands::Commands::Polyglot(polyglot) => run_polyglot(polyglot)?,
+        commands::Commands::Strip(strip) => {
+            let path = &strip.file_path;
+            let keep = strip.keep.iter().map(|s| ChunkPattern::from_str(s)).collect::<Result<Vec<_>, _>>()?;
+            let drop = strip.drop.iter().map(|s| ChunkPattern::from_str(s)).collect::<Result<Vec<_>, _>>()?;
+            let filter = StripFilter::new(keep, drop);
             let data = fs::read(path)?;
-            let p = png::Png::try_from(data.as_slice())?;
-            println!("{:?}", p.as_bytes().as_slice())
+            let mut p = png::Png::try_from(data.as_slice())?;
+            let removed = p.strip_chunks(|chunk| filter.should_strip(chunk.chunk_type()));
+            for chunk in &removed {
+                println!("{} {}\t{} bytes\tcrc {:08x}",
+                         if strip.dry_run { "would remove" } else { "removed" },
+                         chunk.chunk_type(), chunk.length(), chunk.crc());
+            }
+            if removed.is_empty() {
+                println!("nothing to strip")
+            } else if !strip.dry_run {
+                atomic::replace(path, &p.as_bytes())?;
+            }
         },
     }
     Ok(())
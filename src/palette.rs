@@ -0,0 +1,577 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::bytes::{be_u16, invalid_length, InvalidLength};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::{ImageHeader, Png};
+use crate::registry::{ChunkData, DecodeError};
+use crate::text::deflate;
+
+/// Why a palette chunk is invalid for its image, or an edit could not be
+/// made.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PaletteError {
+    /// `IHDR` is missing or invalid, so nothing can be checked against it.
+    InvalidHeader,
+    /// The image is indexed but has no `PLTE`, or `hIST` has no `PLTE` to
+    /// count.
+    MissingPalette,
+    /// The chunk data has the wrong size for the image.
+    InvalidLength { chunk_type: String, length: usize },
+    /// A palette may hold at most 2^bit depth entries, and never more than
+    /// 256.
+    TooManyEntries { entries: usize, max: usize },
+    /// The chunk may not appear in an image of this color type.
+    NotAllowed { chunk_type: String, color_type: u8 },
+    /// A palette index or sample is out of range.
+    InvalidValue(String),
+}
+
+impl Display for PaletteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PaletteError::InvalidHeader => write!(f, "missing or invalid IHDR chunk"),
+            PaletteError::MissingPalette => write!(f, "no PLTE chunk found"),
+            PaletteError::InvalidLength { chunk_type, length } => write!(f, "{} chunk has invalid length {}", chunk_type, length),
+            PaletteError::TooManyEntries { entries, max } => {
+                write!(f, "palette has {} entries but the image allows at most {}", entries, max)
+            },
+            PaletteError::NotAllowed { chunk_type, color_type } => {
+                write!(f, "{} is not allowed in images of color type {}", chunk_type, color_type)
+            },
+            PaletteError::InvalidValue(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
+
+impl InvalidLength for PaletteError {
+    fn invalid_length(chunk_type: &str, length: usize) -> Self {
+        PaletteError::InvalidLength { chunk_type: chunk_type.to_string(), length }
+    }
+}
+
+/// Reads 16-bit samples, checking they fit the image's bit depth.
+fn samples(data: &[u8], header: &ImageHeader, chunk_type: &str) -> Result<Vec<u16>, PaletteError> {
+    let samples: Vec<u16> = data.chunks_exact(2).map(|pair| be_u16(pair, 0)).collect();
+    if header.bit_depth < 16 && samples.iter().any(|&s| s >> header.bit_depth != 0) {
+        return Err(PaletteError::InvalidValue(format!("{} sample exceeds bit depth {}", chunk_type, header.bit_depth)));
+    }
+    Ok(samples)
+}
+
+/// Parses `#rrggbb` or `#rrggbbaa`, with or without the `#`.
+pub fn parse_color(s: &str) -> Result<([u8; 3], Option<u8>), PaletteError> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    let byte = |i: usize| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok());
+    let error = || PaletteError::InvalidValue(format!("color {:?} must look like #rrggbb or #rrggbbaa", s));
+    let rgb = [byte(0).ok_or_else(error)?, byte(2).ok_or_else(error)?, byte(4).ok_or_else(error)?];
+    match hex.len() {
+        6 => Ok((rgb, None)),
+        8 => Ok((rgb, Some(byte(6).ok_or_else(error)?))),
+        _ => Err(error()),
+    }
+}
+
+/// The largest square side `Palette::swatch` accepts; 256 entries then
+/// make a 4096×4096 image.
+pub const MAX_SWATCH_SIZE: u32 = 256;
+
+/// `PLTE`: up to 256 RGB entries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette(pub Vec<[u8; 3]>);
+
+impl Palette {
+    /// The most entries `header` allows.
+    pub fn max_entries(header: &ImageHeader) -> usize {
+        if header.is_indexed() {
+            1 << header.bit_depth
+        } else {
+            256
+        }
+    }
+
+    pub fn decode(data: &[u8], header: &ImageHeader) -> Result<Palette, PaletteError> {
+        if header.color_type == 0 || header.color_type == 4 {
+            return Err(PaletteError::NotAllowed { chunk_type: "PLTE".to_string(), color_type: header.color_type });
+        }
+        if data.is_empty() || !data.len().is_multiple_of(3) {
+            return Err(invalid_length("PLTE", data.len()));
+        }
+        let palette = Palette(data.chunks_exact(3).map(|rgb| [rgb[0], rgb[1], rgb[2]]).collect());
+        palette.check(header)?;
+        Ok(palette)
+    }
+
+    fn check(&self, header: &ImageHeader) -> Result<(), PaletteError> {
+        let max = Palette::max_entries(header);
+        if self.0.len() > max {
+            return Err(PaletteError::TooManyEntries { entries: self.0.len(), max });
+        }
+        Ok(())
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::from_str("PLTE").unwrap(), self.0.concat())
+    }
+
+    /// An RGBA image with one `size`×`size` square per entry, 16 to a row.
+    /// `size` must be between 1 and [`MAX_SWATCH_SIZE`].
+    pub fn swatch(&self, alpha: &[u8], size: u32) -> Png {
+        let columns = self.0.len().clamp(1, 16) as u32;
+        let rows = (self.0.len() as u32).div_ceil(16).max(1);
+        let (width, height) = (columns * size, rows * size);
+        let mut raw = Vec::with_capacity(((width * 4 + 1) * height) as usize);
+        for y in 0..height {
+            raw.push(0);
+            for x in 0..width {
+                let index = ((y / size) * 16 + x / size) as usize;
+                match self.0.get(index) {
+                    Some(rgb) => {
+                        raw.extend_from_slice(rgb);
+                        raw.push(alpha.get(index).copied().unwrap_or(255));
+                    },
+                    None => raw.extend_from_slice(&[0, 0, 0, 0]),
+                }
+            }
+        }
+        let header = ImageHeader { width, height, bit_depth: 8, color_type: 6, interlaced: false };
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), header.to_bytes()),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), deflate(&raw)),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ])
+    }
+}
+
+/// `tRNS`: a transparent color, or alpha per palette entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Transparency {
+    Gray(u16),
+    Rgb([u16; 3]),
+    /// Alpha for the first entries of the palette; the rest are opaque.
+    Alpha(Vec<u8>),
+}
+
+impl Transparency {
+    pub fn decode(data: &[u8], header: &ImageHeader, palette: Option<&Palette>) -> Result<Transparency, PaletteError> {
+        match header.color_type {
+            0 if data.len() == 2 => Ok(Transparency::Gray(samples(data, header, "tRNS")?[0])),
+            2 if data.len() == 6 => {
+                let s = samples(data, header, "tRNS")?;
+                Ok(Transparency::Rgb([s[0], s[1], s[2]]))
+            },
+            3 => {
+                let entries = palette.ok_or(PaletteError::MissingPalette)?.0.len();
+                if data.len() > entries {
+                    return Err(invalid_length("tRNS", data.len()));
+                }
+                Ok(Transparency::Alpha(data.to_vec()))
+            },
+            0 | 2 => Err(invalid_length("tRNS", data.len())),
+            color_type => Err(PaletteError::NotAllowed { chunk_type: "tRNS".to_string(), color_type }),
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = match self {
+            Transparency::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Transparency::Rgb(rgb) => rgb.iter().flat_map(|s| s.to_be_bytes()).collect(),
+            Transparency::Alpha(alpha) => alpha.clone(),
+        };
+        Chunk::new(ChunkType::from_str("tRNS").unwrap(), data)
+    }
+}
+
+impl Display for Transparency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transparency::Gray(gray) => write!(f, "gray {} is transparent", gray),
+            Transparency::Rgb([r, g, b]) => write!(f, "rgb({}, {}, {}) is transparent", r, g, b),
+            Transparency::Alpha(alpha) => write!(f, "alpha for {} entries", alpha.len()),
+        }
+    }
+}
+
+/// `bKGD`: the background color to composite onto.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Background {
+    Gray(u16),
+    Rgb([u16; 3]),
+    Index(u8),
+}
+
+impl Background {
+    pub fn decode(data: &[u8], header: &ImageHeader, palette: Option<&Palette>) -> Result<Background, PaletteError> {
+        match (header.color_type, data.len()) {
+            (0 | 4, 2) => Ok(Background::Gray(samples(data, header, "bKGD")?[0])),
+            (2 | 6, 6) => {
+                let s = samples(data, header, "bKGD")?;
+                Ok(Background::Rgb([s[0], s[1], s[2]]))
+            },
+            (3, 1) => {
+                let entries = palette.ok_or(PaletteError::MissingPalette)?.0.len();
+                if data[0] as usize >= entries {
+                    return Err(PaletteError::InvalidValue(format!("bKGD index {} is outside the palette", data[0])));
+                }
+                Ok(Background::Index(data[0]))
+            },
+            (_, length) => Err(invalid_length("bKGD", length)),
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = match self {
+            Background::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Background::Rgb(rgb) => rgb.iter().flat_map(|s| s.to_be_bytes()).collect(),
+            Background::Index(index) => vec![*index],
+        };
+        Chunk::new(ChunkType::from_str("bKGD").unwrap(), data)
+    }
+}
+
+impl Display for Background {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Background::Gray(gray) => write!(f, "gray {}", gray),
+            Background::Rgb([r, g, b]) => write!(f, "rgb({}, {}, {})", r, g, b),
+            Background::Index(index) => write!(f, "palette entry {}", index),
+        }
+    }
+}
+
+/// `hIST`: how often each palette entry is used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram(pub Vec<u16>);
+
+impl Histogram {
+    pub fn decode(data: &[u8], palette: Option<&Palette>) -> Result<Histogram, PaletteError> {
+        let entries = palette.ok_or(PaletteError::MissingPalette)?.0.len();
+        if data.len() != entries * 2 {
+            return Err(invalid_length("hIST", data.len()));
+        }
+        Ok(Histogram(data.chunks_exact(2).map(|pair| be_u16(pair, 0)).collect()))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::from_str("hIST").unwrap(), self.0.iter().flat_map(|f| f.to_be_bytes()).collect())
+    }
+}
+
+/// One entry of a suggested palette; samples are 8 or 16 bits wide.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SuggestedEntry {
+    pub rgba: [u16; 4],
+    pub frequency: u16,
+}
+
+/// `sPLT`: a named palette suggested for displays with few colors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SuggestedPalette {
+    pub name: String,
+    pub depth: u8,
+    pub entries: Vec<SuggestedEntry>,
+}
+
+impl TryFrom<&[u8]> for SuggestedPalette {
+    type Error = PaletteError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let sep = data.iter().position(|&b| b == 0).ok_or_else(|| invalid_length("sPLT", data.len()))?;
+        if sep == 0 || sep > 79 {
+            return Err(PaletteError::InvalidValue("sPLT name must be 1 to 79 characters".to_string()));
+        }
+        let depth = *data.get(sep + 1).ok_or_else(|| invalid_length("sPLT", data.len()))?;
+        let entries = &data[sep + 2..];
+        let size = match depth {
+            8 => 6,
+            16 => 10,
+            _ => return Err(PaletteError::InvalidValue(format!("sPLT sample depth {} is not 8 or 16", depth))),
+        };
+        if !entries.len().is_multiple_of(size) {
+            return Err(invalid_length("sPLT", data.len()));
+        }
+        let entries = entries
+            .chunks_exact(size)
+            .map(|entry| {
+                let sample = |i: usize| match depth {
+                    8 => entry[i] as u16,
+                    _ => be_u16(entry, i * 2),
+                };
+                SuggestedEntry { rgba: [sample(0), sample(1), sample(2), sample(3)], frequency: be_u16(entry, size - 2) }
+            })
+            .collect();
+        Ok(SuggestedPalette { name: data[..sep].iter().map(|&b| b as char).collect(), depth, entries })
+    }
+}
+
+impl SuggestedPalette {
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data: Vec<u8> = self.name.chars().map(|c| c as u8).collect();
+        data.push(0);
+        data.push(self.depth);
+        for entry in &self.entries {
+            for sample in entry.rgba {
+                match self.depth {
+                    8 => data.push(sample as u8),
+                    _ => data.extend_from_slice(&sample.to_be_bytes()),
+                }
+            }
+            data.extend_from_slice(&entry.frequency.to_be_bytes());
+        }
+        Chunk::new(ChunkType::from_str("sPLT").unwrap(), data)
+    }
+}
+
+impl Display for SuggestedPalette {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} entries, {}-bit", self.name, self.entries.len(), self.depth)
+    }
+}
+
+impl ChunkData for SuggestedPalette {
+    const CHUNK_TYPES: &'static [&'static str] = &["sPLT"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        SuggestedPalette::try_from(chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        self.to_chunk()
+    }
+
+    fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// The palette-related chunks of an image, each checked against `IHDR`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaletteInfo {
+    pub header: ImageHeader,
+    pub palette: Option<Palette>,
+    pub transparency: Option<Transparency>,
+    pub background: Option<Background>,
+    pub histogram: Option<Histogram>,
+    pub suggested: Vec<SuggestedPalette>,
+}
+
+impl PaletteInfo {
+    pub fn from_png(png: &Png) -> Result<PaletteInfo, PaletteError> {
+        let header = png.image_header().map_err(|_| PaletteError::InvalidHeader)?;
+        let palette = png.chunk_by_type("PLTE").map(|chunk| Palette::decode(chunk.data(), &header)).transpose()?;
+        if header.is_indexed() && palette.is_none() {
+            return Err(PaletteError::MissingPalette);
+        }
+        let transparency = png
+            .chunk_by_type("tRNS")
+            .map(|chunk| Transparency::decode(chunk.data(), &header, palette.as_ref()))
+            .transpose()?;
+        let background = png
+            .chunk_by_type("bKGD")
+            .map(|chunk| Background::decode(chunk.data(), &header, palette.as_ref()))
+            .transpose()?;
+        let histogram = png
+            .chunk_by_type("hIST")
+            .map(|chunk| Histogram::decode(chunk.data(), palette.as_ref()))
+            .transpose()?;
+        let suggested = png
+            .chunks()
+            .iter()
+            .filter(|chunk| &chunk.chunk_type().bytes() == b"sPLT")
+            .map(|chunk| SuggestedPalette::try_from(chunk.data()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PaletteInfo { header, palette, transparency, background, histogram, suggested })
+    }
+
+    /// Alpha per palette entry; entries without one in `tRNS` are opaque.
+    pub fn alpha(&self) -> Vec<u8> {
+        let entries = self.palette.as_ref().map_or(0, |palette| palette.0.len());
+        let mut alpha = match &self.transparency {
+            Some(Transparency::Alpha(alpha)) => alpha.clone(),
+            _ => vec![],
+        };
+        alpha.resize(entries, 255);
+        alpha
+    }
+
+    /// Replaces palette entry `index`, or appends one when `index` is the
+    /// palette size. `alpha` is only allowed for indexed images.
+    pub fn set_entry(&mut self, index: usize, rgb: [u8; 3], alpha: Option<u8>) -> Result<(), PaletteError> {
+        if alpha.is_some() && !self.header.is_indexed() {
+            return Err(PaletteError::NotAllowed { chunk_type: "tRNS".to_string(), color_type: self.header.color_type });
+        }
+        let palette = match &mut self.palette {
+            Some(palette) => palette,
+            None if index == 0 && self.header.color_type != 0 && self.header.color_type != 4 => {
+                self.palette.insert(Palette(vec![]))
+            },
+            None => return Err(PaletteError::MissingPalette),
+        };
+        let entries = palette.0.len();
+        match index {
+            _ if index < entries => palette.0[index] = rgb,
+            _ if index == entries => {
+                let max = Palette::max_entries(&self.header);
+                if entries == max {
+                    return Err(PaletteError::TooManyEntries { entries: entries + 1, max });
+                }
+                palette.0.push(rgb);
+                if let Some(histogram) = &mut self.histogram {
+                    histogram.0.push(0);
+                }
+            },
+            _ => return Err(PaletteError::InvalidValue(format!("index {} is outside the {}-entry palette", index, entries))),
+        }
+        if let Some(alpha) = alpha {
+            let mut all = self.alpha();
+            all[index] = alpha;
+            while all.last() == Some(&255) {
+                all.pop();
+            }
+            self.transparency = (!all.is_empty()).then_some(Transparency::Alpha(all));
+        }
+        Ok(())
+    }
+
+    /// Writes `PLTE`, `tRNS` and `hIST` back to `png`. A changed `PLTE` drops
+    /// the unsafe-to-copy chunks, which are returned.
+    pub fn write_to_png(&self, png: &mut Png) -> Vec<Chunk> {
+        png.edit_critical(|png| {
+            if let Some(palette) = &self.palette {
+                replace_chunk(png, palette.to_chunk());
+            }
+            match &self.transparency {
+                Some(transparency) => replace_chunk(png, transparency.to_chunk()),
+                None => {
+                    png.strip_chunks(|chunk| &chunk.chunk_type().bytes() == b"tRNS");
+                },
+            }
+            if let Some(histogram) = &self.histogram {
+                replace_chunk(png, histogram.to_chunk());
+            }
+        })
+        .1
+    }
+}
+
+/// Replaces the chunk of the same type where it is, or adds it where
+/// `set_chunk` would.
+fn replace_chunk(png: &mut Png, chunk: Chunk) {
+    match png.chunks().iter().position(|other| other.chunk_type() == chunk.chunk_type()) {
+        Some(idx) => {
+            png.remove_chunk_at(idx);
+            png.insert_chunk(idx, chunk);
+        },
+        None => {
+            png.set_chunk(chunk);
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    /// A 2-bit indexed image with three palette entries.
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", vec![0, 0, 0, 4, 0, 0, 0, 4, 2, 3, 0, 0, 0]),
+            chunk("PLTE", vec![255, 0, 0, 0, 255, 0, 0, 0, 255]),
+            chunk("tRNS", vec![0]),
+            chunk("bKGD", vec![2]),
+            chunk("hIST", vec![0, 8, 0, 4, 0, 4]),
+            chunk("sPLT", b"web\0\x08\xff\x00\x00\xff\x00\x01".to_vec()),
+            chunk("IDAT", vec![]),
+            chunk("IEND", vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_from_png() {
+        let info = PaletteInfo::from_png(&testing_png()).unwrap();
+        assert_eq!(info.palette.as_ref().unwrap().0.len(), 3);
+        assert_eq!(info.alpha(), vec![0, 255, 255]);
+        assert_eq!(info.background, Some(Background::Index(2)));
+        assert_eq!(info.histogram, Some(Histogram(vec![8, 4, 4])));
+        assert_eq!(info.suggested[0].to_string(), "web: 1 entries, 8-bit");
+        assert_eq!(info.suggested[0].entries[0], SuggestedEntry { rgba: [255, 0, 0, 255], frequency: 1 });
+    }
+
+    #[test]
+    fn test_invalid_palette() {
+        let header = ImageHeader { width: 1, height: 1, bit_depth: 1, color_type: 3, interlaced: false };
+        assert_eq!(Palette::decode(&[0; 9], &header), Err(PaletteError::TooManyEntries { entries: 3, max: 2 }));
+        assert_eq!(Palette::decode(&[0; 4], &header), Err(invalid_length("PLTE", 4)));
+        let palette = Palette(vec![[0; 3]]);
+        assert_eq!(Transparency::decode(&[0, 0], &header, Some(&palette)), Err(invalid_length("tRNS", 2)));
+        assert!(matches!(Background::decode(&[1], &header, Some(&palette)), Err(PaletteError::InvalidValue(_))));
+        assert_eq!(Histogram::decode(&[0, 1], None), Err(PaletteError::MissingPalette));
+
+        let gray = ImageHeader { color_type: 0, bit_depth: 4, ..header };
+        assert_eq!(Palette::decode(&[0; 3], &gray), Err(PaletteError::NotAllowed { chunk_type: "PLTE".to_string(), color_type: 0 }));
+        assert!(matches!(Transparency::decode(&[0, 16], &gray, None), Err(PaletteError::InvalidValue(_))));
+        assert_eq!(Transparency::decode(&[0, 15], &gray, None), Ok(Transparency::Gray(15)));
+    }
+
+    #[test]
+    fn test_set_entry() {
+        let mut png = testing_png();
+        let mut info = PaletteInfo::from_png(&png).unwrap();
+        info.set_entry(0, [1, 2, 3], Some(255)).unwrap();
+        assert_eq!(info.transparency, None);
+        info.set_entry(3, [4, 5, 6], Some(128)).unwrap();
+        assert_eq!(info.set_entry(4, [0, 0, 0], None), Err(PaletteError::TooManyEntries { entries: 5, max: 4 }));
+        assert_eq!(info.palette.as_ref().unwrap().0.len(), 4);
+        assert!(info.write_to_png(&mut png).is_empty());
+
+        let written = PaletteInfo::from_png(&png).unwrap();
+        assert_eq!(written, info);
+        assert_eq!(written.alpha(), vec![255, 255, 255, 128]);
+        assert_eq!(written.histogram, Some(Histogram(vec![8, 4, 4, 0])));
+        let types: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "PLTE", "tRNS", "bKGD", "hIST", "sPLT", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_set_entry_drops_unsafe_to_copy() {
+        let mut png = testing_png();
+        png.insert_chunk(1, Chunk::new(ChunkType::from_str("prVT").unwrap(), vec![1]));
+        png.insert_chunk(1, Chunk::new(ChunkType::from_str("prVt").unwrap(), vec![2]));
+        let mut info = PaletteInfo::from_png(&png).unwrap();
+        // Rewriting the same palette leaves the critical chunks as they were.
+        assert!(info.write_to_png(&mut png).is_empty());
+
+        info.set_entry(0, [9, 9, 9], None).unwrap();
+        let dropped = info.write_to_png(&mut png);
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].chunk_type().to_string(), "prVT");
+        assert!(png.chunk_by_type("prVT").is_none());
+        assert!(png.chunk_by_type("prVt").is_some());
+    }
+
+    #[test]
+    fn test_swatch() {
+        let palette = Palette((0..20).map(|i| [i, i, i]).collect());
+        let swatch = palette.swatch(&[0], 4);
+        let header = swatch.image_header().unwrap();
+        assert_eq!((header.width, header.height, header.color_type), (64, 8, 6));
+        let raw = crate::text::inflate(swatch.chunk_by_type("IDAT").unwrap().data()).unwrap();
+        assert_eq!(raw.len(), (64 * 4 + 1) * 8);
+        assert_eq!(&raw[1..9], &[0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&raw[17..21], &[1, 1, 1, 255]);
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#ff8000"), Ok(([255, 128, 0], None)));
+        assert_eq!(parse_color("ff800080"), Ok(([255, 128, 0], Some(128))));
+        assert!(parse_color("#ff80").is_err());
+        assert!(parse_color("#gg8000").is_err());
+    }
+}
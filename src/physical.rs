@@ -0,0 +1,370 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::bytes::{be_i32, be_u32, invalid_length, latin1, InvalidLength};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::registry::{ChunkData, DecodeError};
+
+const METERS_PER_INCH: f64 = 0.0254;
+
+/// Why a physical dimension chunk is invalid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PhysicalError {
+    /// The chunk data has the wrong size, or a separator is missing.
+    InvalidLength { chunk_type: String, length: usize },
+    /// A field is out of range.
+    InvalidValue(String),
+}
+
+impl Display for PhysicalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PhysicalError::InvalidLength { chunk_type, length } => write!(f, "{} chunk has invalid length {}", chunk_type, length),
+            PhysicalError::InvalidValue(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PhysicalError {}
+
+impl InvalidLength for PhysicalError {
+    fn invalid_length(chunk_type: &str, length: usize) -> Self {
+        PhysicalError::InvalidLength { chunk_type: chunk_type.to_string(), length }
+    }
+}
+
+/// Checks the ASCII floating-point format `sCAL` and `pCAL` use.
+fn is_ascii_float(s: &str) -> bool {
+    let s = s.strip_prefix(['+', '-']).unwrap_or(s);
+    let (mantissa, exponent) = match s.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent.strip_prefix(['+', '-']).unwrap_or(exponent))),
+        None => (s, None),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    (!int.is_empty() || !frac.is_empty()) && digits(int) && digits(frac)
+        && exponent.is_none_or(|exponent| !exponent.is_empty() && digits(exponent))
+}
+
+/// `pHYs`: pixels per unit on each axis. Without a unit, only the aspect
+/// ratio is meaningful.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelDimensions {
+    pub x: u32,
+    pub y: u32,
+    pub in_meters: bool,
+}
+
+impl TryFrom<&[u8]> for PixelDimensions {
+    type Error = PhysicalError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != 9 {
+            return Err(invalid_length("pHYs", data.len()));
+        }
+        if data[8] > 1 {
+            return Err(PhysicalError::InvalidValue(format!("pHYs unit {} is not 0 or 1", data[8])));
+        }
+        Ok(PixelDimensions { x: be_u32(data, 0), y: be_u32(data, 4), in_meters: data[8] == 1 })
+    }
+}
+
+impl Display for PixelDimensions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.dpi() {
+            Some((x, y)) if x.round() == y.round() => write!(f, "{} dpi ({} px/m)", x.round(), self.x),
+            Some((x, y)) => write!(f, "{}x{} dpi ({}x{} px/m)", x.round(), y.round(), self.x, self.y),
+            None => write!(f, "{}:{} aspect ratio", self.x, self.y),
+        }
+    }
+}
+
+impl ChunkData for PixelDimensions {
+    const CHUNK_TYPES: &'static [&'static str] = &["pHYs"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        PixelDimensions::try_from(chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        self.to_chunk()
+    }
+
+    fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl PixelDimensions {
+    /// Pixels per meter for the given dots per inch on each axis.
+    pub fn from_dpi(x: f64, y: f64) -> Result<PixelDimensions, PhysicalError> {
+        let per_meter = |dpi: f64| {
+            let ppm = (dpi / METERS_PER_INCH).round();
+            if dpi.is_finite() && ppm >= 1.0 && ppm <= i32::MAX as f64 {
+                Ok(ppm as u32)
+            } else {
+                Err(PhysicalError::InvalidValue(format!("{} dpi is out of range", dpi)))
+            }
+        };
+        Ok(PixelDimensions { x: per_meter(x)?, y: per_meter(y)?, in_meters: true })
+    }
+
+    /// Dots per inch on each axis, when the unit is known.
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        self.in_meters.then_some((self.x as f64 * METERS_PER_INCH, self.y as f64 * METERS_PER_INCH))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = self.x.to_be_bytes().to_vec();
+        data.extend_from_slice(&self.y.to_be_bytes());
+        data.push(self.in_meters as u8);
+        Chunk::new(ChunkType::from_str("pHYs").unwrap(), data)
+    }
+}
+
+/// `oFFs`: where the image sits on a page, in pixels or micrometers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Offset {
+    pub x: i32,
+    pub y: i32,
+    pub in_micrometers: bool,
+}
+
+impl TryFrom<&[u8]> for Offset {
+    type Error = PhysicalError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != 9 {
+            return Err(invalid_length("oFFs", data.len()));
+        }
+        if data[8] > 1 {
+            return Err(PhysicalError::InvalidValue(format!("oFFs unit {} is not 0 or 1", data[8])));
+        }
+        Ok(Offset { x: be_i32(data, 0), y: be_i32(data, 4), in_micrometers: data[8] == 1 })
+    }
+}
+
+impl Display for Offset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "offset ({}, {}) {}", self.x, self.y, if self.in_micrometers { "µm" } else { "px" })
+    }
+}
+
+impl ChunkData for Offset {
+    const CHUNK_TYPES: &'static [&'static str] = &["oFFs"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        Offset::try_from(chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        self.to_chunk()
+    }
+
+    fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Offset {
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = self.x.to_be_bytes().to_vec();
+        data.extend_from_slice(&self.y.to_be_bytes());
+        data.push(self.in_micrometers as u8);
+        Chunk::new(ChunkType::from_str("oFFs").unwrap(), data)
+    }
+}
+
+/// `sCAL`: the physical size of one pixel, as ASCII floats.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PhysicalScale {
+    /// Meters when true, otherwise radians.
+    pub in_meters: bool,
+    pub width: String,
+    pub height: String,
+}
+
+impl TryFrom<&[u8]> for PhysicalScale {
+    type Error = PhysicalError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let (&unit, rest) = data.split_first().ok_or_else(|| invalid_length("sCAL", data.len()))?;
+        let sep = rest.iter().position(|&b| b == 0).ok_or_else(|| invalid_length("sCAL", data.len()))?;
+        let scale = PhysicalScale { in_meters: unit == 1, width: latin1(&rest[..sep]), height: latin1(&rest[sep + 1..]) };
+        if unit != 1 && unit != 2 {
+            return Err(PhysicalError::InvalidValue(format!("sCAL unit {} is not 1 or 2", unit)));
+        }
+        for value in [&scale.width, &scale.height] {
+            if !is_ascii_float(value) || value.starts_with('-') || value.parse::<f64>().map_or(true, |v| v <= 0.0) {
+                return Err(PhysicalError::InvalidValue(format!("sCAL value {:?} is not a positive number", value)));
+            }
+        }
+        Ok(scale)
+    }
+}
+
+impl Display for PhysicalScale {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "pixel is {} x {} {}", self.width, self.height, if self.in_meters { "m" } else { "rad" })
+    }
+}
+
+impl ChunkData for PhysicalScale {
+    const CHUNK_TYPES: &'static [&'static str] = &["sCAL"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        PhysicalScale::try_from(chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        self.to_chunk()
+    }
+
+    fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl PhysicalScale {
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = vec![if self.in_meters { 1 } else { 2 }];
+        data.extend_from_slice(self.width.as_bytes());
+        data.push(0);
+        data.extend_from_slice(self.height.as_bytes());
+        Chunk::new(ChunkType::from_str("sCAL").unwrap(), data)
+    }
+}
+
+/// `pCAL`: maps stored sample values to physical quantities such as
+/// temperature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PixelCalibration {
+    pub name: String,
+    pub x0: i32,
+    pub x1: i32,
+    /// 0 linear, 1 base-e exponential, 2 arbitrary-base exponential,
+    /// 3 hyperbolic.
+    pub equation: u8,
+    pub unit: String,
+    pub parameters: Vec<String>,
+}
+
+impl TryFrom<&[u8]> for PixelCalibration {
+    type Error = PhysicalError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let truncated = || invalid_length("pCAL", data.len());
+        let sep = data.iter().position(|&b| b == 0).ok_or_else(truncated)?;
+        if sep == 0 || sep > 79 {
+            return Err(PhysicalError::InvalidValue("pCAL name must be 1 to 79 characters".to_string()));
+        }
+        let fixed = data.get(sep + 1..sep + 11).ok_or_else(truncated)?;
+        let (x0, x1, equation, count) = (be_i32(fixed, 0), be_i32(fixed, 4), fixed[8], fixed[9] as usize);
+        let mut fields = data[sep + 11..].split(|&b| b == 0).map(latin1);
+        let unit = fields.next().ok_or_else(truncated)?;
+        let parameters: Vec<String> = fields.collect();
+        let expected = match equation {
+            0 => 2,
+            1 | 2 => 3,
+            3 => 4,
+            _ => return Err(PhysicalError::InvalidValue(format!("pCAL equation type {} is unknown", equation))),
+        };
+        if count != expected || parameters.len() != expected {
+            return Err(PhysicalError::InvalidValue(format!("pCAL equation type {} takes {} parameters", equation, expected)));
+        }
+        if x0 == x1 {
+            return Err(PhysicalError::InvalidValue("pCAL x0 and x1 must differ".to_string()));
+        }
+        if let Some(bad) = parameters.iter().find(|p| !is_ascii_float(p)) {
+            return Err(PhysicalError::InvalidValue(format!("pCAL parameter {:?} is not a number", bad)));
+        }
+        Ok(PixelCalibration { name: latin1(&data[..sep]), x0, x1, equation, unit, parameters })
+    }
+}
+
+impl Display for PixelCalibration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let equation = ["linear", "exponential", "arbitrary-base exponential", "hyperbolic"][self.equation as usize];
+        write!(f, "{}: {} over {}..{} in {:?}", self.name, equation, self.x0, self.x1, self.unit)
+    }
+}
+
+impl ChunkData for PixelCalibration {
+    const CHUNK_TYPES: &'static [&'static str] = &["pCAL"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        PixelCalibration::try_from(chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        self.to_chunk()
+    }
+
+    fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl PixelCalibration {
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data: Vec<u8> = self.name.chars().map(|c| c as u8).collect();
+        data.push(0);
+        data.extend_from_slice(&self.x0.to_be_bytes());
+        data.extend_from_slice(&self.x1.to_be_bytes());
+        data.extend_from_slice(&[self.equation, self.parameters.len() as u8]);
+        data.extend(self.unit.chars().map(|c| c as u8));
+        for parameter in &self.parameters {
+            data.push(0);
+            data.extend_from_slice(parameter.as_bytes());
+        }
+        Chunk::new(ChunkType::from_str("pCAL").unwrap(), data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dpi() {
+        let dims = PixelDimensions::from_dpi(300.0, 300.0).unwrap();
+        assert_eq!((dims.x, dims.y), (11811, 11811));
+        assert_eq!(dims.to_string(), "300 dpi (11811 px/m)");
+        assert_eq!(PixelDimensions::try_from(dims.to_chunk().data()), Ok(dims));
+        let aspect = PixelDimensions { x: 2, y: 1, in_meters: false };
+        assert_eq!(aspect.to_string(), "2:1 aspect ratio");
+        assert!(PixelDimensions::from_dpi(0.0, 300.0).is_err());
+        assert!(PixelDimensions::try_from(&[0, 0, 0, 1, 0, 0, 0, 1, 2][..]).is_err());
+    }
+
+    #[test]
+    fn test_offset_and_scale() {
+        let offset = Offset { x: -20, y: 40, in_micrometers: true };
+        assert_eq!(Offset::try_from(offset.to_chunk().data()), Ok(offset));
+        assert_eq!(offset.to_string(), "offset (-20, 40) µm");
+
+        let scale = PhysicalScale { in_meters: true, width: "1.5e-4".to_string(), height: ".00015".to_string() };
+        assert_eq!(PhysicalScale::try_from(scale.to_chunk().data()), Ok(scale));
+        assert!(PhysicalScale::try_from(&b"\x01-1\x001"[..]).is_err());
+        assert!(PhysicalScale::try_from(&b"\x01abc\x001"[..]).is_err());
+        assert!(PhysicalScale::try_from(&b"\x03\x31\x001"[..]).is_err());
+    }
+
+    #[test]
+    fn test_calibration() {
+        let calibration = PixelCalibration {
+            name: "temperature".to_string(),
+            x0: 0,
+            x1: 65535,
+            equation: 0,
+            unit: "K".to_string(),
+            parameters: vec!["250".to_string(), "1e2".to_string()],
+        };
+        assert_eq!(PixelCalibration::try_from(calibration.to_chunk().data()), Ok(calibration.clone()));
+        assert_eq!(calibration.to_string(), "temperature: linear over 0..65535 in \"K\"");
+        let wrong_count = PixelCalibration { equation: 3, ..calibration.clone() };
+        assert!(PixelCalibration::try_from(wrong_count.to_chunk().data()).is_err());
+        let same = PixelCalibration { x1: 0, ..calibration };
+        assert!(PixelCalibration::try_from(same.to_chunk().data()).is_err());
+    }
+}
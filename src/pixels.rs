@@ -0,0 +1,281 @@
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+use flate2::read::ZlibDecoder;
+use crate::png::{ImageHeader, Png};
+
+/// Adam7 passes as (x start, y start, x step, y step).
+const ADAM7: [(u32, u32, u32, u32); 7] = [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+
+/// Why the image data could not be decoded to scanlines.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PixelError {
+    /// The concatenated `IDAT` data does not inflate.
+    InvalidCompression,
+    /// The inflated data does not hold exactly the scanlines `IHDR` implies.
+    InvalidLength { expected: usize, found: usize },
+    /// A scanline starts with a filter type other than 0 to 4.
+    InvalidFilter { filter: u8, row: usize },
+    /// An indexed pixel refers past the end of `PLTE`.
+    InvalidPaletteIndex(u8),
+}
+
+impl Display for PixelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PixelError::InvalidCompression => write!(f, "image data does not inflate"),
+            PixelError::InvalidLength { expected, found } => {
+                write!(f, "expected {} bytes of scanlines, found {}", expected, found)
+            },
+            PixelError::InvalidFilter { filter, row } => write!(f, "unknown filter type {} on row {}", filter, row),
+            PixelError::InvalidPaletteIndex(index) => write!(f, "palette index {} is out of range", index),
+        }
+    }
+}
+
+impl std::error::Error for PixelError {}
+
+/// The contents of every `IDAT` chunk, in order.
+pub fn image_data(png: &Png) -> Vec<u8> {
+    png.chunks()
+        .iter()
+        .filter(|chunk| &chunk.chunk_type().bytes() == b"IDAT")
+        .flat_map(|chunk| chunk.data().iter().copied())
+        .collect()
+}
+
+fn bits_per_pixel(header: &ImageHeader) -> usize {
+    header.channels() as usize * header.bit_depth as usize
+}
+
+/// Bytes in one row of `width` pixels, without the filter byte.
+fn stride(header: &ImageHeader, width: u32) -> usize {
+    (width as usize * bits_per_pixel(header)).div_ceil(8)
+}
+
+/// The size of each reduced image the scanlines are stored as: the whole
+/// image, or the non-empty Adam7 passes.
+fn passes(header: &ImageHeader) -> Vec<(u32, u32)> {
+    if !header.interlaced {
+        return vec![(header.width, header.height)];
+    }
+    ADAM7
+        .iter()
+        .map(|&(x, y, dx, dy)| (header.width.saturating_sub(x).div_ceil(dx), header.height.saturating_sub(y).div_ceil(dy)))
+        .filter(|&(width, height)| width > 0 && height > 0)
+        .collect()
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// The length of the inflated image data `header` implies, filter bytes
+/// included. Checked, as IHDR can describe far more data than fits in memory.
+fn scanlines_length(header: &ImageHeader) -> Option<usize> {
+    passes(header).iter().try_fold(0usize, |total, &(width, height)| {
+        let line = (width as usize).checked_mul(bits_per_pixel(header))?.div_ceil(8) + 1;
+        total.checked_add(line.checked_mul(height as usize)?)
+    })
+}
+
+/// Undoes the per-scanline filters of inflated image data, returning the
+/// rows back to back without their filter bytes. Interlaced images come
+/// back pass by pass rather than deinterlaced.
+pub fn unfilter(data: &[u8], header: &ImageHeader) -> Result<Vec<u8>, PixelError> {
+    let bpp = bits_per_pixel(header).div_ceil(8);
+    let passes = passes(header);
+    // Once the length matches the data, `stride` cannot overflow below.
+    let expected = scanlines_length(header);
+    if expected != Some(data.len()) {
+        return Err(PixelError::InvalidLength { expected: expected.unwrap_or(usize::MAX), found: data.len() });
+    }
+    let mut pixels = Vec::with_capacity(data.len());
+    let mut offset = 0;
+    let mut row_number = 0;
+    for (width, height) in passes {
+        let stride = stride(header, width);
+        for y in 0..height as usize {
+            let line = &data[offset..offset + stride + 1];
+            offset += stride + 1;
+            let (filter, line) = (line[0], &line[1..]);
+            let row_start = pixels.len();
+            for x in 0..stride {
+                let left = if x >= bpp { pixels[row_start + x - bpp] } else { 0 };
+                let up = if y > 0 { pixels[row_start - stride + x] } else { 0 };
+                let up_left = if y > 0 && x >= bpp { pixels[row_start - stride + x - bpp] } else { 0 };
+                let predicted = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    4 => paeth(left, up, up_left),
+                    _ => return Err(PixelError::InvalidFilter { filter, row: row_number }),
+                };
+                pixels.push(line[x].wrapping_add(predicted));
+            }
+            row_number += 1;
+        }
+    }
+    Ok(pixels)
+}
+
+/// The `bits`-wide sample starting `bit` bits into `row`, for depths of
+/// 8 bits or fewer.
+fn sample(row: &[u8], bit: usize, bits: usize) -> u8 {
+    row[bit / 8] >> (8 - bits - bit % 8) & (0xFF >> (8 - bits))
+}
+
+/// Rearranges the passes returned by `unfilter` into the rows of the full
+/// image, as if it had not been interlaced.
+pub fn deinterlace(data: &[u8], header: &ImageHeader) -> Vec<u8> {
+    if !header.interlaced {
+        return data.to_vec();
+    }
+    let bits = bits_per_pixel(header);
+    let row_stride = stride(header, header.width);
+    let mut rows = vec![0; row_stride * header.height as usize];
+    let mut offset = 0;
+    for &(x0, y0, dx, dy) in &ADAM7 {
+        let width = header.width.saturating_sub(x0).div_ceil(dx);
+        let height = header.height.saturating_sub(y0).div_ceil(dy);
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let pass_stride = stride(header, width);
+        for (py, line) in data[offset..offset + pass_stride * height as usize].chunks(pass_stride).enumerate() {
+            let row = &mut rows[(y0 as usize + py * dy as usize) * row_stride..][..row_stride];
+            for px in 0..width as usize {
+                let x = x0 as usize + px * dx as usize;
+                if bits >= 8 {
+                    let n = bits / 8;
+                    row[x * n..(x + 1) * n].copy_from_slice(&line[px * n..(px + 1) * n]);
+                } else {
+                    row[x * bits / 8] |= sample(line, px * bits, bits) << (8 - bits - x * bits % 8);
+                }
+            }
+        }
+        offset += pass_stride * height as usize;
+    }
+    rows
+}
+
+/// Looks up each pixel of indexed, non-interlaced `rows` in `PLTE` and
+/// `tRNS`, giving RGBA samples.
+pub fn expand_palette(rows: &[u8], header: &ImageHeader, palette: &[u8], alpha: &[u8]) -> Result<Vec<u8>, PixelError> {
+    let bits = header.bit_depth as usize;
+    let row_stride = stride(header, header.width);
+    let mut rgba = Vec::with_capacity(rows.len() / row_stride.max(1) * header.width as usize * 4);
+    for row in rows.chunks(row_stride.max(1)) {
+        for x in 0..header.width as usize {
+            let index = sample(row, x * bits, bits);
+            let start = index as usize * 3;
+            let color = palette.get(start..start + 3).ok_or(PixelError::InvalidPaletteIndex(index))?;
+            rgba.extend_from_slice(color);
+            rgba.push(alpha.get(index as usize).copied().unwrap_or(255));
+        }
+    }
+    Ok(rgba)
+}
+
+/// Inflates and unfilters the image data of a standard PNG.
+pub fn decode(png: &Png, header: &ImageHeader) -> Result<Vec<u8>, PixelError> {
+    // Inflate one byte past what IHDR implies, so a stream that expands
+    // further is caught by `unfilter` without being read to the end.
+    let limit = scanlines_length(header).map_or(1, |expected| expected as u64 + 1);
+    let mut data = vec![];
+    ZlibDecoder::new(image_data(png).as_slice())
+        .take(limit)
+        .read_to_end(&mut data)
+        .map_err(|_| PixelError::InvalidCompression)?;
+    unfilter(&data, header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(width: u32, height: u32, bit_depth: u8, color_type: u8, interlaced: bool) -> ImageHeader {
+        ImageHeader { width, height, bit_depth, color_type, interlaced }
+    }
+
+    #[test]
+    fn test_unfilter() {
+        // One row each of none, sub, up, average and paeth over RGB.
+        let data = [
+            0, 10, 20, 30, 40, 50, 60,
+            1, 1, 2, 3, 1, 1, 1,
+            2, 1, 1, 1, 1, 1, 1,
+            3, 4, 4, 4, 0, 0, 0,
+            4, 1, 1, 1, 1, 1, 1,
+        ];
+        assert_eq!(unfilter(&data, &header(2, 5, 8, 2, false)).unwrap(), vec![
+            10, 20, 30, 40, 50, 60,
+            1, 2, 3, 2, 3, 4,
+            2, 3, 4, 3, 4, 5,
+            5, 5, 6, 4, 4, 5,
+            6, 6, 7, 6, 6, 7,
+        ]);
+        assert_eq!(unfilter(&[5, 0], &header(1, 1, 8, 0, false)), Err(PixelError::InvalidFilter { filter: 5, row: 0 }));
+        let huge = header(2147483647, 2147483647, 16, 6, false);
+        assert_eq!(unfilter(&[0], &huge), Err(PixelError::InvalidLength { expected: usize::MAX, found: 1 }));
+        let huge = header(2147483647, 2147483647, 16, 6, true);
+        assert_eq!(unfilter(&[0], &huge), Err(PixelError::InvalidLength { expected: usize::MAX, found: 1 }));
+    }
+
+    #[test]
+    fn test_sub_byte_and_interlaced() {
+        // 10 one-bit pixels take two bytes; sub works on whole bytes.
+        assert_eq!(unfilter(&[1, 0x0f, 0x01], &header(10, 1, 1, 0, false)).unwrap(), vec![0x0f, 0x10]);
+        // A 3x3 interlaced gray image has no pixels in passes 2 and 3.
+        assert_eq!(passes(&header(3, 3, 8, 0, true)), vec![(1, 1), (1, 1), (2, 1), (1, 2), (3, 1)]);
+        let data = [0, 1, 0, 2, 0, 3, 4, 0, 5, 0, 6, 0, 7, 8, 9];
+        assert_eq!(unfilter(&data, &header(3, 3, 8, 0, true)).unwrap(), vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(unfilter(&data[1..], &header(3, 3, 8, 0, true)), Err(PixelError::InvalidLength { expected: 15, found: 14 }));
+    }
+
+    #[test]
+    fn test_decode_stops_past_expected_length() {
+        use std::str::FromStr;
+        use crate::chunk::Chunk;
+        use crate::chunk_type::ChunkType;
+        use crate::registry::ChunkData;
+
+        let idat = |data: &[u8]| {
+            Png::from_chunks(vec![
+                header(1, 1, 8, 0, false).encode(),
+                Chunk::new(ChunkType::from_str("IDAT").unwrap(), crate::text::deflate(data)),
+                Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+            ])
+        };
+        assert_eq!(decode(&idat(&[0, 7]), &header(1, 1, 8, 0, false)).unwrap(), vec![7]);
+        // A megabyte of zeros is cut off one byte past the two expected.
+        let bomb = idat(&vec![0; 1 << 20]);
+        assert_eq!(decode(&bomb, &header(1, 1, 8, 0, false)), Err(PixelError::InvalidLength { expected: 2, found: 3 }));
+    }
+
+    #[test]
+    fn test_deinterlace() {
+        let pixels = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+        assert_eq!(deinterlace(&pixels, &header(3, 3, 8, 0, true)), vec![1, 5, 2, 7, 8, 9, 3, 6, 4]);
+        assert_eq!(deinterlace(&pixels, &header(3, 3, 8, 0, false)), pixels.to_vec());
+        // Passes 1, 6 and 7 of a 2x2 one-bit image, a byte per row each.
+        assert_eq!(deinterlace(&[0x80, 0x80, 0x40], &header(2, 2, 1, 0, true)), vec![0xC0, 0x40]);
+    }
+
+    #[test]
+    fn test_expand_palette() {
+        let palette = [0, 0, 0, 10, 20, 30, 40, 50, 60];
+        let rgba = expand_palette(&[0x60], &header(2, 1, 2, 3, false), &palette, &[0, 128]).unwrap();
+        assert_eq!(rgba, vec![10, 20, 30, 128, 40, 50, 60, 255]);
+        let rgba = expand_palette(&[3], &header(1, 1, 8, 3, false), &palette, &[]);
+        assert_eq!(rgba, Err(PixelError::InvalidPaletteIndex(3)));
+    }
+}
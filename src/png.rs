@@ -1,48 +1,169 @@
-use super::*;
-use std::fmt::{Display, Formatter, write};
-use std::process::id;
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
-use anyhow::{Result, Error};
-use crate::chunk_type::ChunkType;
 use crate::chunk::{Chunk, ChunkError};
+use crate::chunk_type::ChunkType;
+use crate::registry::{ChunkData, DecodeError};
+
+/// Errors produced while reading or editing a `Png`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PngError {
+    /// The input does not start with the 8-byte PNG signature.
+    InvalidHeader,
+    /// The chunk starting at `offset` could not be read.
+    InvalidChunk { offset: usize, source: ChunkError },
+    /// There is no chunk of the requested type.
+    ChunkNotFound(String),
+    /// `IHDR` is missing, the wrong size, or has an invalid combination of
+    /// color type and bit depth.
+    InvalidImageHeader,
+}
 
+impl std::fmt::Display for PngError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PngError::InvalidHeader => write!(f, "not a png: bad signature"),
+            PngError::InvalidChunk { offset, source } => write!(f, "bad chunk at offset {}: {}", offset, source),
+            PngError::ChunkNotFound(chunk_type) => write!(f, "no {} chunk found", chunk_type),
+            PngError::InvalidImageHeader => write!(f, "missing or invalid IHDR chunk"),
+        }
+    }
+}
 
+impl std::error::Error for PngError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PngError::InvalidChunk { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// The `IHDR` chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    /// 0 grayscale, 2 rgb, 3 indexed, 4 grayscale+alpha, 6 rgba.
+    pub color_type: u8,
+    pub interlaced: bool,
+}
 
-#[derive(Debug)]
-pub struct PngError;
+impl TryFrom<&[u8]> for ImageHeader {
+    type Error = PngError;
 
-impl std::fmt::Display for PngError {
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != 13 || data[10] != 0 || data[11] != 0 || data[12] > 1 {
+            return Err(PngError::InvalidImageHeader);
+        }
+        let header = ImageHeader {
+            width: u32::from_be_bytes(data[0..4].try_into().unwrap()),
+            height: u32::from_be_bytes(data[4..8].try_into().unwrap()),
+            bit_depth: data[8],
+            color_type: data[9],
+            interlaced: data[12] == 1,
+        };
+        let depth_allowed = match header.color_type {
+            0 => [1, 2, 4, 8, 16].contains(&header.bit_depth),
+            3 => [1, 2, 4, 8].contains(&header.bit_depth),
+            2 | 4 | 6 => [8, 16].contains(&header.bit_depth),
+            _ => false,
+        };
+        if !depth_allowed {
+            return Err(PngError::InvalidImageHeader);
+        }
+        Ok(header)
+    }
+}
+
+impl Display for ImageHeader {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Chunk Error")
+        let color = match self.color_type {
+            0 => "grayscale",
+            2 => "rgb",
+            3 => "indexed",
+            4 => "grayscale+alpha",
+            _ => "rgba",
+        };
+        let interlace = if self.interlaced { "adam7" } else { "non-interlaced" };
+        write!(f, "{}x{} {}-bit {}, {}", self.width, self.height, self.bit_depth, color, interlace)
     }
 }
 
-impl std::error::Error for PngError {
+impl ChunkData for ImageHeader {
+    const CHUNK_TYPES: &'static [&'static str] = &["IHDR"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        ImageHeader::try_from(chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        Chunk::new(ChunkType::from_str("IHDR").unwrap(), self.to_bytes())
+    }
+
+    fn summary(&self) -> String {
+        self.to_string()
+    }
 }
 
+impl ImageHeader {
+    pub fn is_indexed(&self) -> bool {
+        self.color_type == 3
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.color_type == 4 || self.color_type == 6
+    }
+
+    /// Samples per pixel.
+    pub fn channels(&self) -> u8 {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
 
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.width.to_be_bytes().to_vec();
+        data.extend_from_slice(&self.height.to_be_bytes());
+        data.extend_from_slice(&[self.bit_depth, self.color_type, 0, 0, self.interlaced as u8]);
+        data
+    }
+}
 
-#[derive(Eq, PartialEq, Debug)]
+/// A PNG file as its list of chunks; the signature is implied. Bytes after
+/// `IEND` are kept as the trailer and written back out after it.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Png {
-    chunks: Vec<Chunk>
+    chunks: Vec<Chunk>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty", with = "base64_bytes"))]
+    trailer: Vec<u8>,
+}
+
+/// The trailer serializes as base64, like chunk data.
+#[cfg(feature = "serde")]
+mod base64_bytes {
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
 }
 
 impl TryFrom<&[u8]> for Png {
     type Error = PngError;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let mut chunks = vec![];
-        if value[0..8] == Png::STANDARD_HEADER {
-            let mut idx: usize = 8;
-            while idx < value.len() {
-                let chunk = Chunk::try_from(&value[idx..]).unwrap();
-                idx += chunk.length() as usize + 12;
-                chunks.push(chunk);
-            }
-            Ok(Png::from_chunks(chunks))
-        } else {
-            Err(PngError)
-        }
+        Png::parse_with(value, |bytes| Chunk::try_from(bytes))
     }
 }
 
@@ -57,23 +178,159 @@ impl Png {
 
     pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
         Png{
-            chunks
+            chunks,
+            trailer: vec![],
+        }
+    }
+
+    /// Like `try_from` but keeps chunks whose stored CRC is wrong, for
+    /// inspecting damaged files.
+    pub fn from_bytes_unchecked(value: &[u8]) -> Result<Png, PngError> {
+        Png::parse_with(value, Chunk::from_bytes_unchecked)
+    }
+
+    fn parse_with<F>(value: &[u8], parse_chunk: F) -> Result<Png, PngError>
+    where
+        F: Fn(&[u8]) -> Result<Chunk, ChunkError>,
+    {
+        let mut chunks = vec![];
+        if value.len() >= 8 && value[0..8] == Png::STANDARD_HEADER {
+            let mut idx: usize = 8;
+            while idx < value.len() {
+                let chunk = parse_chunk(&value[idx..]).map_err(|source| PngError::InvalidChunk { offset: idx, source })?;
+                idx += chunk.length() as usize + 12;
+                let is_end = &chunk.chunk_type().bytes() == b"IEND";
+                chunks.push(chunk);
+                if is_end {
+                    break;
+                }
+            }
+            let mut png = Png::from_chunks(chunks);
+            png.trailer = value[idx.min(value.len())..].to_vec();
+            Ok(png)
+        } else {
+            Err(PngError::InvalidHeader)
         }
     }
 
+    /// Adds a chunk at the end of the image, i.e. just before `IEND` when
+    /// there is one so the file stays valid.
     pub fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
+        match self.chunks.last() {
+            Some(last) if &last.chunk_type().bytes() == b"IEND" => {
+                let idx = self.chunks.len() - 1;
+                self.chunks.insert(idx, chunk)
+            },
+            _ => self.chunks.push(chunk),
+        }
+    }
+
+    /// The chunk types that a chunk of `chunk_type` must come before, per
+    /// the spec's ordering rules.
+    fn must_precede(chunk_type: &[u8; 4]) -> &'static [&'static [u8; 4]] {
+        match chunk_type {
+            b"cHRM" | b"gAMA" | b"iCCP" | b"sBIT" | b"sRGB" | b"cICP" | b"mDCV" | b"cLLI" => &[b"PLTE", b"IDAT"],
+            b"PLTE" => &[b"tRNS", b"bKGD", b"hIST", b"IDAT"],
+            b"tRNS" | b"bKGD" | b"hIST" | b"pHYs" | b"sPLT" | b"eXIf" | b"oFFs" | b"pCAL" | b"sCAL"
+            | b"acTL" => &[b"IDAT"],
+            _ => &[],
+        }
     }
 
-    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
+    /// Replaces every chunk of the same type with `chunk`, placed where the
+    /// spec wants it: before `PLTE` for color space chunks, before `IDAT`
+    /// for `eXIf`, `pHYs` and the like, otherwise where `append_chunk` puts
+    /// it. Returns the replaced chunks.
+    pub fn set_chunk(&mut self, chunk: Chunk) -> Vec<Chunk> {
+        let chunk_type = *chunk.chunk_type();
+        let removed = self.strip_chunks(|other| *other.chunk_type() == chunk_type);
+        let before = Png::must_precede(&chunk_type.bytes());
+        match self.chunks.iter().position(|other| before.contains(&&other.chunk_type().bytes())) {
+            Some(idx) => self.chunks.insert(idx, chunk),
+            None => self.append_chunk(chunk),
+        }
+        removed
+    }
+
+    /// Inserts a chunk at `index` in the chunk list.
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) {
+        self.chunks.insert(index, chunk)
+    }
+
+    /// Removes the chunk at `index` in the chunk list.
+    pub fn remove_chunk_at(&mut self, index: usize) -> Chunk {
+        self.chunks.remove(index)
+    }
+
+    /// Removes the first chunk of the given type.
+    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk, PngError> {
         let idx = self.chunks.iter().position(|chunk| chunk.chunk_type().to_string() == chunk_type);
-        Ok(self.chunks.remove(idx.unwrap()))
+        match idx {
+            Some(idx) => Ok(self.chunks.remove(idx)),
+            None => Err(PngError::ChunkNotFound(chunk_type.to_string())),
+        }
+    }
+
+    /// Removes every chunk for which `predicate` returns true and hands them
+    /// back in file order.
+    pub fn strip_chunks<F: FnMut(&Chunk) -> bool>(&mut self, mut predicate: F) -> Vec<Chunk> {
+        let mut removed = vec![];
+        let mut kept = vec![];
+        for chunk in self.chunks.drain(..) {
+            if predicate(&chunk) {
+                removed.push(chunk);
+            } else {
+                kept.push(chunk);
+            }
+        }
+        self.chunks = kept;
+        removed
+    }
+
+    /// Runs an edit and, if it changed any critical chunk, applies the spec's
+    /// copy rules: unknown ancillary chunks that are unsafe-to-copy no longer
+    /// match the image and are dropped. Returns the edit's result together
+    /// with the dropped chunks.
+    pub fn edit_critical<R, F: FnOnce(&mut Png) -> R>(&mut self, edit: F) -> (R, Vec<Chunk>) {
+        let before = self.critical_checksums();
+        let ret = edit(self);
+        let dropped = if self.critical_checksums() != before {
+            self.strip_chunks(|chunk| {
+                let chunk_type = chunk.chunk_type();
+                !chunk_type.is_critical() && !chunk_type.is_safe_to_copy() && !chunk_type.is_known()
+            })
+        } else {
+            vec![]
+        };
+        (ret, dropped)
+    }
+
+    fn critical_checksums(&self) -> Vec<u32> {
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.chunk_type().is_critical())
+            .map(|chunk| chunk.crc())
+            .collect()
     }
 
     pub fn header(&self) -> &[u8; 8] {
         &Png::STANDARD_HEADER
     }
 
+    /// The decoded `IHDR` chunk.
+    pub fn image_header(&self) -> Result<ImageHeader, PngError> {
+        let chunk = self.chunk_by_type("IHDR").ok_or(PngError::InvalidImageHeader)?;
+        ImageHeader::try_from(chunk.data())
+    }
+
+    /// Decodes the first chunk `T` handles, if there is one.
+    pub fn decode_chunk<T: ChunkData>(&self) -> Option<Result<T, DecodeError>> {
+        self.chunks
+            .iter()
+            .find(|chunk| T::CHUNK_TYPES.contains(&chunk.chunk_type().to_string().as_str()))
+            .map(|chunk| chunk.decode())
+    }
+
     pub fn chunks(&self) -> &[Chunk] {
         self.chunks.as_ref()
     }
@@ -86,12 +343,32 @@ impl Png {
         }
     }
 
+    /// The bytes after `IEND`; empty for a well-formed file.
+    pub fn trailer(&self) -> &[u8] {
+        &self.trailer
+    }
+
+    /// The offset of the trailer in the file, given chunk lengths as stored.
+    pub fn trailer_offset(&self) -> usize {
+        Png::STANDARD_HEADER.len() + self.chunks.iter().map(|chunk| chunk.length() as usize + 12).sum::<usize>()
+    }
+
+    pub fn set_trailer(&mut self, trailer: Vec<u8>) {
+        self.trailer = trailer;
+    }
+
+    /// Removes and returns the trailer.
+    pub fn take_trailer(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.trailer)
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut ret = vec![];
-        ret.append(&mut Png::STANDARD_HEADER.to_vec());
+        ret.extend_from_slice(self.header());
         for chunk in self.chunks() {
             ret.append(&mut chunk.as_bytes().clone());
         }
+        ret.extend_from_slice(&self.trailer);
         ret
     }
 }
@@ -101,17 +378,14 @@ mod tests {
     use super::*;
     use crate::chunk_type::ChunkType;
     use crate::chunk::Chunk;
-    use std::str::FromStr;
     use std::convert::TryFrom;
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -119,7 +393,7 @@ mod tests {
         Png::from_chunks(chunks)
     }
 
-    fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk> {
+    fn chunk_from_strings(chunk_type: &str, data: &str) -> anyhow::Result<Chunk> {
         use std::str::FromStr;
         // let chunk_type = ChunkType::from_str(chunk_type)?;
         let chunk_type = ChunkType::from_str(chunk_type)?;
@@ -220,6 +494,65 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_append_chunk_before_iend() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        let last = png.chunks().last().unwrap();
+        assert_eq!(&last.chunk_type().to_string(), "IEND");
+        assert_eq!(&png.chunks()[3].chunk_type().to_string(), "TeSt");
+    }
+
+    #[test]
+    fn test_set_chunk() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "").unwrap(),
+            chunk_from_strings("PLTE", "").unwrap(),
+            chunk_from_strings("IDAT", "").unwrap(),
+            chunk_from_strings("eXIf", "old").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        let replaced = png.set_chunk(chunk_from_strings("eXIf", "new").unwrap());
+        assert_eq!(replaced, vec![chunk_from_strings("eXIf", "old").unwrap()]);
+        png.set_chunk(chunk_from_strings("gAMA", "").unwrap());
+        png.set_chunk(chunk_from_strings("TeSt", "").unwrap());
+        let types: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, vec!["IHDR", "gAMA", "PLTE", "eXIf", "IDAT", "TeSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_image_header() {
+        let data = [0, 0, 0, 3, 0, 0, 0, 2, 4, 3, 0, 0, 1];
+        let header = ImageHeader::try_from(&data[..]).unwrap();
+        assert_eq!((header.width, header.height, header.bit_depth), (3, 2, 4));
+        assert!(header.is_indexed() && header.interlaced);
+        assert_eq!(header.to_bytes(), data);
+        assert_eq!(ImageHeader::try_from(&[0, 0, 0, 3, 0, 0, 0, 2, 16, 3, 0, 0, 0][..]), Err(PngError::InvalidImageHeader));
+        assert_eq!(ImageHeader::try_from(&[0, 0, 0, 3, 0, 0, 0, 2, 4, 6, 0, 0, 0][..]), Err(PngError::InvalidImageHeader));
+        assert_eq!(testing_png().image_header(), Err(PngError::InvalidImageHeader));
+    }
+
+    #[test]
+    fn test_trailer() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+        let mut bytes = png.as_bytes();
+        bytes.extend_from_slice(b"PK\x03\x04 and not a chunk");
+        let parsed = Png::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(parsed.chunks().len(), 4);
+        assert_eq!(parsed.trailer(), b"PK\x03\x04 and not a chunk");
+        assert_eq!(parsed.trailer_offset(), png.as_bytes().len());
+        assert_eq!(parsed.as_bytes(), bytes);
+        assert_eq!(Png::try_from(png.as_bytes().as_slice()).unwrap().trailer(), b"");
+    }
+
+    #[test]
+    fn test_remove_missing_chunk() {
+        let mut png = testing_png();
+        assert_eq!(png.remove_chunk("TeSt"), Err(PngError::ChunkNotFound("TeSt".to_string())));
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
@@ -229,6 +562,39 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_strip_chunks() {
+        let mut png = testing_png();
+        let removed = png.strip_chunks(|chunk| !chunk.chunk_type().is_critical());
+        assert_eq!(removed.len(), 1);
+        assert_eq!(&removed[0].chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunks().len(), 2);
+    }
+
+    #[test]
+    fn test_edit_critical_drops_unsafe_to_copy() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("prVT", "stale").unwrap());
+        png.append_chunk(chunk_from_strings("prVt", "still fine").unwrap());
+        png.append_chunk(chunk_from_strings("tIME", "known").unwrap());
+
+        let (_, dropped) = png.edit_critical(|png| png.remove_chunk("LASt"));
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(&dropped[0].chunk_type().to_string(), "prVT");
+        assert!(png.chunk_by_type("prVt").is_some());
+        assert!(png.chunk_by_type("tIME").is_some());
+    }
+
+    #[test]
+    fn test_edit_ancillary_keeps_unsafe_to_copy() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("prVT", "stale").unwrap());
+
+        let (_, dropped) = png.edit_critical(|png| png.remove_chunk("miDl"));
+        assert!(dropped.is_empty());
+        assert!(png.chunk_by_type("prVT").is_some());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -239,10 +605,20 @@ mod tests {
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
-        let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
+        let expected: Vec<u8> = PNG_FILE.to_vec();
         assert_eq!(actual, expected);
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let json = serde_json::to_string(&png).unwrap();
+        assert_eq!(serde_json::from_str::<Png>(&json).unwrap().as_bytes(), PNG_FILE.to_vec());
+        let yaml = serde_norway::to_string(&png).unwrap();
+        assert_eq!(serde_norway::from_str::<Png>(&yaml).unwrap().as_bytes(), PNG_FILE.to_vec());
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
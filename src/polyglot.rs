@@ -0,0 +1,332 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::bytes::{le_u16, le_u32};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::{Png, PngError};
+
+/// The private chunk a ZIP is hidden in with [`Placement::Chunk`]:
+/// ancillary, private and safe to copy.
+pub const ARCHIVE_CHUNK: &str = "arCh";
+
+const LOCAL_HEADER: &[u8] = b"PK\x03\x04";
+const CENTRAL_HEADER: &[u8] = b"PK\x01\x02";
+const END_OF_DIRECTORY: &[u8] = b"PK\x05\x06";
+const END_OF_DIRECTORY_LEN: usize = 22;
+const CENTRAL_HEADER_LEN: usize = 46;
+const LOCAL_HEADER_LEN: usize = 30;
+
+/// Why a PNG/ZIP polyglot could not be built or does not verify.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PolyglotError {
+    /// The ZIP directory is missing or damaged.
+    InvalidZip(String),
+    /// ZIP64 archives keep their offsets elsewhere; they are not supported.
+    Zip64,
+    /// The PNG does not end with `IEND`.
+    MissingEnd,
+    /// The PNG already has data after `IEND`.
+    HasTrailer(usize),
+    /// The archive comment cannot grow to cover the rest of the PNG.
+    CommentTooLong,
+    Png(PngError),
+}
+
+impl Display for PolyglotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolyglotError::InvalidZip(e) => write!(f, "invalid zip: {}", e),
+            PolyglotError::Zip64 => write!(f, "zip64 archives are not supported"),
+            PolyglotError::MissingEnd => write!(f, "png does not end with IEND"),
+            PolyglotError::HasTrailer(length) => write!(f, "png already has {} bytes after IEND", length),
+            PolyglotError::CommentTooLong => write!(f, "zip comment is too long to cover the end of the png"),
+            PolyglotError::Png(e) => write!(f, "invalid png: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PolyglotError {}
+
+impl From<PngError> for PolyglotError {
+    fn from(e: PngError) -> Self {
+        PolyglotError::Png(e)
+    }
+}
+
+/// Where the ZIP goes in the PNG.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Placement {
+    /// Appended after `IEND`, where every unzip tool looks first.
+    Trailer,
+    /// Inside an [`ARCHIVE_CHUNK`] just before `IEND`, so the PNG stays
+    /// well-formed; the archive comment is stretched over the bytes after it.
+    Chunk,
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trailer" => Ok(Placement::Trailer),
+            "chunk" => Ok(Placement::Chunk),
+            _ => Err(format!("unknown placement {:?}, expected trailer or chunk", s)),
+        }
+    }
+}
+
+/// A file in the ZIP central directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZipEntry {
+    pub name: String,
+    pub compressed_size: u32,
+    pub size: u32,
+    /// Offset of the local header from the start of the file.
+    pub local_offset: u32,
+    /// Offset of this entry's local-header-offset field in the file.
+    offset_field: usize,
+}
+
+/// The central directory of a ZIP, found from the end of `data` the way
+/// unzip tools find it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZipDirectory {
+    pub entries: Vec<ZipEntry>,
+    /// Offset of the end of central directory record.
+    pub end_offset: usize,
+    pub directory_offset: u32,
+    pub directory_size: u32,
+    pub comment_length: u16,
+}
+
+impl ZipDirectory {
+    pub fn parse(data: &[u8]) -> Result<ZipDirectory, PolyglotError> {
+        let invalid = |e: &str| PolyglotError::InvalidZip(e.to_string());
+        if data.len() < END_OF_DIRECTORY_LEN {
+            return Err(invalid("too short"));
+        }
+        let earliest = (data.len() - END_OF_DIRECTORY_LEN).saturating_sub(u16::MAX as usize);
+        let end_offset = (earliest..=data.len() - END_OF_DIRECTORY_LEN)
+            .rev()
+            .find(|&at| data[at..].starts_with(END_OF_DIRECTORY))
+            .ok_or_else(|| invalid("no end of central directory record"))?;
+        let end = &data[end_offset..];
+        let count = le_u16(end, 10);
+        let directory_size = le_u32(end, 12);
+        let directory_offset = le_u32(end, 16);
+        if count == u16::MAX || directory_size == u32::MAX || directory_offset == u32::MAX {
+            return Err(PolyglotError::Zip64);
+        }
+        if le_u16(end, 4) != 0 || le_u16(end, 6) != 0 {
+            return Err(invalid("multi-disk archives are not supported"));
+        }
+
+        let mut entries = vec![];
+        let mut at = directory_offset as usize;
+        for _ in 0..count {
+            if at + CENTRAL_HEADER_LEN > end_offset || !data[at..].starts_with(CENTRAL_HEADER) {
+                return Err(invalid("central directory entry not found where expected"));
+            }
+            let header = &data[at..];
+            let name_length = le_u16(header, 28) as usize;
+            let extra_length = le_u16(header, 30) as usize;
+            let comment_length = le_u16(header, 32) as usize;
+            let local_offset = le_u32(header, 42);
+            if local_offset == u32::MAX {
+                return Err(PolyglotError::Zip64);
+            }
+            let name_end = at + CENTRAL_HEADER_LEN + name_length;
+            if name_end > end_offset {
+                return Err(invalid("central directory entry runs past its end"));
+            }
+            entries.push(ZipEntry {
+                name: String::from_utf8_lossy(&data[at + CENTRAL_HEADER_LEN..name_end]).to_string(),
+                compressed_size: le_u32(header, 20),
+                size: le_u32(header, 24),
+                local_offset,
+                offset_field: at + 42,
+            });
+            at = name_end + extra_length + comment_length;
+        }
+        if at != directory_offset as usize + directory_size as usize || at > end_offset {
+            return Err(invalid("central directory size does not match its entries"));
+        }
+        Ok(ZipDirectory {
+            entries,
+            end_offset,
+            directory_offset,
+            directory_size,
+            comment_length: le_u16(end, 20),
+        })
+    }
+
+    /// Checks that every entry's local header is where the directory says,
+    /// with the same name.
+    pub fn check_local_headers(&self, data: &[u8]) -> Result<(), PolyglotError> {
+        for entry in &self.entries {
+            let at = entry.local_offset as usize;
+            let mismatch = || PolyglotError::InvalidZip(format!("local header of {} not found at offset {}", entry.name, at));
+            if at + LOCAL_HEADER_LEN > data.len() || !data[at..].starts_with(LOCAL_HEADER) {
+                return Err(mismatch());
+            }
+            let name_length = le_u16(data, at + 26) as usize;
+            let name = data.get(at + LOCAL_HEADER_LEN..at + LOCAL_HEADER_LEN + name_length).ok_or_else(mismatch)?;
+            if name != entry.name.as_bytes() {
+                return Err(mismatch());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Copies `zip` with every offset moved by `base`, for when it will start
+/// `base` bytes into a file, and its comment lengthened by `extra_comment`
+/// bytes that will follow it.
+pub fn relocate(zip: &[u8], base: u32, extra_comment: u16) -> Result<Vec<u8>, PolyglotError> {
+    let directory = ZipDirectory::parse(zip)?;
+    directory.check_local_headers(zip)?;
+    let overflow = || PolyglotError::InvalidZip("offsets overflow after relocation".to_string());
+    let mut relocated = zip.to_vec();
+    for entry in &directory.entries {
+        let offset = entry.local_offset.checked_add(base).ok_or_else(overflow)?;
+        relocated[entry.offset_field..entry.offset_field + 4].copy_from_slice(&offset.to_le_bytes());
+    }
+    let end = directory.end_offset;
+    let directory_offset = directory.directory_offset.checked_add(base).ok_or_else(overflow)?;
+    relocated[end + 16..end + 20].copy_from_slice(&directory_offset.to_le_bytes());
+    let comment_length = directory.comment_length.checked_add(extra_comment).ok_or(PolyglotError::CommentTooLong)?;
+    relocated[end + 20..end + 22].copy_from_slice(&comment_length.to_le_bytes());
+    Ok(relocated)
+}
+
+/// Puts `zip` into `png` so the result opens both as an image and as an
+/// archive.
+pub fn build(png: &Png, zip: &[u8], placement: Placement) -> Result<Png, PolyglotError> {
+    if !png.trailer().is_empty() {
+        return Err(PolyglotError::HasTrailer(png.trailer().len()));
+    }
+    let Some(end) = png.chunks().last().filter(|chunk| &chunk.chunk_type().bytes() == b"IEND") else {
+        return Err(PolyglotError::MissingEnd);
+    };
+    let mut polyglot = png.clone();
+    let offset = u32::try_from(png.trailer_offset()).map_err(|_| PolyglotError::InvalidZip("png is too large".to_string()))?;
+    match placement {
+        Placement::Trailer => polyglot.set_trailer(relocate(zip, offset, 0)?),
+        Placement::Chunk => {
+            // The chunk takes IEND's place and its data starts after the
+            // length and type; its CRC and IEND follow the archive comment.
+            let base = offset - end.as_bytes().len() as u32 + 8;
+            let data = relocate(zip, base, 4 + end.as_bytes().len() as u16)?;
+            polyglot.append_chunk(Chunk::new(ChunkType::from_str(ARCHIVE_CHUNK).unwrap(), data));
+        },
+    }
+    Ok(polyglot)
+}
+
+/// What [`verify`] found in each view of a polyglot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Verification {
+    pub chunks: usize,
+    pub entries: Vec<ZipEntry>,
+}
+
+/// Checks that `data` parses as a PNG with valid CRCs and as a ZIP whose
+/// entries are all where its directory says.
+pub fn verify(data: &[u8]) -> Result<Verification, PolyglotError> {
+    let png = Png::try_from(data)?;
+    let directory = ZipDirectory::parse(data)?;
+    directory.check_local_headers(data)?;
+    Ok(Verification { chunks: png.chunks().len(), entries: directory.entries })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stored (uncompressed) single-file archive, as `zip -0` writes it.
+    fn testing_zip(name: &str, contents: &[u8]) -> Vec<u8> {
+        let crc = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC).checksum(contents);
+        let mut common = vec![];
+        common.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&[0, 0]);
+
+        let mut zip = LOCAL_HEADER.to_vec();
+        zip.extend_from_slice(&common);
+        zip.extend_from_slice(name.as_bytes());
+        zip.extend_from_slice(contents);
+        let directory_offset = zip.len() as u32;
+        zip.extend_from_slice(CENTRAL_HEADER);
+        zip.extend_from_slice(&[20, 0]);
+        zip.extend_from_slice(&common);
+        zip.extend_from_slice(&[0; 14]);
+        zip.extend_from_slice(name.as_bytes());
+        let directory_size = zip.len() as u32 - directory_offset;
+        zip.extend_from_slice(END_OF_DIRECTORY);
+        zip.extend_from_slice(&[0, 0, 0, 0, 1, 0, 1, 0]);
+        zip.extend_from_slice(&directory_size.to_le_bytes());
+        zip.extend_from_slice(&directory_offset.to_le_bytes());
+        zip.extend_from_slice(&[0, 0]);
+        zip
+    }
+
+    fn testing_png() -> Png {
+        let chunk = |chunk_type: &str, data: &[u8]| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec());
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk("IDAT", &[0x78, 0x9c, 0x63, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01]),
+            chunk("IEND", &[]),
+        ])
+    }
+
+    #[test]
+    fn test_parse_zip() {
+        let zip = testing_zip("hello.txt", b"hello");
+        let directory = ZipDirectory::parse(&zip).unwrap();
+        assert_eq!(directory.entries.len(), 1);
+        assert_eq!(directory.entries[0].name, "hello.txt");
+        assert_eq!((directory.entries[0].local_offset, directory.entries[0].size), (0, 5));
+        assert!(directory.check_local_headers(&zip).is_ok());
+        assert!(ZipDirectory::parse(b"not a zip at all, clearly").is_err());
+    }
+
+    #[test]
+    fn test_trailer_polyglot() {
+        let zip = testing_zip("hello.txt", b"hello");
+        let polyglot = build(&testing_png(), &zip, Placement::Trailer).unwrap();
+        let bytes = polyglot.as_bytes();
+        let verification = verify(&bytes).unwrap();
+        assert_eq!(verification.chunks, 3);
+        assert_eq!(verification.entries[0].local_offset as usize, testing_png().as_bytes().len());
+        assert_eq!(Png::try_from(bytes.as_slice()).unwrap().trailer().len(), zip.len());
+        // The untouched archive would point into the PNG instead.
+        let mut naive = testing_png().as_bytes();
+        naive.extend_from_slice(&zip);
+        assert!(verify(&naive).is_err());
+    }
+
+    #[test]
+    fn test_chunk_polyglot() {
+        let zip = testing_zip("hello.txt", b"hello");
+        let polyglot = build(&testing_png(), &zip, Placement::Chunk).unwrap();
+        assert!(polyglot.trailer().is_empty());
+        assert_eq!(&polyglot.chunks()[2].chunk_type().to_string(), ARCHIVE_CHUNK);
+        let bytes = polyglot.as_bytes();
+        let verification = verify(&bytes).unwrap();
+        assert_eq!(verification.chunks, 4);
+        // The stretched comment ends exactly at the end of the file.
+        let directory = ZipDirectory::parse(&bytes).unwrap();
+        assert_eq!(directory.end_offset + END_OF_DIRECTORY_LEN + directory.comment_length as usize, bytes.len());
+    }
+
+    #[test]
+    fn test_existing_trailer() {
+        let mut png = testing_png();
+        png.set_trailer(b"junk".to_vec());
+        let zip = testing_zip("hello.txt", b"hello");
+        assert_eq!(build(&png, &zip, Placement::Trailer), Err(PolyglotError::HasTrailer(4)));
+    }
+}
@@ -0,0 +1,195 @@
+use std::fmt::{Display, Formatter};
+use std::sync::OnceLock;
+use crate::chunk::Chunk;
+
+/// Why [`Chunk::decode`] failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The chunk is not of a type the decoder handles.
+    WrongType { expected: String, found: String },
+    /// The chunk data is invalid for its type.
+    Invalid(String),
+}
+
+impl DecodeError {
+    pub fn invalid(e: impl Display) -> DecodeError {
+        DecodeError::Invalid(e.to_string())
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::WrongType { expected, found } => write!(f, "expected a {} chunk, found {}", expected, found),
+            DecodeError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// A typed view of one or more chunk types: how to read it from a
+/// [`Chunk`], write it back, and describe it in one line.
+///
+/// ```
+/// use rush_png::registry::{ChunkData, DecodeError, Registry};
+/// use rush_png::{Chunk, ChunkType};
+/// use std::str::FromStr;
+///
+/// struct Build(u32);
+///
+/// impl ChunkData for Build {
+///     const CHUNK_TYPES: &'static [&'static str] = &["buLd"];
+///
+///     fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+///         let bytes = chunk.data().try_into().map_err(|_| DecodeError::invalid("buLd is 4 bytes"))?;
+///         Ok(Build(u32::from_be_bytes(bytes)))
+///     }
+///
+///     fn encode(&self) -> Chunk {
+///         Chunk::new(ChunkType::from_str("buLd").unwrap(), self.0.to_be_bytes().to_vec())
+///     }
+///
+///     fn summary(&self) -> String {
+///         format!("build #{}", self.0)
+///     }
+/// }
+///
+/// let mut registry = Registry::default();
+/// registry.register::<Build>();
+/// let chunk = Build(42).encode();
+/// assert_eq!(chunk.decode::<Build>().unwrap().0, 42);
+/// assert_eq!(registry.summarize(&chunk), Some("build #42".to_string()));
+/// ```
+pub trait ChunkData: Sized {
+    /// The chunk types this decodes, e.g. `["tIME"]`.
+    const CHUNK_TYPES: &'static [&'static str];
+
+    /// Reads the chunk, which is known to be one of `CHUNK_TYPES`.
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError>;
+
+    fn encode(&self) -> Chunk;
+
+    /// A one-line description for `print`.
+    fn summary(&self) -> String;
+}
+
+type Summarize = fn(&Chunk) -> Result<String, DecodeError>;
+
+fn summarize_as<T: ChunkData>(chunk: &Chunk) -> Result<String, DecodeError> {
+    chunk.decode::<T>().map(|decoded| decoded.summary())
+}
+
+/// Chunk types with a registered [`ChunkData`] decoder. `Default` holds the
+/// chunk types this crate knows; register your own on top.
+#[derive(Clone)]
+pub struct Registry {
+    decoders: Vec<([u8; 4], Summarize)>,
+}
+
+impl Registry {
+    /// A registry with nothing registered.
+    pub fn new() -> Registry {
+        Registry { decoders: vec![] }
+    }
+
+    /// The built-in registry `print` uses.
+    pub fn builtin() -> &'static Registry {
+        static BUILTIN: OnceLock<Registry> = OnceLock::new();
+        BUILTIN.get_or_init(Registry::default)
+    }
+
+    /// Registers `T` for its chunk types, replacing earlier decoders for
+    /// the same types.
+    pub fn register<T: ChunkData>(&mut self) {
+        for chunk_type in T::CHUNK_TYPES {
+            let bytes: [u8; 4] = chunk_type.as_bytes().try_into().expect("chunk types are four bytes");
+            self.decoders.retain(|(other, _)| *other != bytes);
+            self.decoders.push((bytes, summarize_as::<T>));
+        }
+    }
+
+    pub fn is_registered(&self, chunk_type: &[u8; 4]) -> bool {
+        self.decoders.iter().any(|(other, _)| other == chunk_type)
+    }
+
+    /// The summary of `chunk`, or why it does not decode; `None` when no
+    /// decoder is registered for its type.
+    pub fn summarize(&self, chunk: &Chunk) -> Option<String> {
+        let chunk_type = chunk.chunk_type().bytes();
+        let (_, summarize) = self.decoders.iter().find(|(other, _)| *other == chunk_type)?;
+        Some(summarize(chunk).unwrap_or_else(|e| e.to_string()))
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        let mut registry = Registry::new();
+        registry.register::<crate::png::ImageHeader>();
+        registry.register::<crate::cgbi::CgbiHeader>();
+        registry.register::<crate::color::ColorChunk>();
+        registry.register::<crate::icc::IccProfile>();
+        registry.register::<crate::palette::SuggestedPalette>();
+        registry.register::<crate::physical::PixelDimensions>();
+        registry.register::<crate::physical::Offset>();
+        registry.register::<crate::physical::PhysicalScale>();
+        registry.register::<crate::physical::PixelCalibration>();
+        registry.register::<crate::time::Timestamp>();
+        registry.register::<crate::text::InternationalText>();
+        registry.register::<crate::exif::Exif>();
+        registry.register::<crate::apng::AnimationControl>();
+        registry.register::<crate::apng::FrameControl>();
+        registry.register::<crate::apng::FrameData>();
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::time::Timestamp;
+    use std::str::FromStr;
+
+    struct Loud(String);
+
+    impl ChunkData for Loud {
+        const CHUNK_TYPES: &'static [&'static str] = &["tIME"];
+
+        fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+            Ok(Loud(format!("{} bytes", chunk.length())))
+        }
+
+        fn encode(&self) -> Chunk {
+            Chunk::new(ChunkType::from_str("tIME").unwrap(), vec![])
+        }
+
+        fn summary(&self) -> String {
+            self.0.to_uppercase()
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let time = Timestamp::new(2024, 2, 29, 0, 0, 0).unwrap();
+        let chunk = time.encode();
+        assert_eq!(chunk.decode::<Timestamp>(), Ok(time));
+        let other = Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0; 4]);
+        assert_eq!(other.decode::<Timestamp>(),
+                   Err(DecodeError::WrongType { expected: "tIME".to_string(), found: "gAMA".to_string() }));
+        let broken = Chunk::new(ChunkType::from_str("tIME").unwrap(), vec![0; 3]);
+        assert_eq!(broken.decode::<Timestamp>(), Err(DecodeError::Invalid("tIME chunk has invalid length 3".to_string())));
+    }
+
+    #[test]
+    fn test_register() {
+        let chunk = Timestamp::new(2024, 2, 29, 0, 0, 0).unwrap().encode();
+        assert_eq!(Registry::new().summarize(&chunk), None);
+        let mut registry = Registry::default();
+        assert_eq!(registry.summarize(&chunk), Some("2024-02-29 00:00:00 UTC".to_string()));
+        registry.register::<Loud>();
+        assert_eq!(registry.summarize(&chunk), Some("7 BYTES".to_string()));
+        assert!(registry.is_registered(b"gAMA"));
+        assert!(!registry.is_registered(b"ruSt"));
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::chunk_type::ChunkType;
+use crate::polyglot::ARCHIVE_CHUNK;
+
+/// Ancillary chunks kept when no `drop` pattern is given: the ones that
+/// change how the image renders or animates, and the default message and
+/// archive chunks this tool writes.
+pub const DEFAULT_KEEP: [&str; 15] = [
+    "tRNS", "gAMA", "cHRM", "sRGB", "iCCP", "sBIT", "cICP", "mDCV", "cLLI", "pHYs",
+    "acTL", "fcTL", "fdAT",
+    "ruSt", ARCHIVE_CHUNK,
+];
+
+/// A `--keep`/`--drop` pattern, either a chunk type name (`?` matches any
+/// single letter, e.g. `t???`) or one of the property bits of `ChunkType`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChunkPattern {
+    Name([u8; 4]),
+    Critical,
+    Ancillary,
+    Public,
+    Private,
+    SafeToCopy,
+    UnsafeToCopy,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParsePatternError {
+    pattern: String,
+}
+
+impl Display for ParsePatternError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid chunk pattern: {:?}", self.pattern)
+    }
+}
+
+impl std::error::Error for ParsePatternError {}
+
+impl FromStr for ChunkPattern {
+    type Err = ParsePatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "critical" => return Ok(ChunkPattern::Critical),
+            "ancillary" => return Ok(ChunkPattern::Ancillary),
+            "public" => return Ok(ChunkPattern::Public),
+            "private" => return Ok(ChunkPattern::Private),
+            "safe-to-copy" => return Ok(ChunkPattern::SafeToCopy),
+            "unsafe-to-copy" => return Ok(ChunkPattern::UnsafeToCopy),
+            _ => {}
+        }
+        if s.len() == 4 && s.bytes().all(|c| c.is_ascii_alphabetic() || c == b'?') {
+            Ok(ChunkPattern::Name(<[u8; 4]>::try_from(s.as_bytes()).unwrap()))
+        } else {
+            Err(ParsePatternError { pattern: s.to_string() })
+        }
+    }
+}
+
+impl ChunkPattern {
+    pub fn matches(&self, chunk_type: &ChunkType) -> bool {
+        match self {
+            ChunkPattern::Name(name) => name
+                .iter()
+                .zip(chunk_type.bytes().iter())
+                .all(|(p, c)| *p == b'?' || p == c),
+            ChunkPattern::Critical => chunk_type.is_critical(),
+            ChunkPattern::Ancillary => !chunk_type.is_critical(),
+            ChunkPattern::Public => chunk_type.is_public(),
+            ChunkPattern::Private => !chunk_type.is_public(),
+            ChunkPattern::SafeToCopy => chunk_type.is_safe_to_copy(),
+            ChunkPattern::UnsafeToCopy => !chunk_type.is_safe_to_copy(),
+        }
+    }
+}
+
+/// Decides which chunks `strip` removes. A `keep` match always wins, and
+/// critical chunks are never stripped since the image would stop decoding.
+/// With no `drop` patterns every ancillary chunk outside [`DEFAULT_KEEP`] is
+/// a candidate; `--drop ancillary` takes those too.
+#[derive(Debug, Default)]
+pub struct StripFilter {
+    keep: Vec<ChunkPattern>,
+    drop: Vec<ChunkPattern>,
+}
+
+impl StripFilter {
+    pub fn new(keep: Vec<ChunkPattern>, drop: Vec<ChunkPattern>) -> StripFilter {
+        StripFilter { keep, drop }
+    }
+
+    pub fn should_strip(&self, chunk_type: &ChunkType) -> bool {
+        if chunk_type.is_critical() || self.keep.iter().any(|p| p.matches(chunk_type)) {
+            return false;
+        }
+        if self.drop.is_empty() {
+            return !DEFAULT_KEEP.contains(&chunk_type.to_string().as_str());
+        }
+        self.drop.iter().any(|p| p.matches(chunk_type))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(keep: &[&str], drop: &[&str]) -> StripFilter {
+        let parse = |v: &[&str]| v.iter().map(|s| ChunkPattern::from_str(s).unwrap()).collect();
+        StripFilter::new(parse(keep), parse(drop))
+    }
+
+    fn chunk_type(s: &str) -> ChunkType {
+        ChunkType::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_pattern_from_str() {
+        assert_eq!(ChunkPattern::from_str("tEXt").unwrap(), ChunkPattern::Name(*b"tEXt"));
+        assert_eq!(ChunkPattern::from_str("Safe-To-Copy").unwrap(), ChunkPattern::SafeToCopy);
+        assert!(ChunkPattern::from_str("tEXtt").is_err());
+        assert!(ChunkPattern::from_str("t*Xt").is_err());
+    }
+
+    #[test]
+    fn test_wildcard_name() {
+        let pattern = ChunkPattern::from_str("?TXt").unwrap();
+        assert!(pattern.matches(&chunk_type("iTXt")));
+        assert!(pattern.matches(&chunk_type("zTXt")));
+        assert!(!pattern.matches(&chunk_type("tEXt")));
+    }
+
+    #[test]
+    fn test_default_keeps_rendering_and_messages() {
+        let filter = filter(&[], &[]);
+        assert!(filter.should_strip(&chunk_type("tIME")));
+        assert!(filter.should_strip(&chunk_type("eXIf")));
+        assert!(filter.should_strip(&chunk_type("prVt")));
+        for name in ["tRNS", "gAMA", "sRGB", "iCCP", "fdAT", "ruSt", "arCh", "IDAT"] {
+            assert!(!filter.should_strip(&chunk_type(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_drop_ancillary_overrides_default() {
+        let filter = filter(&[], &["ancillary"]);
+        assert!(filter.should_strip(&chunk_type("tRNS")));
+        assert!(filter.should_strip(&chunk_type("ruSt")));
+    }
+
+    #[test]
+    fn test_keep_wins_over_drop() {
+        let filter = filter(&["ruSt"], &["private", "tEXt"]);
+        assert!(!filter.should_strip(&chunk_type("ruSt")));
+        assert!(filter.should_strip(&chunk_type("prVt")));
+        assert!(filter.should_strip(&chunk_type("tEXt")));
+        assert!(!filter.should_strip(&chunk_type("tIME")));
+    }
+
+    #[test]
+    fn test_critical_never_stripped() {
+        let filter = filter(&[], &["IDAT", "critical"]);
+        assert!(!filter.should_strip(&chunk_type("IDAT")));
+        assert!(!filter.should_strip(&chunk_type("IHDR")));
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use std::str::FromStr;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::registry::{ChunkData, DecodeError};
+use crate::xmp::{Xmp, XMP_KEYWORD};
+
+/// Why an `iTXt` chunk could not be read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TextError {
+    /// Keywords are 1 to 79 Latin-1 characters.
+    InvalidKeyword,
+    /// A null separator is missing.
+    Truncated,
+    /// The compression flag or method is unknown, or the data does not
+    /// inflate.
+    InvalidCompression,
+    /// The text is not UTF-8.
+    InvalidUtf8,
+}
+
+impl Display for TextError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextError::InvalidKeyword => write!(f, "text keyword must be 1 to 79 characters"),
+            TextError::Truncated => write!(f, "text chunk is truncated"),
+            TextError::InvalidCompression => write!(f, "text chunk has invalid compressed data"),
+            TextError::InvalidUtf8 => write!(f, "international text is not valid utf-8"),
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+/// Inflates zlib data, as used by `zTXt`, `iTXt` and `iCCP`.
+pub fn inflate(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut ret = vec![];
+    ZlibDecoder::new(data).read_to_end(&mut ret)?;
+    Ok(ret)
+}
+
+/// Deflates data into a zlib stream.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// The `iTXt` chunk: UTF-8 text with a language tag, optionally
+/// compressed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InternationalText {
+    pub keyword: String,
+    pub compressed: bool,
+    pub language_tag: String,
+    pub translated_keyword: String,
+    pub text: String,
+}
+
+fn split_null(data: &[u8]) -> Result<(&[u8], &[u8]), TextError> {
+    let idx = data.iter().position(|&b| b == 0).ok_or(TextError::Truncated)?;
+    Ok((&data[..idx], &data[idx + 1..]))
+}
+
+impl TryFrom<&[u8]> for InternationalText {
+    type Error = TextError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let (keyword, rest) = split_null(data)?;
+        if keyword.is_empty() || keyword.len() > 79 {
+            return Err(TextError::InvalidKeyword);
+        }
+        let (&flag, rest) = rest.split_first().ok_or(TextError::Truncated)?;
+        let (&method, rest) = rest.split_first().ok_or(TextError::Truncated)?;
+        let (language_tag, rest) = split_null(rest)?;
+        let (translated_keyword, text) = split_null(rest)?;
+        let text = match (flag, method) {
+            (0, _) => text.to_vec(),
+            (1, 0) => inflate(text).map_err(|_| TextError::InvalidCompression)?,
+            _ => return Err(TextError::InvalidCompression),
+        };
+        let utf8 = |bytes: Vec<u8>| String::from_utf8(bytes).map_err(|_| TextError::InvalidUtf8);
+        Ok(InternationalText {
+            keyword: keyword.iter().map(|&b| b as char).collect(),
+            compressed: flag == 1,
+            language_tag: utf8(language_tag.to_vec())?,
+            translated_keyword: utf8(translated_keyword.to_vec())?,
+            text: utf8(text)?,
+        })
+    }
+}
+
+impl InternationalText {
+    /// Uncompressed text with no language tag.
+    pub fn new(keyword: &str, text: &str) -> InternationalText {
+        InternationalText {
+            keyword: keyword.to_string(),
+            compressed: false,
+            language_tag: String::new(),
+            translated_keyword: String::new(),
+            text: text.to_string(),
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data: Vec<u8> = self.keyword.chars().map(|c| c as u8).collect();
+        data.push(0);
+        data.push(self.compressed as u8);
+        data.push(0);
+        data.extend_from_slice(self.language_tag.as_bytes());
+        data.push(0);
+        data.extend_from_slice(self.translated_keyword.as_bytes());
+        data.push(0);
+        if self.compressed {
+            data.extend_from_slice(&deflate(self.text.as_bytes()));
+        } else {
+            data.extend_from_slice(self.text.as_bytes());
+        }
+        Chunk::new(ChunkType::from_str("iTXt").unwrap(), data)
+    }
+}
+
+impl ChunkData for InternationalText {
+    const CHUNK_TYPES: &'static [&'static str] = &["iTXt"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        InternationalText::try_from(chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        self.to_chunk()
+    }
+
+    fn summary(&self) -> String {
+        if self.keyword != XMP_KEYWORD {
+            let text: String = self.text.chars().take(40).collect();
+            let ellipsis = if text.len() < self.text.len() { "..." } else { "" };
+            return format!("{}: {}{}", self.keyword, text, ellipsis);
+        }
+        match Xmp::parse(&self.text) {
+            Ok(xmp) => format!("xmp, {} properties", xmp.properties().len()),
+            Err(_) => "invalid xmp packet".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut text = InternationalText::new("Title", "月へ連れてって");
+        text.language_tag = "ja".to_string();
+        assert_eq!(InternationalText::try_from(text.to_chunk().data()).unwrap(), text);
+        text.compressed = true;
+        assert_eq!(InternationalText::try_from(text.to_chunk().data()).unwrap(), text);
+    }
+
+    #[test]
+    fn test_invalid_text() {
+        assert_eq!(InternationalText::try_from(&b"\0\0\0\0\0"[..]), Err(TextError::InvalidKeyword));
+        assert_eq!(InternationalText::try_from(&b"Title\0\0\0en"[..]), Err(TextError::Truncated));
+        assert_eq!(InternationalText::try_from(&b"Title\0\x01\0\0\0abc"[..]), Err(TextError::InvalidCompression));
+        assert_eq!(InternationalText::try_from(&b"Title\0\0\0\0\0\xff"[..]), Err(TextError::InvalidUtf8));
+    }
+}
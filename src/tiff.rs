@@ -0,0 +1,454 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use crate::container::{self, Container, Format, Segment};
+use crate::Error;
+
+/// Why a byte slice could not be read as a `Tiff`, or a message could not be
+/// found in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TiffError {
+    /// The file does not start with `II*\0` or `MM\0*`.
+    NotTiff,
+    /// BigTIFF and other versions besides 42 are not supported.
+    UnsupportedVersion(u16),
+    /// The IFD at `offset` runs past the end of the file.
+    Truncated { offset: usize },
+    /// The IFD chain loops back to the IFD at `offset`.
+    IfdLoop { offset: usize },
+    /// The message tag's value does not fit in a TIFF.
+    MessageTooLarge,
+    /// No message is stored under this key.
+    MessageNotFound(String),
+}
+
+impl Display for TiffError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TiffError::NotTiff => write!(f, "not a tiff: bad byte order mark"),
+            TiffError::UnsupportedVersion(version) => write!(f, "unsupported tiff version {}", version),
+            TiffError::Truncated { offset } => write!(f, "tiff ifd at offset {} is truncated", offset),
+            TiffError::IfdLoop { offset } => write!(f, "tiff ifd chain loops back to offset {}", offset),
+            TiffError::MessageTooLarge => write!(f, "message is too large for a tiff"),
+            TiffError::MessageNotFound(key) => write!(f, "no {} message found", key),
+        }
+    }
+}
+
+impl std::error::Error for TiffError {}
+
+/// One 12-byte IFD entry. `value` holds the raw value or, when it does not
+/// fit in four bytes, the offset of the value, both in file byte order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IfdEntry {
+    pub tag: u16,
+    pub field_type: u16,
+    pub count: u32,
+    value: [u8; 4],
+}
+
+impl IfdEntry {
+    /// Size of the value in bytes, or `None` for unknown field types.
+    pub fn value_len(&self) -> Option<usize> {
+        let size = match self.field_type {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 | 13 => 4,
+            5 | 10 | 12 => 8,
+            _ => return None,
+        };
+        Some(size * self.count as usize)
+    }
+}
+
+/// An image file directory and the offset of the next one (0 for the last).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ifd {
+    pub offset: usize,
+    pub entries: Vec<IfdEntry>,
+    pub next: u32,
+}
+
+impl Ifd {
+    fn len(&self) -> usize {
+        2 + self.entries.len() * 12 + 4
+    }
+}
+
+/// Messages live in a private tag of type UNDEFINED in the first IFD. Its
+/// value is `RUSH` followed by records of key, big-endian u32 length and
+/// data.
+pub const MESSAGE_TAG: u16 = 0xFD52;
+const MESSAGE_MAGIC: &[u8; 4] = b"RUSH";
+const UNDEFINED: u16 = 7;
+
+/// A baseline TIFF, kept as raw bytes with its IFD chain parsed.
+///
+/// Editing never moves existing data. The first IFD is rewritten at the end
+/// of the file with the message tag and the header points to it, so strip
+/// offsets and other absolute offsets stay valid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tiff {
+    bytes: Vec<u8>,
+    little_endian: bool,
+    ifds: Vec<Ifd>,
+}
+
+impl TryFrom<&[u8]> for Tiff {
+    type Error = TiffError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let little_endian = if value.starts_with(b"II") {
+            true
+        } else if value.starts_with(b"MM") {
+            false
+        } else {
+            return Err(TiffError::NotTiff);
+        };
+        let mut tiff = Tiff { bytes: value.to_vec(), little_endian, ifds: vec![] };
+        if value.len() < 8 {
+            return Err(TiffError::Truncated { offset: 0 });
+        }
+        match tiff.u16_at(2) {
+            42 => {},
+            version => return Err(TiffError::UnsupportedVersion(version)),
+        }
+        tiff.ifds = tiff.read_ifds()?;
+        Ok(tiff)
+    }
+}
+
+impl Tiff {
+    fn u16_at(&self, at: usize) -> u16 {
+        let bytes = [self.bytes[at], self.bytes[at + 1]];
+        if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) }
+    }
+
+    fn u32_at(&self, at: usize) -> u32 {
+        let bytes = self.bytes[at..at + 4].try_into().unwrap();
+        if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) }
+    }
+
+    fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        if self.little_endian { value.to_le_bytes() } else { value.to_be_bytes() }
+    }
+
+    fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        if self.little_endian { value.to_le_bytes() } else { value.to_be_bytes() }
+    }
+
+    fn read_ifds(&self) -> Result<Vec<Ifd>, TiffError> {
+        let mut ifds = vec![];
+        let mut visited = HashSet::new();
+        let mut offset = self.u32_at(4) as usize;
+        while offset != 0 {
+            if !visited.insert(offset) {
+                return Err(TiffError::IfdLoop { offset });
+            }
+            if offset + 2 > self.bytes.len() {
+                return Err(TiffError::Truncated { offset });
+            }
+            let count = self.u16_at(offset) as usize;
+            if offset + 2 + count * 12 + 4 > self.bytes.len() {
+                return Err(TiffError::Truncated { offset });
+            }
+            let entries = (0..count)
+                .map(|i| {
+                    let at = offset + 2 + i * 12;
+                    IfdEntry {
+                        tag: self.u16_at(at),
+                        field_type: self.u16_at(at + 2),
+                        count: self.u32_at(at + 4),
+                        value: self.bytes[at + 8..at + 12].try_into().unwrap(),
+                    }
+                })
+                .collect();
+            let next = self.u32_at(offset + 2 + count * 12);
+            ifds.push(Ifd { offset, entries, next });
+            offset = next as usize;
+        }
+        Ok(ifds)
+    }
+
+    pub fn ifds(&self) -> &[Ifd] {
+        &self.ifds
+    }
+
+    pub fn is_little_endian(&self) -> bool {
+        self.little_endian
+    }
+
+    /// Where the value of `entry` is stored: inline in the entry or at the
+    /// offset it holds. `None` if it is out of bounds.
+    fn value_range(&self, ifd: &Ifd, index: usize) -> Option<std::ops::Range<usize>> {
+        let entry = &ifd.entries[index];
+        let len = entry.value_len()?;
+        let start = if len <= 4 {
+            ifd.offset + 2 + index * 12 + 8
+        } else {
+            self.u32_at(ifd.offset + 2 + index * 12 + 8) as usize
+        };
+        (start + len <= self.bytes.len()).then_some(start..start + len)
+    }
+
+    /// The value of `tag` in the first IFD as an unsigned integer, for SHORT
+    /// and LONG tags.
+    pub fn tag_u32(&self, tag: u16) -> Option<u32> {
+        let ifd = self.ifds.first()?;
+        let index = ifd.entries.iter().position(|entry| entry.tag == tag)?;
+        let range = self.value_range(ifd, index)?;
+        match ifd.entries[index].field_type {
+            3 => Some(self.u16_at(range.start) as u32),
+            4 => Some(self.u32_at(range.start)),
+            _ => None,
+        }
+    }
+
+    fn message_value(&self) -> Option<std::ops::Range<usize>> {
+        let ifd = self.ifds.first()?;
+        let index = ifd.entries.iter().position(|entry| entry.tag == MESSAGE_TAG)?;
+        self.value_range(ifd, index).filter(|range| self.bytes[range.clone()].starts_with(MESSAGE_MAGIC))
+    }
+
+    /// The `(key, message)` records stored in the message tag.
+    pub fn messages(&self) -> Vec<([u8; 4], Vec<u8>)> {
+        let mut ret = vec![];
+        if let Some(range) = self.message_value() {
+            let value = &self.bytes[range];
+            let mut idx = MESSAGE_MAGIC.len();
+            while idx + 8 <= value.len() {
+                let key = value[idx..idx + 4].try_into().unwrap();
+                let len = u32::from_be_bytes(value[idx + 4..idx + 8].try_into().unwrap()) as usize;
+                let Some(data) = value.get(idx + 8..idx + 8 + len) else { break };
+                ret.push((key, data.to_vec()));
+                idx += 8 + len;
+            }
+        }
+        ret
+    }
+
+    pub fn message(&self, key: &[u8; 4]) -> Option<Vec<u8>> {
+        self.messages().into_iter().find(|(k, _)| k == key).map(|(_, data)| data)
+    }
+
+    pub fn insert_message(&mut self, key: &[u8; 4], message: &[u8]) -> Result<(), TiffError> {
+        let mut messages = self.messages();
+        match messages.iter_mut().find(|(k, _)| k == key) {
+            Some((_, data)) => *data = message.to_vec(),
+            None => messages.push((*key, message.to_vec())),
+        }
+        self.write_messages(&messages)
+    }
+
+    pub fn remove_message(&mut self, key: &[u8; 4]) -> Result<(), TiffError> {
+        let mut messages = self.messages();
+        let idx = messages
+            .iter()
+            .position(|(k, _)| k == key)
+            .ok_or_else(|| TiffError::MessageNotFound(String::from_utf8_lossy(key).to_string()))?;
+        messages.remove(idx);
+        self.write_messages(&messages)
+    }
+
+    fn pad_to_word(&mut self) {
+        if self.bytes.len() % 2 == 1 {
+            self.bytes.push(0);
+        }
+    }
+
+    /// Rewrites the first IFD at the end of the file with the message tag
+    /// set to `messages`, or without it when there are none. If the old IFD
+    /// and message value were written by us at the end of the file, they
+    /// are dropped first so repeated edits do not grow the file.
+    fn write_messages(&mut self, messages: &[([u8; 4], Vec<u8>)]) -> Result<(), TiffError> {
+        let Some(first) = self.ifds.first().cloned() else {
+            return Err(TiffError::Truncated { offset: self.u32_at(4) as usize });
+        };
+        let old_value = self.message_value();
+        if first.offset + first.len() == self.bytes.len() {
+            self.bytes.truncate(first.offset);
+            if let Some(range) = old_value.filter(|range| range.end.div_ceil(2) * 2 == self.bytes.len()) {
+                self.bytes.truncate(range.start);
+            }
+        }
+
+        let mut entries: Vec<IfdEntry> = first.entries.into_iter().filter(|entry| entry.tag != MESSAGE_TAG).collect();
+        if !messages.is_empty() {
+            let mut value = MESSAGE_MAGIC.to_vec();
+            for (key, data) in messages {
+                value.extend_from_slice(key);
+                value.extend_from_slice(&u32::try_from(data.len()).map_err(|_| TiffError::MessageTooLarge)?.to_be_bytes());
+                value.extend_from_slice(data);
+            }
+            let count = u32::try_from(value.len()).map_err(|_| TiffError::MessageTooLarge)?;
+            self.pad_to_word();
+            let offset = u32::try_from(self.bytes.len()).map_err(|_| TiffError::MessageTooLarge)?;
+            self.bytes.extend_from_slice(&value);
+            let entry = IfdEntry { tag: MESSAGE_TAG, field_type: UNDEFINED, count, value: self.u32_bytes(offset) };
+            let idx = entries.partition_point(|other| other.tag < MESSAGE_TAG);
+            entries.insert(idx, entry);
+        }
+
+        self.pad_to_word();
+        let offset = self.bytes.len();
+        let mut ifd = self.u16_bytes(entries.len() as u16).to_vec();
+        for entry in &entries {
+            ifd.extend_from_slice(&self.u16_bytes(entry.tag));
+            ifd.extend_from_slice(&self.u16_bytes(entry.field_type));
+            ifd.extend_from_slice(&self.u32_bytes(entry.count));
+            ifd.extend_from_slice(&entry.value);
+        }
+        ifd.extend_from_slice(&self.u32_bytes(first.next));
+        self.bytes.extend_from_slice(&ifd);
+        let pointer = self.u32_bytes(u32::try_from(offset).map_err(|_| TiffError::MessageTooLarge)?);
+        self.bytes[4..8].copy_from_slice(&pointer);
+        self.ifds[0] = Ifd { offset, entries, next: first.next };
+        Ok(())
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+}
+
+impl Container for Tiff {
+    fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Tiff::try_from(bytes)?)
+    }
+
+    fn format(&self) -> Format {
+        Format::Tiff
+    }
+
+    fn segments(&self) -> Vec<Segment> {
+        let byte_order = if self.little_endian { "little-endian" } else { "big-endian" };
+        let mut ret = vec![Segment { offset: 0, name: "Header".to_string(), length: 8, summary: Some(byte_order.to_string()) }];
+        for (idx, ifd) in self.ifds.iter().enumerate() {
+            let mut summary = format!("{} entries", ifd.entries.len());
+            if idx == 0 {
+                if let (Some(width), Some(height)) = (self.tag_u32(256), self.tag_u32(257)) {
+                    summary = format!("{}, {}x{}", summary, width, height);
+                }
+            }
+            ret.push(Segment { offset: ifd.offset, name: format!("IFD{}", idx), length: ifd.len(), summary: Some(summary) });
+        }
+        if let Some(range) = self.message_value() {
+            let keys: Vec<String> = self.messages().iter().map(|(key, _)| String::from_utf8_lossy(key).to_string()).collect();
+            ret.push(Segment {
+                offset: range.start,
+                name: format!("Tag {}", MESSAGE_TAG),
+                length: range.len(),
+                summary: Some(format!("messages: {}", keys.join(", "))),
+            });
+        }
+        ret.sort_by_key(|segment| segment.offset);
+        ret
+    }
+
+    fn insert_message(&mut self, key: &str, message: &[u8]) -> Result<Vec<String>, Error> {
+        Tiff::insert_message(self, &container::parse_key(key)?, message)?;
+        Ok(vec![])
+    }
+
+    fn extract_message(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.message(&container::parse_key(key)?))
+    }
+
+    fn remove_message(&mut self, key: &str) -> Result<Vec<String>, Error> {
+        Tiff::remove_message(self, &container::parse_key(key)?)?;
+        Ok(vec![])
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 2x1 grayscale image with its strip at offset 8 and the IFD after
+    /// it, written in either byte order.
+    fn testing_tiff(little_endian: bool) -> Vec<u8> {
+        let u16_bytes = |v: u16| if little_endian { v.to_le_bytes() } else { v.to_be_bytes() };
+        let u32_bytes = |v: u32| if little_endian { v.to_le_bytes() } else { v.to_be_bytes() };
+        let mut bytes = if little_endian { b"II".to_vec() } else { b"MM".to_vec() };
+        bytes.extend_from_slice(&u16_bytes(42));
+        bytes.extend_from_slice(&u32_bytes(10));
+        bytes.extend_from_slice(&[0x10, 0xF0]);
+        let entries: [(u16, u16, u32); 4] = [(256, 3, 2), (257, 3, 1), (273, 4, 8), (279, 4, 2)];
+        bytes.extend_from_slice(&u16_bytes(entries.len() as u16));
+        for (tag, field_type, value) in entries {
+            bytes.extend_from_slice(&u16_bytes(tag));
+            bytes.extend_from_slice(&u16_bytes(field_type));
+            bytes.extend_from_slice(&u32_bytes(1));
+            if field_type == 3 {
+                bytes.extend_from_slice(&u16_bytes(value as u16));
+                bytes.extend_from_slice(&[0, 0]);
+            } else {
+                bytes.extend_from_slice(&u32_bytes(value));
+            }
+        }
+        bytes.extend_from_slice(&u32_bytes(0));
+        bytes
+    }
+
+    #[test]
+    fn test_parse() {
+        for little_endian in [true, false] {
+            let tiff = Tiff::try_from(testing_tiff(little_endian).as_slice()).unwrap();
+            assert_eq!(tiff.ifds().len(), 1);
+            assert_eq!((tiff.tag_u32(256), tiff.tag_u32(257), tiff.tag_u32(273)), (Some(2), Some(1), Some(8)));
+        }
+        assert_eq!(Tiff::try_from(&b"XX*\0"[..]), Err(TiffError::NotTiff));
+        assert_eq!(Tiff::try_from(&b"II+\0\x08\0\0\0"[..]), Err(TiffError::UnsupportedVersion(43)));
+        assert_eq!(Tiff::try_from(&testing_tiff(true)[..40]), Err(TiffError::Truncated { offset: 10 }));
+
+        let mut bytes = testing_tiff(true);
+        let next = bytes.len() - 4;
+        bytes[next] = 10;
+        assert_eq!(Tiff::try_from(bytes.as_slice()), Err(TiffError::IfdLoop { offset: 10 }));
+    }
+
+    #[test]
+    fn test_messages() {
+        for little_endian in [true, false] {
+            let original = testing_tiff(little_endian);
+            let mut tiff = Tiff::try_from(original.as_slice()).unwrap();
+            tiff.insert_message(b"ruSt", b"hello").unwrap();
+            tiff.insert_message(b"abCd", b"hi").unwrap();
+
+            let mut tiff = Tiff::try_from(tiff.as_bytes().as_slice()).unwrap();
+            assert_eq!(tiff.message(b"ruSt"), Some(b"hello".to_vec()));
+            assert_eq!(tiff.message(b"abCd"), Some(b"hi".to_vec()));
+            assert_eq!(tiff.tag_u32(273), Some(8));
+            assert_eq!(&tiff.as_bytes()[8..10], &[0x10, 0xF0]);
+            let tags: Vec<u16> = tiff.ifds()[0].entries.iter().map(|entry| entry.tag).collect();
+            assert_eq!(tags, vec![256, 257, 273, 279, MESSAGE_TAG]);
+            assert_eq!(tiff.ifds()[0].offset % 2, 0);
+
+            tiff.insert_message(b"ruSt", b"bye").unwrap();
+            let mut tiff = Tiff::try_from(tiff.as_bytes().as_slice()).unwrap();
+            let keys: Vec<[u8; 4]> = tiff.messages().into_iter().map(|(key, _)| key).collect();
+            assert_eq!(keys, vec![*b"ruSt", *b"abCd"]);
+            assert_eq!(tiff.message(b"ruSt"), Some(b"bye".to_vec()));
+
+            tiff.remove_message(b"ruSt").unwrap();
+            tiff.remove_message(b"abCd").unwrap();
+            assert_eq!(tiff.as_bytes(), original);
+            assert_eq!(tiff.remove_message(b"ruSt"), Err(TiffError::MessageNotFound("ruSt".to_string())));
+        }
+    }
+
+    #[test]
+    fn test_messages_keep_trailing_data() {
+        let mut bytes = testing_tiff(true);
+        bytes.extend_from_slice(b"trailing");
+        let mut tiff = Tiff::try_from(bytes.as_slice()).unwrap();
+        tiff.insert_message(b"ruSt", b"hello").unwrap();
+        assert_eq!(tiff.as_bytes()[8..bytes.len()], bytes[8..]);
+        let tiff = Tiff::try_from(tiff.as_bytes().as_slice()).unwrap();
+        assert_eq!(tiff.message(b"ruSt"), Some(b"hello".to_vec()));
+        assert_eq!(tiff.tag_u32(256), Some(2));
+    }
+}
@@ -0,0 +1,184 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::registry::{ChunkData, DecodeError};
+
+/// Why a `tIME` chunk or timestamp is invalid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TimeError {
+    /// `tIME` is always 7 bytes.
+    InvalidLength(usize),
+    /// A field is out of range, e.g. February 30th.
+    InvalidDate(String),
+}
+
+impl Display for TimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeError::InvalidLength(length) => write!(f, "tIME chunk has invalid length {}", length),
+            TimeError::InvalidDate(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for TimeError {}
+
+fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// `tIME`: the last modification time, always in UTC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    /// Up to 60 to allow for leap seconds.
+    pub second: u8,
+}
+
+impl TryFrom<&[u8]> for Timestamp {
+    type Error = TimeError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != 7 {
+            return Err(TimeError::InvalidLength(data.len()));
+        }
+        Timestamp::new(u16::from_be_bytes([data[0], data[1]]), data[2], data[3], data[4], data[5], data[6])
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = TimeError;
+
+    /// Parses `YYYY-MM-DDTHH:MM:SS`, with a space or `T` between the date
+    /// and time and an optional trailing `Z`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || TimeError::InvalidDate(format!("time {:?} must look like 2024-02-29T13:45:00", s));
+        let (date, time) = s.trim_end_matches('Z').split_once(['T', ' ']).ok_or_else(error)?;
+        let fields: Vec<&str> = date.split('-').chain(time.split(':')).collect();
+        if fields.len() != 6 || fields.iter().any(|field| field.is_empty() || !field.bytes().all(|b| b.is_ascii_digit())) {
+            return Err(error());
+        }
+        let number = |i: usize| fields[i].parse::<u8>().map_err(|_| error());
+        Timestamp::new(fields[0].parse().map_err(|_| error())?, number(1)?, number(2)?, number(3)?, number(4)?, number(5)?)
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+               self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+impl ChunkData for Timestamp {
+    const CHUNK_TYPES: &'static [&'static str] = &["tIME"];
+
+    fn parse(chunk: &Chunk) -> Result<Self, DecodeError> {
+        Timestamp::try_from(chunk.data()).map_err(DecodeError::invalid)
+    }
+
+    fn encode(&self) -> Chunk {
+        self.to_chunk()
+    }
+
+    fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Timestamp {
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Result<Timestamp, TimeError> {
+        if !(1..=12).contains(&month) {
+            return Err(TimeError::InvalidDate(format!("month {} is not 1 to 12", month)));
+        }
+        if day == 0 || day > days_in_month(year, month) {
+            return Err(TimeError::InvalidDate(format!("{:04}-{:02} has no day {}", year, month, day)));
+        }
+        if hour > 23 || minute > 59 || second > 60 {
+            return Err(TimeError::InvalidDate(format!("{:02}:{:02}:{:02} is not a time of day", hour, minute, second)));
+        }
+        Ok(Timestamp { year, month, day, hour, minute, second })
+    }
+
+    /// The UTC time `secs` seconds after the Unix epoch.
+    pub fn from_unix(secs: u64) -> Timestamp {
+        let (days, rem) = (secs / 86400, secs % 86400);
+        // Days to civil date, from Howard Hinnant's date algorithms.
+        let z = days as i64 + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+        let year = (yoe + era * 400 + (month <= 2) as i64) as u16;
+        Timestamp {
+            year,
+            month,
+            day,
+            hour: (rem / 3600) as u8,
+            minute: (rem % 3600 / 60) as u8,
+            second: (rem % 60) as u8,
+        }
+    }
+
+    pub fn now() -> Timestamp {
+        Timestamp::from_unix(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = self.year.to_be_bytes().to_vec();
+        data.extend_from_slice(&[self.month, self.day, self.hour, self.minute, self.second]);
+        Chunk::new(ChunkType::from_str("tIME").unwrap(), data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let time = Timestamp::from_str("2024-02-29T23:59:60Z").unwrap();
+        assert_eq!(Timestamp::try_from(time.to_chunk().data()), Ok(time));
+        assert_eq!(time.to_string(), "2024-02-29 23:59:60 UTC");
+        assert_eq!(Timestamp::from_str("2024-02-29 23:59:60"), Ok(time));
+    }
+
+    #[test]
+    fn test_invalid_time() {
+        assert!(Timestamp::from_str("2023-02-29T00:00:00").is_err());
+        assert!(Timestamp::from_str("1900-02-29T00:00:00").is_err());
+        assert!(Timestamp::from_str("2000-02-29T00:00:00").is_ok());
+        assert!(Timestamp::from_str("2024-04-31T00:00:00").is_err());
+        assert!(Timestamp::from_str("2024-13-01T00:00:00").is_err());
+        assert!(Timestamp::from_str("2024-01-01T24:00:00").is_err());
+        assert!(Timestamp::from_str("2024-01-01").is_err());
+        assert!(Timestamp::from_str("2024-01-+1T00:00:00").is_err());
+        assert_eq!(Timestamp::try_from(&[0; 6][..]), Err(TimeError::InvalidLength(6)));
+    }
+
+    #[test]
+    fn test_from_unix() {
+        assert_eq!(Timestamp::from_unix(0).to_string(), "1970-01-01 00:00:00 UTC");
+        assert_eq!(Timestamp::from_unix(951782400).to_string(), "2000-02-29 00:00:00 UTC");
+        assert_eq!(Timestamp::from_unix(1709251199).to_string(), "2024-02-29 23:59:59 UTC");
+        assert!(Timestamp::now().year >= 2024);
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt::{Display, Formatter};
+
+/// A file format recognised at the start of the data after `IEND`, which is
+/// where archives and documents get appended to images.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrailerKind {
+    Zip,
+    Rar,
+    SevenZip,
+    Pdf,
+}
+
+impl TrailerKind {
+    const SIGNATURES: [(&'static [u8], TrailerKind); 6] = [
+        (b"PK\x03\x04", TrailerKind::Zip),
+        // An empty archive is just its end of central directory record.
+        (b"PK\x05\x06", TrailerKind::Zip),
+        (b"Rar!\x1a\x07\x00", TrailerKind::Rar),
+        (b"Rar!\x1a\x07\x01\x00", TrailerKind::Rar),
+        (b"7z\xbc\xaf\x27\x1c", TrailerKind::SevenZip),
+        (b"%PDF-", TrailerKind::Pdf),
+    ];
+
+    pub fn detect(data: &[u8]) -> Option<TrailerKind> {
+        TrailerKind::SIGNATURES
+            .iter()
+            .find(|(signature, _)| data.starts_with(signature))
+            .map(|(_, kind)| *kind)
+    }
+
+    /// The usual file extension, without the dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TrailerKind::Zip => "zip",
+            TrailerKind::Rar => "rar",
+            TrailerKind::SevenZip => "7z",
+            TrailerKind::Pdf => "pdf",
+        }
+    }
+}
+
+impl Display for TrailerKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrailerKind::Zip => write!(f, "zip archive"),
+            TrailerKind::Rar => write!(f, "rar archive"),
+            TrailerKind::SevenZip => write!(f, "7z archive"),
+            TrailerKind::Pdf => write!(f, "pdf document"),
+        }
+    }
+}
+
+/// One line describing a trailer for `print`.
+pub fn describe(trailer: &[u8], offset: usize) -> String {
+    match TrailerKind::detect(trailer) {
+        Some(kind) => format!("{} bytes after IEND at offset {} ({})", trailer.len(), offset, kind),
+        None => format!("{} bytes after IEND at offset {}", trailer.len(), offset),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(TrailerKind::detect(b"PK\x03\x04\x14\x00"), Some(TrailerKind::Zip));
+        assert_eq!(TrailerKind::detect(b"Rar!\x1a\x07\x01\x00"), Some(TrailerKind::Rar));
+        assert_eq!(TrailerKind::detect(b"7z\xbc\xaf\x27\x1c\x00\x04"), Some(TrailerKind::SevenZip));
+        assert_eq!(TrailerKind::detect(b"%PDF-1.7"), Some(TrailerKind::Pdf));
+        assert_eq!(TrailerKind::detect(b"PK"), None);
+        assert_eq!(TrailerKind::detect(b""), None);
+        assert_eq!(describe(b"%PDF-1.7", 100), "8 bytes after IEND at offset 100 (pdf document)");
+        assert_eq!(describe(b"junk", 100), "4 bytes after IEND at offset 100");
+    }
+}
@@ -0,0 +1,432 @@
+use std::fmt::{Display, Formatter};
+use crate::container::{self, Container, Format, Segment};
+use crate::Error;
+
+/// Why a byte slice could not be read as a `Webp`, or a chunk could not be
+/// found in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WebpError {
+    /// The file does not start with a `RIFF....WEBP` header.
+    NotWebp,
+    /// The chunk starting at `offset` runs past the end of the RIFF data.
+    Truncated { offset: usize },
+    /// There is no `VP8 `/`VP8L` bitstream to take the canvas size from.
+    MissingBitstream,
+    /// There is no chunk with the requested FourCC.
+    ChunkNotFound(String),
+    /// The bitstream gives a zero width or height, which `VP8X` cannot hold.
+    EmptyCanvas { width: u32, height: u32 },
+}
+
+impl Display for WebpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebpError::NotWebp => write!(f, "not a webp: bad RIFF header"),
+            WebpError::Truncated { offset } => write!(f, "riff chunk at offset {} is truncated", offset),
+            WebpError::MissingBitstream => write!(f, "webp has no VP8/VP8L bitstream"),
+            WebpError::ChunkNotFound(fourcc) => write!(f, "no {} chunk found", fourcc),
+            WebpError::EmptyCanvas { width, height } => write!(f, "webp canvas is {}x{}", width, height),
+        }
+    }
+}
+
+impl std::error::Error for WebpError {}
+
+/// A RIFF chunk. The pad byte after odd-sized data is implied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RiffChunk {
+    fourcc: [u8; 4],
+    data: Vec<u8>,
+}
+
+impl RiffChunk {
+    pub fn new(fourcc: [u8; 4], data: Vec<u8>) -> RiffChunk {
+        RiffChunk { fourcc, data }
+    }
+
+    pub fn fourcc(&self) -> [u8; 4] {
+        self.fourcc
+    }
+
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.fourcc).to_string()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Size on disk: header, data and pad byte.
+    pub fn padded_len(&self) -> usize {
+        8 + self.data.len() + self.data.len() % 2
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = self.fourcc.to_vec();
+        ret.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        ret.extend_from_slice(&self.data);
+        if self.data.len() % 2 == 1 {
+            ret.push(0);
+        }
+        ret
+    }
+}
+
+/// A WebP file as the chunks inside its `RIFF`/`WEBP` header, plus any
+/// bytes found after the RIFF data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Webp {
+    chunks: Vec<RiffChunk>,
+    trailer: Vec<u8>,
+}
+
+impl TryFrom<&[u8]> for Webp {
+    type Error = WebpError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() < 12 || &value[0..4] != b"RIFF" || &value[8..12] != b"WEBP" {
+            return Err(WebpError::NotWebp);
+        }
+        let riff_end = 8 + u32::from_le_bytes(value[4..8].try_into().unwrap()) as usize;
+        if riff_end > value.len() {
+            return Err(WebpError::Truncated { offset: 0 });
+        }
+        let mut chunks = vec![];
+        let mut idx = 12;
+        while idx < riff_end {
+            if idx + 8 > riff_end {
+                return Err(WebpError::Truncated { offset: idx });
+            }
+            let fourcc = <[u8; 4]>::try_from(&value[idx..idx + 4]).unwrap();
+            let size = u32::from_le_bytes(value[idx + 4..idx + 8].try_into().unwrap()) as usize;
+            let end = idx + 8 + size;
+            if end > riff_end {
+                return Err(WebpError::Truncated { offset: idx });
+            }
+            chunks.push(RiffChunk::new(fourcc, value[idx + 8..end].to_vec()));
+            // the pad byte may be missing on the very last chunk of sloppy encoders
+            idx = (end + size % 2).min(riff_end);
+        }
+        Ok(Webp { chunks, trailer: value[riff_end..].to_vec() })
+    }
+}
+
+impl Webp {
+    pub const FLAG_ICC: u8 = 0x20;
+    pub const FLAG_ALPHA: u8 = 0x10;
+    pub const FLAG_EXIF: u8 = 0x08;
+    pub const FLAG_XMP: u8 = 0x04;
+    pub const FLAG_ANIMATION: u8 = 0x02;
+
+    pub fn from_chunks(chunks: Vec<RiffChunk>) -> Webp {
+        Webp { chunks, trailer: vec![] }
+    }
+
+    pub fn chunks(&self) -> &[RiffChunk] {
+        &self.chunks
+    }
+
+    pub fn chunk_by_fourcc(&self, fourcc: &[u8; 4]) -> Option<&RiffChunk> {
+        self.chunks.iter().find(|chunk| &chunk.fourcc == fourcc)
+    }
+
+    pub fn is_extended(&self) -> bool {
+        self.chunks.first().map(|chunk| &chunk.fourcc == b"VP8X").unwrap_or(false)
+    }
+
+    /// The VP8X feature flags, or `None` for the simple formats.
+    pub fn flags(&self) -> Option<u8> {
+        self.chunks
+            .first()
+            .filter(|chunk| &chunk.fourcc == b"VP8X")
+            .and_then(|chunk| chunk.data.first().copied())
+    }
+
+    /// Canvas width and height, from VP8X or the image bitstream.
+    pub fn canvas_size(&self) -> Option<(u32, u32)> {
+        if let Some(vp8x) = self.chunk_by_fourcc(b"VP8X").filter(|chunk| chunk.data.len() >= 10) {
+            let d = &vp8x.data;
+            let width = u32::from_le_bytes([d[4], d[5], d[6], 0]) + 1;
+            let height = u32::from_le_bytes([d[7], d[8], d[9], 0]) + 1;
+            return Some((width, height));
+        }
+        if let Some(vp8) = self.chunk_by_fourcc(b"VP8 ").filter(|chunk| chunk.data.len() >= 10) {
+            let d = &vp8.data;
+            if d[3..6] != [0x9D, 0x01, 0x2A] {
+                return None;
+            }
+            let width = u16::from_le_bytes([d[6], d[7]]) & 0x3FFF;
+            let height = u16::from_le_bytes([d[8], d[9]]) & 0x3FFF;
+            return Some((width as u32, height as u32));
+        }
+        if let Some(vp8l) = self.chunk_by_fourcc(b"VP8L").filter(|chunk| chunk.data.len() >= 5) {
+            let d = &vp8l.data;
+            if d[0] != 0x2F {
+                return None;
+            }
+            let bits = u32::from_le_bytes([d[1], d[2], d[3], d[4]]);
+            return Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1));
+        }
+        None
+    }
+
+    fn has_alpha(&self) -> bool {
+        self.chunks.iter().any(|chunk| {
+            &chunk.fourcc == b"ALPH"
+                || (&chunk.fourcc == b"VP8L"
+                    && chunk.data.len() >= 5
+                    && u32::from_le_bytes([chunk.data[1], chunk.data[2], chunk.data[3], chunk.data[4]]) & (1 << 28) != 0)
+        })
+    }
+
+    /// Turns a simple (VP8/VP8L only) file into the extended format, which
+    /// is required before any other chunk may be added.
+    pub fn make_extended(&mut self) -> Result<(), WebpError> {
+        if self.is_extended() {
+            return Ok(());
+        }
+        let (width, height) = self.canvas_size().ok_or(WebpError::MissingBitstream)?;
+        // VP8X stores the size minus one
+        let (Some(width_minus_one), Some(height_minus_one)) = (width.checked_sub(1), height.checked_sub(1)) else {
+            return Err(WebpError::EmptyCanvas { width, height });
+        };
+        let mut data = vec![0; 4];
+        data.extend_from_slice(&width_minus_one.to_le_bytes()[..3]);
+        data.extend_from_slice(&height_minus_one.to_le_bytes()[..3]);
+        self.chunks.insert(0, RiffChunk::new(*b"VP8X", data));
+        self.update_flags();
+        Ok(())
+    }
+
+    /// Recomputes the VP8X feature flags from the chunks present.
+    pub fn update_flags(&mut self) {
+        if !self.is_extended() {
+            return;
+        }
+        let mut flags = self.chunks[0].data.first().copied().unwrap_or(0)
+            & !(Webp::FLAG_ICC | Webp::FLAG_ALPHA | Webp::FLAG_EXIF | Webp::FLAG_XMP | Webp::FLAG_ANIMATION);
+        for (fourcc, flag) in [(b"ICCP", Webp::FLAG_ICC), (b"EXIF", Webp::FLAG_EXIF),
+                               (b"XMP ", Webp::FLAG_XMP), (b"ANIM", Webp::FLAG_ANIMATION)] {
+            if self.chunk_by_fourcc(fourcc).is_some() {
+                flags |= flag;
+            }
+        }
+        if self.has_alpha() {
+            flags |= Webp::FLAG_ALPHA;
+        }
+        if let Some(first) = self.chunks[0].data.first_mut() {
+            *first = flags;
+        }
+    }
+
+    /// Adds a chunk, converting to the extended format if needed. `ICCP`
+    /// goes right after VP8X as the spec requires; anything else is
+    /// appended after the image data.
+    pub fn add_chunk(&mut self, chunk: RiffChunk) -> Result<(), WebpError> {
+        self.make_extended()?;
+        if &chunk.fourcc == b"ICCP" {
+            self.chunks.insert(1, chunk);
+        } else {
+            self.chunks.push(chunk);
+        }
+        self.update_flags();
+        Ok(())
+    }
+
+    /// Replaces the first chunk with the same FourCC, or adds it. Meant for
+    /// metadata such as `EXIF`, `XMP ` and `ICCP`.
+    pub fn set_chunk(&mut self, chunk: RiffChunk) -> Result<(), WebpError> {
+        match self.chunks.iter_mut().find(|c| c.fourcc == chunk.fourcc) {
+            Some(existing) => {
+                *existing = chunk;
+                self.update_flags();
+                Ok(())
+            },
+            None => self.add_chunk(chunk),
+        }
+    }
+
+    /// Removes the first chunk with the given FourCC.
+    pub fn remove_chunk(&mut self, fourcc: &[u8; 4]) -> Result<RiffChunk, WebpError> {
+        let idx = self
+            .chunks
+            .iter()
+            .position(|chunk| &chunk.fourcc == fourcc)
+            .ok_or_else(|| WebpError::ChunkNotFound(String::from_utf8_lossy(fourcc).to_string()))?;
+        let chunk = self.chunks.remove(idx);
+        self.update_flags();
+        Ok(chunk)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let body: Vec<u8> = self.chunks.iter().flat_map(|chunk| chunk.as_bytes()).collect();
+        let mut ret = b"RIFF".to_vec();
+        ret.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+        ret.extend_from_slice(b"WEBP");
+        ret.extend_from_slice(&body);
+        ret.extend_from_slice(&self.trailer);
+        ret
+    }
+
+    fn summary(&self, chunk: &RiffChunk) -> Option<String> {
+        match &chunk.fourcc {
+            b"VP8X" => {
+                let (width, height) = self.canvas_size()?;
+                let flags = chunk.data.first()?;
+                let names: Vec<&str> = [(Webp::FLAG_ICC, "icc"), (Webp::FLAG_ALPHA, "alpha"), (Webp::FLAG_EXIF, "exif"),
+                                        (Webp::FLAG_XMP, "xmp"), (Webp::FLAG_ANIMATION, "animation")]
+                    .iter()
+                    .filter(|(flag, _)| flags & flag != 0)
+                    .map(|&(_, name)| name)
+                    .collect();
+                Some(format!("{}x{} canvas, flags: {}", width, height,
+                             if names.is_empty() { "none".to_string() } else { names.join(", ") }))
+            },
+            b"VP8 " => Some("lossy bitstream".to_string()),
+            b"VP8L" => Some("lossless bitstream".to_string()),
+            _ if chunk.fourcc.iter().all(|c| c.is_ascii_alphabetic()) && !chunk.fourcc[0].is_ascii_uppercase() => {
+                String::from_utf8(chunk.data.clone()).ok().map(|s| format!("\"{}\"", s))
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Container for Webp {
+    fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Webp::try_from(bytes)?)
+    }
+
+    fn format(&self) -> Format {
+        Format::WebP
+    }
+
+    fn segments(&self) -> Vec<Segment> {
+        let mut offset = 12;
+        let mut ret = vec![];
+        for chunk in &self.chunks {
+            ret.push(Segment {
+                offset,
+                name: chunk.name(),
+                length: chunk.data.len(),
+                summary: self.summary(chunk),
+            });
+            offset += chunk.padded_len();
+        }
+        ret
+    }
+
+    fn insert_message(&mut self, key: &str, message: &[u8]) -> Result<Vec<String>, Error> {
+        self.set_chunk(RiffChunk::new(container::parse_key(key)?, message.to_vec()))?;
+        Ok(vec![])
+    }
+
+    fn extract_message(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let fourcc = container::parse_key(key)?;
+        Ok(self.chunk_by_fourcc(&fourcc).map(|chunk| chunk.data.clone()))
+    }
+
+    fn remove_message(&mut self, key: &str) -> Result<Vec<String>, Error> {
+        self.remove_chunk(&container::parse_key(key)?)?;
+        Ok(vec![])
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webp_from_chunks(chunks: &[RiffChunk]) -> Vec<u8> {
+        Webp::from_chunks(chunks.to_vec()).as_bytes()
+    }
+
+    fn lossy_webp() -> Vec<u8> {
+        // frame tag, start code, 20x10 and an odd-sized tail to exercise padding
+        let vp8 = vec![0x30, 0x01, 0x00, 0x9D, 0x01, 0x2A, 20, 0, 10, 0, 0xAA];
+        webp_from_chunks(&[RiffChunk::new(*b"VP8 ", vp8)])
+    }
+
+    fn lossless_webp() -> Vec<u8> {
+        // 3x2 with the alpha hint bit set
+        let bits: u32 = 2 | (1 << 14) | (1 << 28);
+        let mut vp8l = vec![0x2F];
+        vp8l.extend_from_slice(&bits.to_le_bytes());
+        vp8l.push(0);
+        webp_from_chunks(&[RiffChunk::new(*b"VP8L", vp8l)])
+    }
+
+    #[test]
+    fn test_parse_and_padding() {
+        let bytes = lossy_webp();
+        assert_eq!(bytes.len(), 12 + 8 + 11 + 1);
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize, bytes.len() - 8);
+        let webp = Webp::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(webp.chunks().len(), 1);
+        assert_eq!(webp.canvas_size(), Some((20, 10)));
+        assert!(!webp.is_extended());
+        assert_eq!(webp.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_invalid_webp() {
+        assert_eq!(Webp::try_from(&b"RIFF\0\0\0\0WAVE"[..]), Err(WebpError::NotWebp));
+        let bytes = lossy_webp();
+        assert_eq!(Webp::try_from(&bytes[..20]), Err(WebpError::Truncated { offset: 0 }));
+    }
+
+    #[test]
+    fn test_message_makes_file_extended() {
+        let mut webp = Webp::try_from(lossless_webp().as_slice()).unwrap();
+        webp.insert_message("ruSt", b"hey").unwrap();
+        let webp = Webp::try_from(webp.as_bytes().as_slice()).unwrap();
+
+        let names: Vec<String> = webp.chunks().iter().map(|chunk| chunk.name()).collect();
+        assert_eq!(names, vec!["VP8X", "VP8L", "ruSt"]);
+        assert_eq!(webp.canvas_size(), Some((3, 2)));
+        assert_eq!(webp.flags(), Some(Webp::FLAG_ALPHA));
+        assert_eq!(webp.extract_message("ruSt").unwrap(), Some(b"hey".to_vec()));
+
+        let mut webp = webp;
+        webp.insert_message("ruSt", b"again").unwrap();
+        let webp = Webp::try_from(webp.as_bytes().as_slice()).unwrap();
+        let names: Vec<String> = webp.chunks().iter().map(|chunk| chunk.name()).collect();
+        assert_eq!(names, vec!["VP8X", "VP8L", "ruSt"]);
+        assert_eq!(webp.extract_message("ruSt").unwrap(), Some(b"again".to_vec()));
+    }
+
+    #[test]
+    fn test_empty_canvas() {
+        let vp8 = vec![0x30, 0x01, 0x00, 0x9D, 0x01, 0x2A, 0, 0, 10, 0];
+        let mut webp = Webp::try_from(webp_from_chunks(&[RiffChunk::new(*b"VP8 ", vp8)]).as_slice()).unwrap();
+        assert_eq!(webp.make_extended(), Err(WebpError::EmptyCanvas { width: 0, height: 10 }));
+        assert!(webp.insert_message("ruSt", b"hey").is_err());
+    }
+
+    #[test]
+    fn test_metadata_updates_flags() {
+        let mut webp = Webp::try_from(lossy_webp().as_slice()).unwrap();
+        webp.set_chunk(RiffChunk::new(*b"EXIF", b"II*\0".to_vec())).unwrap();
+        webp.set_chunk(RiffChunk::new(*b"XMP ", b"<x/>".to_vec())).unwrap();
+        webp.set_chunk(RiffChunk::new(*b"ICCP", vec![0; 3])).unwrap();
+        assert_eq!(webp.flags(), Some(Webp::FLAG_EXIF | Webp::FLAG_XMP | Webp::FLAG_ICC));
+        assert_eq!(webp.chunks()[1].name(), "ICCP");
+
+        webp.remove_chunk(b"EXIF").unwrap();
+        assert_eq!(webp.flags(), Some(Webp::FLAG_XMP | Webp::FLAG_ICC));
+        assert_eq!(webp.remove_chunk(b"EXIF"), Err(WebpError::ChunkNotFound("EXIF".to_string())));
+    }
+
+    #[test]
+    fn test_remove_message() {
+        let mut webp = Webp::try_from(lossy_webp().as_slice()).unwrap();
+        webp.insert_message("ruSt", b"odd").unwrap();
+        let mut webp = Webp::try_from(webp.as_bytes().as_slice()).unwrap();
+        webp.remove_message("ruSt").unwrap();
+        assert_eq!(webp.extract_message("ruSt").unwrap(), None);
+        assert!(webp.remove_message("ruSt").is_err());
+    }
+}
@@ -0,0 +1,452 @@
+use std::fmt::{Display, Formatter};
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use crate::png::Png;
+use crate::text::{InternationalText, TextError};
+
+/// The `iTXt` keyword XMP packets are stored under.
+pub const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+
+const RDF_NS: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+
+/// Namespaces that can be used without declaring them first.
+const KNOWN_NAMESPACES: &[(&str, &str)] = &[
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("xmpRights", "http://ns.adobe.com/xap/1.0/rights/"),
+    ("xmpMM", "http://ns.adobe.com/xap/1.0/mm/"),
+    ("photoshop", "http://ns.adobe.com/photoshop/1.0/"),
+    ("tiff", "http://ns.adobe.com/tiff/1.0/"),
+    ("exif", "http://ns.adobe.com/exif/1.0/"),
+    ("Iptc4xmpCore", "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/"),
+];
+
+/// Standard properties that hold arrays or alternatives rather than text.
+const LANG_ALT_PROPERTIES: &[&str] = &["dc:title", "dc:description", "dc:rights", "xmpRights:UsageTerms"];
+const SEQ_PROPERTIES: &[&str] = &["dc:creator", "dc:date"];
+const BAG_PROPERTIES: &[&str] = &["dc:subject", "dc:contributor", "dc:publisher", "dc:language", "dc:type"];
+
+/// Whitespace after the packet lets other tools edit it in place.
+const PADDING: usize = 2048;
+
+/// Why an XMP packet could not be read or edited.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum XmpError {
+    /// The packet is not well-formed XML.
+    InvalidXml(String),
+    /// The `iTXt` chunk holding the packet could not be read.
+    Text(TextError),
+    /// Property names are `prefix:name`.
+    InvalidName(String),
+    /// The prefix is neither declared in the packet nor a standard one.
+    UnknownNamespace(String),
+}
+
+impl Display for XmpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XmpError::InvalidXml(e) => write!(f, "invalid xmp packet: {}", e),
+            XmpError::Text(e) => write!(f, "{}", e),
+            XmpError::InvalidName(name) => write!(f, "xmp property {:?} must look like prefix:name", name),
+            XmpError::UnknownNamespace(prefix) => write!(f, "unknown xmp namespace prefix {:?}", prefix),
+        }
+    }
+}
+
+impl std::error::Error for XmpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            XmpError::Text(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<TextError> for XmpError {
+    fn from(e: TextError) -> Self {
+        XmpError::Text(e)
+    }
+}
+
+impl From<quick_xml::Error> for XmpError {
+    fn from(e: quick_xml::Error) -> Self {
+        XmpError::InvalidXml(e.to_string())
+    }
+}
+
+impl From<quick_xml::events::attributes::AttrError> for XmpError {
+    fn from(e: quick_xml::events::attributes::AttrError) -> Self {
+        XmpError::InvalidXml(e.to_string())
+    }
+}
+
+/// A property value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum XmpValue {
+    Text(String),
+    /// An ordered array (`rdf:Seq`).
+    Seq(Vec<String>),
+    /// An unordered array (`rdf:Bag`).
+    Bag(Vec<String>),
+    /// Alternatives by language (`rdf:Alt`), `x-default` first.
+    Alt(Vec<(String, String)>),
+    /// A structure or qualified value we keep as its original XML.
+    Raw(String),
+}
+
+impl Display for XmpValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XmpValue::Text(text) => write!(f, "{}", text),
+            XmpValue::Seq(items) | XmpValue::Bag(items) => write!(f, "{}", items.join("; ")),
+            XmpValue::Alt(items) => {
+                let parts: Vec<String> = items.iter().map(|(lang, text)| format!("[{}] {}", lang, text)).collect();
+                write!(f, "{}", parts.join("; "))
+            },
+            XmpValue::Raw(xml) => write!(f, "{}", xml),
+        }
+    }
+}
+
+impl XmpValue {
+    /// The value a standard property expects for `values`: a language
+    /// alternative for `dc:title`, a sequence for `dc:creator` and so on.
+    /// Other properties get text, or a sequence for several values.
+    pub fn for_property(name: &str, values: &[String]) -> XmpValue {
+        if LANG_ALT_PROPERTIES.contains(&name) {
+            XmpValue::Alt(vec![("x-default".to_string(), values.join(" "))])
+        } else if SEQ_PROPERTIES.contains(&name) {
+            XmpValue::Seq(values.to_vec())
+        } else if BAG_PROPERTIES.contains(&name) {
+            XmpValue::Bag(values.to_vec())
+        } else if values.len() == 1 {
+            XmpValue::Text(values[0].clone())
+        } else {
+            XmpValue::Seq(values.to_vec())
+        }
+    }
+}
+
+/// An XMP packet as its namespace declarations and a flat list of
+/// top-level properties, named `prefix:name`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Xmp {
+    namespaces: Vec<(String, String)>,
+    properties: Vec<(String, XmpValue)>,
+}
+
+fn name_of(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.name().as_ref()).to_string()
+}
+
+/// Reads the value of a property element from its inner XML, or `None` if
+/// it is not text or a simple array.
+fn parse_value(inner: &str) -> Result<Option<XmpValue>, XmpError> {
+    let mut reader = Reader::from_str(inner);
+    reader.config_mut().trim_text(true);
+    let mut text = String::new();
+    let mut array: Option<String> = None;
+    let mut items = vec![];
+    let mut depth = 0;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                depth += 1;
+                let name = name_of(&e);
+                match (depth, name.as_str()) {
+                    (1, "rdf:Seq" | "rdf:Bag" | "rdf:Alt") if e.attributes().next().is_none() => array = Some(name),
+                    (2, "rdf:li") if array.is_some() => {
+                        let mut lang = "x-default".to_string();
+                        for attr in e.attributes() {
+                            let attr = attr?;
+                            match attr.key.as_ref() {
+                                b"xml:lang" => lang = attr.unescape_value()?.to_string(),
+                                _ => return Ok(None),
+                            }
+                        }
+                        let item = reader.read_text(e.name())?;
+                        if item.contains('<') {
+                            return Ok(None);
+                        }
+                        items.push((lang, quick_xml::escape::unescape(&item).map_err(|e| XmpError::InvalidXml(e.to_string()))?.to_string()));
+                        depth -= 1;
+                    },
+                    _ => return Ok(None),
+                }
+            },
+            Event::Empty(_) => return Ok(None),
+            Event::End(_) => depth -= 1,
+            Event::Text(t) if depth == 0 => text.push_str(&t.unescape()?),
+            Event::Text(_) => return Ok(None),
+            Event::Eof => break,
+            _ => {},
+        }
+    }
+    let values = || items.iter().map(|(_, text)| text.clone()).collect();
+    Ok(Some(match array.as_deref() {
+        Some("rdf:Seq") => XmpValue::Seq(values()),
+        Some("rdf:Bag") => XmpValue::Bag(values()),
+        Some(_) => XmpValue::Alt(items),
+        None => XmpValue::Text(text),
+    }))
+}
+
+impl Xmp {
+    pub fn new() -> Xmp {
+        Xmp::default()
+    }
+
+    /// Parses the properties of every `rdf:Description` in the packet, both
+    /// as attributes and as child elements.
+    pub fn parse(packet: &str) -> Result<Xmp, XmpError> {
+        let mut xmp = Xmp::new();
+        let mut reader = Reader::from_str(packet);
+        reader.config_mut().trim_text(true);
+        let mut in_description = false;
+        loop {
+            let start = reader.buffer_position() as usize;
+            let event = reader.read_event()?;
+            match event {
+                Event::Start(ref e) | Event::Empty(ref e) if !in_description => {
+                    let name = name_of(e);
+                    for attr in e.attributes() {
+                        let attr = attr?;
+                        let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+                        let value = attr.unescape_value()?.to_string();
+                        if let Some(prefix) = key.strip_prefix("xmlns:") {
+                            if !matches!(prefix, "x" | "rdf") {
+                                xmp.declare_namespace(prefix, &value);
+                            }
+                        } else if name == "rdf:Description" && key.contains(':') && !key.starts_with("rdf:") && !key.starts_with("xml:") {
+                            xmp.properties.push((key, XmpValue::Text(value)));
+                        }
+                    }
+                    in_description = name == "rdf:Description" && matches!(event, Event::Start(_));
+                },
+                Event::Start(e) => {
+                    let name = name_of(&e);
+                    let has_attributes = e.attributes().next().is_some();
+                    let span = reader.read_to_end(e.name())?;
+                    let inner = &packet[span.start as usize..span.end as usize];
+                    let value = match has_attributes {
+                        false => parse_value(inner)?,
+                        true => None,
+                    };
+                    let raw = || XmpValue::Raw(packet[start..reader.buffer_position() as usize].trim().to_string());
+                    xmp.properties.push((name, value.unwrap_or_else(raw)));
+                },
+                Event::Empty(e) => {
+                    let raw = packet[start..reader.buffer_position() as usize].trim().to_string();
+                    xmp.properties.push((name_of(&e), XmpValue::Raw(raw)));
+                },
+                Event::End(e) if e.name().as_ref() == b"rdf:Description" => in_description = false,
+                Event::Eof => break,
+                _ => {},
+            }
+        }
+        Ok(xmp)
+    }
+
+    /// Reads the packet from the PNG's XMP `iTXt` chunk, if there is one.
+    pub fn from_png(png: &Png) -> Result<Option<Xmp>, XmpError> {
+        match xmp_chunk_index(png) {
+            Some(idx) => {
+                let text = InternationalText::try_from(png.chunks()[idx].data())?;
+                Ok(Some(Xmp::parse(&text.text)?))
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Stores the packet in the PNG's XMP `iTXt` chunk, replacing the old
+    /// one in place or adding one before `IEND`.
+    pub fn write_to_png(&self, png: &mut Png) {
+        let chunk = InternationalText::new(XMP_KEYWORD, &self.to_packet()).to_chunk();
+        match xmp_chunk_index(png) {
+            Some(idx) => {
+                png.remove_chunk_at(idx);
+                png.insert_chunk(idx, chunk);
+            },
+            None => png.append_chunk(chunk),
+        }
+    }
+
+    pub fn namespaces(&self) -> &[(String, String)] {
+        &self.namespaces
+    }
+
+    pub fn properties(&self) -> &[(String, XmpValue)] {
+        &self.properties
+    }
+
+    pub fn get(&self, name: &str) -> Option<&XmpValue> {
+        self.properties.iter().find(|(other, _)| other == name).map(|(_, value)| value)
+    }
+
+    pub fn declare_namespace(&mut self, prefix: &str, uri: &str) {
+        match self.namespaces.iter_mut().find(|(other, _)| other == prefix) {
+            Some(namespace) => namespace.1 = uri.to_string(),
+            None => self.namespaces.push((prefix.to_string(), uri.to_string())),
+        }
+    }
+
+    /// Sets a property, declaring a standard namespace if needed. Custom
+    /// prefixes must be declared with `declare_namespace` first.
+    pub fn set(&mut self, name: &str, value: XmpValue) -> Result<(), XmpError> {
+        let (prefix, local) = name.split_once(':').ok_or_else(|| XmpError::InvalidName(name.to_string()))?;
+        if prefix.is_empty() || local.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || "_-.:".contains(c)) {
+            return Err(XmpError::InvalidName(name.to_string()));
+        }
+        if !self.namespaces.iter().any(|(other, _)| other == prefix) {
+            let &(_, uri) = KNOWN_NAMESPACES
+                .iter()
+                .find(|(known, _)| *known == prefix)
+                .ok_or_else(|| XmpError::UnknownNamespace(prefix.to_string()))?;
+            self.declare_namespace(prefix, uri);
+        }
+        match self.properties.iter_mut().find(|(other, _)| other == name) {
+            Some(property) => property.1 = value,
+            None => self.properties.push((name.to_string(), value)),
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<XmpValue> {
+        let idx = self.properties.iter().position(|(other, _)| other == name)?;
+        Some(self.properties.remove(idx).1)
+    }
+
+    /// Serializes the packet with a single `rdf:Description` and trailing
+    /// padding, wrapped in `xpacket` processing instructions.
+    pub fn to_packet(&self) -> String {
+        let mut out = String::from("<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n");
+        out.push_str("<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n");
+        out.push_str(&format!(" <rdf:RDF xmlns:rdf=\"{}\">\n", RDF_NS));
+        out.push_str("  <rdf:Description rdf:about=\"\"");
+        for (prefix, uri) in &self.namespaces {
+            out.push_str(&format!("\n    xmlns:{}=\"{}\"", prefix, escape(uri.as_str())));
+        }
+        out.push_str(">\n");
+        for (name, value) in &self.properties {
+            let items = |kind: &str, items: &[(Option<&str>, &str)]| {
+                let mut out = format!("   <{}>\n    <rdf:{}>\n", name, kind);
+                for (lang, text) in items {
+                    match lang {
+                        Some(lang) => out.push_str(&format!("     <rdf:li xml:lang=\"{}\">{}</rdf:li>\n", escape(*lang), escape(*text))),
+                        None => out.push_str(&format!("     <rdf:li>{}</rdf:li>\n", escape(*text))),
+                    }
+                }
+                out.push_str(&format!("    </rdf:{}>\n   </{}>\n", kind, name));
+                out
+            };
+            match value {
+                XmpValue::Text(text) => out.push_str(&format!("   <{0}>{1}</{0}>\n", name, escape(text.as_str()))),
+                XmpValue::Seq(values) => {
+                    out.push_str(&items("Seq", &values.iter().map(|v| (None, v.as_str())).collect::<Vec<_>>()))
+                },
+                XmpValue::Bag(values) => {
+                    out.push_str(&items("Bag", &values.iter().map(|v| (None, v.as_str())).collect::<Vec<_>>()))
+                },
+                XmpValue::Alt(values) => {
+                    out.push_str(&items("Alt", &values.iter().map(|(l, v)| (Some(l.as_str()), v.as_str())).collect::<Vec<_>>()))
+                },
+                XmpValue::Raw(xml) => out.push_str(&format!("   {}\n", xml)),
+            }
+        }
+        out.push_str("  </rdf:Description>\n </rdf:RDF>\n</x:xmpmeta>\n");
+        for _ in 0..PADDING / 100 {
+            out.push_str(&" ".repeat(99));
+            out.push('\n');
+        }
+        out.push_str("<?xpacket end=\"w\"?>");
+        out
+    }
+}
+
+fn xmp_chunk_index(png: &Png) -> Option<usize> {
+    png.chunks().iter().position(|chunk| {
+        &chunk.chunk_type().bytes() == b"iTXt" && chunk.data().starts_with(XMP_KEYWORD.as_bytes())
+            && chunk.data().get(XMP_KEYWORD.len()) == Some(&0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    const PACKET: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:CreatorTool="rush &amp; co"/>
+  <rdf:Description rdf:about=""
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:xmpMM="http://ns.adobe.com/xap/1.0/mm/">
+   <dc:title><rdf:Alt><rdf:li xml:lang="x-default">Dice</rdf:li><rdf:li xml:lang="de">Würfel</rdf:li></rdf:Alt></dc:title>
+   <dc:creator><rdf:Seq><rdf:li>Qasak</rdf:li></rdf:Seq></dc:creator>
+   <dc:format>image/png</dc:format>
+   <xmpMM:DerivedFrom rdf:parseType="Resource"><xmpMM:documentID>abc</xmpMM:documentID></xmpMM:DerivedFrom>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+    #[test]
+    fn test_parse() {
+        let xmp = Xmp::parse(PACKET).unwrap();
+        assert_eq!(xmp.get("xmp:CreatorTool"), Some(&XmpValue::Text("rush & co".to_string())));
+        assert_eq!(xmp.get("dc:title"), Some(&XmpValue::Alt(vec![
+            ("x-default".to_string(), "Dice".to_string()),
+            ("de".to_string(), "Würfel".to_string()),
+        ])));
+        assert_eq!(xmp.get("dc:creator"), Some(&XmpValue::Seq(vec!["Qasak".to_string()])));
+        assert_eq!(xmp.get("dc:format"), Some(&XmpValue::Text("image/png".to_string())));
+        assert!(matches!(xmp.get("xmpMM:DerivedFrom"), Some(XmpValue::Raw(raw)) if raw.ends_with("</xmpMM:DerivedFrom>")));
+        assert_eq!(xmp.namespaces().len(), 3);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let xmp = Xmp::parse(PACKET).unwrap();
+        let packet = xmp.to_packet();
+        assert!(packet.ends_with("<?xpacket end=\"w\"?>"));
+        assert!(packet.len() > PADDING);
+        assert_eq!(Xmp::parse(&packet).unwrap(), xmp);
+    }
+
+    #[test]
+    fn test_set_and_remove() {
+        let mut xmp = Xmp::new();
+        xmp.set("dc:title", XmpValue::for_property("dc:title", &["Hello".to_string()])).unwrap();
+        assert_eq!(xmp.set("acme:Batch", XmpValue::Text("7".to_string())), Err(XmpError::UnknownNamespace("acme".to_string())));
+        xmp.declare_namespace("acme", "http://acme.example/ns/1.0/");
+        xmp.set("acme:Batch", XmpValue::Text("7 < 8".to_string())).unwrap();
+        assert_eq!(xmp.set("title", XmpValue::Text(String::new())), Err(XmpError::InvalidName("title".to_string())));
+
+        let parsed = Xmp::parse(&xmp.to_packet()).unwrap();
+        assert_eq!(parsed.get("acme:Batch"), Some(&XmpValue::Text("7 < 8".to_string())));
+        assert_eq!(parsed.get("dc:title").unwrap().to_string(), "[x-default] Hello");
+        let mut parsed = parsed;
+        assert!(parsed.remove("acme:Batch").is_some());
+        assert!(parsed.remove("acme:Batch").is_none());
+    }
+
+    #[test]
+    fn test_png() {
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ]);
+        assert_eq!(Xmp::from_png(&png), Ok(None));
+        let mut xmp = Xmp::new();
+        xmp.set("dc:format", XmpValue::Text("image/png".to_string())).unwrap();
+        xmp.write_to_png(&mut png);
+        xmp.set("dc:format", XmpValue::Text("image/apng".to_string())).unwrap();
+        xmp.write_to_png(&mut png);
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(Xmp::from_png(&png).unwrap(), Some(xmp));
+    }
+}